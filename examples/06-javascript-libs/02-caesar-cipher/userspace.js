@@ -3,7 +3,7 @@
  * A rot12 encoder/decoder example using an IIFE JavaScript
  * module implementing Caesar's cipher.
  *
- * Also, it uses a pure JavaScript ``querystring.parse()`` function.
+ * The form data is already decoded by the runtime as ``request.form``.
  *
  * This demonstrates the non-standard ``@fh:include``
  * directive to include external JavaScript code.
@@ -13,15 +13,10 @@
  *
 **/
 
-// @fh:include("./modquery.js")
 // @fh:include("./modcaesar.js")
 
-// Decode "x-www-form-urlencoded" form data.
-// https://morioh.com/p/480aef8e92cd
-let data = modquery.parse(request.body);
-
-// Use "payload" field;
-let payload = data.payload;
+// Use "payload" field of the "x-www-form-urlencoded" form data.
+let payload = request.form.payload[0];
 
 // Apply rot12 encoding/decoding to payload content.
 var encoded = modcaesar.rot12_encode(payload);
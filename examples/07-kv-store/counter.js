@@ -0,0 +1,6 @@
+// A basic example of the fh.kv key-value store: counts invocations across runs.
+
+const count = ((await fh.kv.get("counter")) || 0) + 1;
+await fh.kv.set("counter", count);
+
+await fh.log(`Count: ${count}`);
@@ -0,0 +1,10 @@
+// A basic example of the fh.kv key-value store: keys with a TTL, listing and deleting.
+
+await fh.kv.set("token:a", "secret-a", { ttl: 3600 });
+await fh.kv.set("token:b", "secret-b");
+await fh.kv.set("other", { "nested": true });
+
+await fh.log(JSON.stringify(await fh.kv.list("token:")));
+
+await fh.kv.delete("token:b");
+await fh.log(JSON.stringify(await fh.kv.list()));
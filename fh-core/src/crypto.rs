@@ -0,0 +1,83 @@
+//! Hashing and HMAC primitives, which back `crypto.subtle` in the processor
+//! runtime and are used for verifying webhook signatures.
+use anyhow::anyhow;
+use hmac::{Hmac, Mac, NewMac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::str::FromStr;
+
+/// Supported hash algorithms, named like in the WebCrypto API.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "SHA-1" => Ok(Self::Sha1),
+            "SHA-256" => Ok(Self::Sha256),
+            "SHA-384" => Ok(Self::Sha384),
+            "SHA-512" => Ok(Self::Sha512),
+            _ => Err(anyhow!("Unsupported hash algorithm '{}'", s)),
+        }
+    }
+}
+
+/// Runs `$body` with `$mac` bound to a fresh HMAC for the given algorithm.
+macro_rules! with_hmac {
+    ($algorithm:expr, $key:expr, |$mac:ident| $body:expr) => {
+        match $algorithm {
+            HashAlgorithm::Sha1 => {
+                let mut $mac = Hmac::<Sha1>::new_varkey($key).expect("HMAC takes keys of any size");
+                $body
+            }
+            HashAlgorithm::Sha256 => {
+                let mut $mac =
+                    Hmac::<Sha256>::new_varkey($key).expect("HMAC takes keys of any size");
+                $body
+            }
+            HashAlgorithm::Sha384 => {
+                let mut $mac =
+                    Hmac::<Sha384>::new_varkey($key).expect("HMAC takes keys of any size");
+                $body
+            }
+            HashAlgorithm::Sha512 => {
+                let mut $mac =
+                    Hmac::<Sha512>::new_varkey($key).expect("HMAC takes keys of any size");
+                $body
+            }
+        }
+    };
+}
+
+/// Computes the digest of `data`.
+pub fn digest(algorithm: HashAlgorithm, data: &[u8]) -> Vec<u8> {
+    match algorithm {
+        HashAlgorithm::Sha1 => Sha1::digest(data).to_vec(),
+        HashAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+        HashAlgorithm::Sha384 => Sha384::digest(data).to_vec(),
+        HashAlgorithm::Sha512 => Sha512::digest(data).to_vec(),
+    }
+}
+
+/// Computes the HMAC signature of `data` with the given key.
+pub fn hmac_sign(algorithm: HashAlgorithm, key: &[u8], data: &[u8]) -> Vec<u8> {
+    with_hmac!(algorithm, key, |mac| {
+        mac.update(data);
+        mac.finalize().into_bytes().to_vec()
+    })
+}
+
+/// Verifies the HMAC `signature` of `data` in constant time.
+pub fn hmac_verify(algorithm: HashAlgorithm, key: &[u8], data: &[u8], signature: &[u8]) -> bool {
+    with_hmac!(algorithm, key, |mac| {
+        mac.update(data);
+        mac.verify(signature).is_ok()
+    })
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// (De-)Serializable representation of an error, which occurred while running
+/// the code of a RequestProcessor. For uncaught JavaScript exceptions, the
+/// stack trace and source position are filled in, for other errors only the
+/// message is available.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScriptError {
+    pub message: String,
+    pub stack: Option<String>,
+    pub script_resource_name: Option<String>,
+    /// 1-based line number.
+    pub line_number: Option<i64>,
+    /// 1-based column number.
+    pub column_number: Option<i64>,
+    pub source_line: Option<String>,
+}
+
+impl ScriptError {
+    /// Creates a ScriptError, which only consists of a message.
+    pub fn new(message: String) -> Self {
+        Self {
+            message,
+            stack: None,
+            script_resource_name: None,
+            line_number: None,
+            column_number: None,
+            source_line: None,
+        }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let (Some(name), Some(line), Some(column)) = (
+            &self.script_resource_name,
+            self.line_number,
+            self.column_number,
+        ) {
+            write!(f, " at {}:{}:{}", name, line, column)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,24 +1,35 @@
+use crate::{metrics::Queued, telemetry::Traced};
 use sqlx::{pool::PoolConnection, Pool, Sqlite, SqlitePool};
 use std::{
     collections::HashMap,
     sync::{Arc, Mutex},
 };
-use tokio::sync::oneshot::error::RecvError;
 use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc::error::SendError, oneshot::error::RecvError};
 use warp::{
     http::{self, HeaderValue},
     hyper::HeaderMap,
     reject::Reject,
 };
 
+pub mod crypto;
+pub mod error;
+pub mod metrics;
+pub mod rate_limit;
 pub mod request;
 pub mod response;
+pub mod shutdown;
+pub mod telemetry;
 
 /// Generic type alias for oneshot Responders.
 pub type Responder<T> = oneshot::Sender<T>;
 
-/// Generic type alias for cross-thread clonable mpsc::Senders.
-pub type ReqSender<T> = Arc<Mutex<mpsc::Sender<T>>>;
+/// Generic type alias for cross-thread clonable mpsc::Senders. Commands are
+/// wrapped in [`Traced`] to carry the span of the sender.
+pub type ReqSender<T> = Arc<Mutex<mpsc::Sender<Traced<T>>>>;
+
+/// Generic type alias for the receiving end of a [`ReqSender`].
+pub type ReqReceiver<T> = mpsc::Receiver<Traced<T>>;
 
 /// Generic type alias for the SQLX DB Pool.
 pub type DbPool<T> = Pool<T>;
@@ -34,6 +45,56 @@ pub type DbType = Sqlite;
 /// Alias for the database connection.
 pub type DbConnection = PoolConnection<DbType>;
 
+/// Sends a command over the given [`ReqSender`] and awaits the response. The
+/// command is built by the `build` closure, which receives the [`Responder`]
+/// of a fresh [`tokio::sync::oneshot`] channel.
+///
+/// This is the non-warp counterpart of the command macros, used e.g. by
+/// background tasks, which are not bound to a HTTP request.
+pub async fn send_cmd<C, T, F>(tx: &ReqSender<C>, build: F) -> Result<T, anyhow::Error>
+where
+    C: Queued,
+    F: FnOnce(Responder<T>) -> C,
+{
+    let mut tx2 = tx
+        .lock()
+        .map_err(|e| anyhow::Error::msg(e.to_string()))?
+        .clone();
+
+    let (cmd_tx, cmd_rx) = oneshot::channel();
+    enqueue(&mut tx2, build(cmd_tx))
+        .await
+        .map_err(|_| anyhow::Error::msg("Unable to send command, the receiver is closed"))?;
+
+    Ok(cmd_rx.await?)
+}
+
+/// Sends the result of a command back to its sender. A sender, which stopped
+/// waiting in the meantime (e.g. because its client disconnected or a
+/// readiness check timed out), must not stop the receiving manager, so this
+/// is only logged.
+pub fn respond<T>(cmd_tx: Responder<T>, res: T) {
+    if cmd_tx.send(res).is_err() {
+        tracing::warn!("Unable to send response, the sender stopped waiting");
+    }
+}
+
+/// Sends a command over the given channel together with the current span and
+/// tracks the depth of its [`metrics::Queue`]. The receiving manager counts
+/// the command as dequeued.
+pub async fn enqueue<C: Queued>(
+    tx: &mut mpsc::Sender<Traced<C>>,
+    cmd: C,
+) -> Result<(), SendError<C>> {
+    C::QUEUE.enqueued();
+    tx.send(Traced::new(cmd))
+        .await
+        .map_err(|SendError(traced)| {
+            C::QUEUE.dequeued();
+            SendError(traced.into_inner())
+        })
+}
+
 /// Locking Error, used in the warp rejection handling.
 #[derive(Debug)]
 pub struct FhLockingError<T> {
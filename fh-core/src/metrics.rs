@@ -0,0 +1,128 @@
+//! Prometheus metrics, which are shared by all crates and exposed by fh-http
+//! on `/metrics`.
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec, Encoder,
+    HistogramVec, IntCounterVec, IntGaugeVec, TextEncoder,
+};
+
+lazy_static! {
+    /// Answered HTTP requests to RequestProcessors.
+    pub static ref HTTP_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "fh_http_requests_total",
+        "Answered HTTP requests to RequestProcessors",
+        &["processor_id", "status"]
+    )
+    .expect("valid metric");
+
+    /// Latency of HTTP requests to RequestProcessors.
+    pub static ref HTTP_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "fh_http_request_duration_seconds",
+        "Latency of HTTP requests to RequestProcessors",
+        &["processor_id"]
+    )
+    .expect("valid metric");
+
+    /// Time spent executing the code of RequestProcessors in V8.
+    pub static ref V8_EXECUTION_DURATION: HistogramVec = register_histogram_vec!(
+        "fh_v8_execution_duration_seconds",
+        "Time spent executing the code of RequestProcessors in V8",
+        &["processor_id"]
+    )
+    .expect("valid metric");
+
+    /// Outbound requests, which received a response.
+    pub static ref OUTBOUND_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "fh_outbound_requests_total",
+        "Outbound requests, which received a response",
+        &["host", "status"]
+    )
+    .expect("valid metric");
+
+    /// Latency of outbound requests, including failed ones.
+    pub static ref OUTBOUND_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "fh_outbound_request_duration_seconds",
+        "Latency of outbound requests",
+        &["host"]
+    )
+    .expect("valid metric");
+
+    /// Outbound requests, which failed without a response, e.g. because the
+    /// host was not reachable.
+    pub static ref OUTBOUND_REQUEST_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "fh_outbound_request_errors_total",
+        "Outbound requests, which failed without a response",
+        &["host"]
+    )
+    .expect("valid metric");
+
+    /// Commands waiting in the channels to the managers.
+    pub static ref QUEUE_DEPTH: IntGaugeVec = register_int_gauge_vec!(
+        "fh_queue_depth",
+        "Commands waiting in the channels to the managers",
+        &["queue"]
+    )
+    .expect("valid metric");
+
+    /// Requests, which were rejected by a rate limit.
+    pub static ref RATE_LIMITED: IntCounterVec = register_int_counter_vec!(
+        "fh_rate_limited_total",
+        "Requests, which were rejected by a rate limit",
+        &["scope"]
+    )
+    .expect("valid metric");
+
+    /// Written audit items.
+    pub static ref AUDIT_ITEMS: IntCounterVec = register_int_counter_vec!(
+        "fh_audit_items_total",
+        "Written audit items",
+        &["kind"]
+    )
+    .expect("valid metric");
+}
+
+/// Channels to the managers, whose depth is tracked in [`QUEUE_DEPTH`].
+#[derive(Debug, Clone, Copy)]
+pub enum Queue {
+    /// Channel of `ReqCmd`s to the database manager.
+    Db,
+    /// Channel of `ProcessorCmd`s to the request processing manager.
+    Processor,
+}
+
+impl Queue {
+    fn label(self) -> &'static str {
+        match self {
+            Queue::Db => "db",
+            Queue::Processor => "processor",
+        }
+    }
+
+    /// Counts a command, which was sent to the queue.
+    pub fn enqueued(self) {
+        QUEUE_DEPTH.with_label_values(&[self.label()]).inc();
+    }
+
+    /// Counts a command, which was received from the queue.
+    pub fn dequeued(self) {
+        QUEUE_DEPTH.with_label_values(&[self.label()]).dec();
+    }
+
+    /// Commands currently waiting in the queue.
+    pub fn depth(self) -> i64 {
+        QUEUE_DEPTH.with_label_values(&[self.label()]).get()
+    }
+}
+
+/// Commands, which are sent over a tracked [`Queue`].
+pub trait Queued {
+    const QUEUE: Queue;
+}
+
+/// Renders all metrics in the Prometheus text format.
+pub fn gather() -> Result<String, anyhow::Error> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+
+    Ok(String::from_utf8(buffer)?)
+}
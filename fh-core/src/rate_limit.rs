@@ -0,0 +1,120 @@
+//! Token bucket rate limiting. Each key (e.g. a RequestProcessor, a client or
+//! a target host) gets its own bucket, which holds up to `burst` tokens and is
+//! refilled with `rate` tokens per second. Every request takes one token.
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Number of buckets, from which on full buckets are dropped. A full bucket
+/// behaves exactly like a missing one, so this only bounds the memory used.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Configuration of a token bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// Tokens, which are refilled per second.
+    pub rate: f64,
+    /// Maximum number of tokens, i.e. requests which may be sent at once.
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Rejects limits, which would never allow a request.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.rate.is_finite() || self.rate <= 0.0 {
+            bail!("The rate of a rate limit must be greater than 0");
+        }
+        if self.burst == 0 {
+            bail!("The burst of a rate limit must be greater than 0");
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses `<rate>` or `<rate>,<burst>`, e.g. `10,20`. Without a burst, as many
+/// requests as are refilled per second may be sent at once.
+impl FromStr for RateLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ',').map(str::trim);
+        let rate: f64 = parts.next().unwrap_or("").parse().context("Invalid rate")?;
+        let burst = match parts.next() {
+            Some(burst) => burst.parse().context("Invalid burst")?,
+            None => rate.ceil().max(1.0) as u32,
+        };
+
+        let limit = Self { rate, burst };
+        limit.validate()?;
+
+        Ok(limit)
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Token buckets of all keys, which share the same [`RateLimit`].
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    limit: RateLimit,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token from the bucket of `key`. If the bucket is empty, the
+    /// time until the next token is available is returned as error.
+    pub fn check(&self, key: K) -> Result<(), Duration> {
+        let now = Instant::now();
+        // a poisoned lock only means, that another check panicked; the
+        // buckets themselves are still consistent
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            let limit = self.limit;
+            buckets.retain(|_, bucket| refill(bucket, limit, now) < f64::from(limit.burst));
+        }
+
+        let burst = f64::from(self.limit.burst);
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: burst,
+            updated_at: now,
+        });
+
+        if refill(bucket, self.limit, now) >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.limit.rate,
+            ))
+        }
+    }
+}
+
+/// Adds the tokens refilled since the last update and returns the new amount.
+fn refill(bucket: &mut Bucket, limit: RateLimit, now: Instant) -> f64 {
+    let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+    bucket.tokens = (bucket.tokens + elapsed * limit.rate).min(f64::from(limit.burst));
+    bucket.updated_at = now;
+
+    bucket.tokens
+}
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::{collections::HashMap, convert::TryFrom};
+use url::form_urlencoded;
+use uuid::Uuid;
 use warp::http;
 
 use crate::{response::Response, try_header_map_to_hashmap, version_to_string};
@@ -10,8 +13,54 @@ use crate::{response::Response, try_header_map_to_hashmap, version_to_string};
 pub struct RequestSpec {
     pub request: Request,
     pub url: String,
+    /// Headers, which are sent with the request. The headers of `request`
+    /// are never sent, as it is often the incoming request, which carries
+    /// credentials of the client like `Authorization` or `Cookie`.
+    #[serde(default)]
+    pub headers: HashMap<String, Vec<String>>,
+    /// If set, failed deliveries are queued and retried in the background.
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
+    /// If set, the received body is additionally passed base64 encoded as
+    /// `raw_body`, e.g. for `fetch`, which exposes the bytes.
+    #[serde(default)]
+    pub raw: bool,
+}
+
+/// Describes, how often and how fast a failed outbound request is retried. The
+/// delay between two attempts doubles with every attempt, starting with
+/// `backoff_ms`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RetryPolicy {
+    #[serde(default = "RetryPolicy::default_max_attempts")]
+    pub max_attempts: i32,
+    #[serde(default = "RetryPolicy::default_backoff_ms")]
+    pub backoff_ms: i64,
 }
 
+impl RetryPolicy {
+    fn default_max_attempts() -> i32 {
+        5
+    }
+
+    fn default_backoff_ms() -> i64 {
+        1000
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: Self::default_max_attempts(),
+            backoff_ms: Self::default_backoff_ms(),
+        }
+    }
+}
+
+/// Type alias for parsed query parameters and form fields. Like headers, every
+/// name may occur multiple times.
+pub type Params = HashMap<String, Vec<String>>;
+
 /// (De-)Serializable representation of a HTTP Request.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Request {
@@ -21,6 +70,141 @@ pub struct Request {
     pub path: String,
     pub query: Option<String>,
     pub version: String,
+    /// Parsed `query`.
+    #[serde(default)]
+    pub query_params: Params,
+    /// Parsed `body`, if the content type is JSON and the body is valid.
+    #[serde(default)]
+    pub json: Option<Value>,
+    /// Parsed `body`, if the content type is
+    /// `application/x-www-form-urlencoded` or `multipart/form-data`.
+    #[serde(default)]
+    pub form: Option<Params>,
+    /// IP address of the client. Behind trusted proxies, this is taken from
+    /// `X-Forwarded-For`.
+    #[serde(default)]
+    pub remote_addr: Option<String>,
+    /// Whether the request was received from a trusted proxy, whose headers
+    /// may be honoured. Not exposed to the code of RequestProcessors.
+    #[serde(skip)]
+    pub trusted_proxy: bool,
+    /// Host, the client sent the request to, including the port if given.
+    #[serde(default)]
+    pub host: Option<String>,
+    /// Either `http` or `https`, as seen by the client.
+    #[serde(default)]
+    pub scheme: Option<String>,
+    /// Id of the RequestProcessor, which handles this request.
+    #[serde(default)]
+    pub processor_id: Option<Uuid>,
+    /// Values of the parameters in the path pattern of a custom route, like
+    /// `device` in `/hooks/ttn/{device}`.
+    #[serde(default)]
+    pub path_params: HashMap<String, String>,
+}
+
+impl Request {
+    /// Returns the first value of the given header, ignoring its case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .and_then(|(_, values)| values.first())
+            .map(|v| v.as_str())
+    }
+
+    /// Fills `query_params`, `json` and `form` from the raw query and body.
+    pub fn parse(&mut self) {
+        self.query_params = self
+            .query
+            .as_deref()
+            .map(|q| parse_params(q.as_bytes()))
+            .unwrap_or_default();
+
+        let content_type = self
+            .header("content-type")
+            .map(|c| c.to_ascii_lowercase())
+            .unwrap_or_default();
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+
+        self.json = None;
+        self.form = None;
+        if mime == "application/json" || mime.ends_with("+json") {
+            self.json = serde_json::from_str(&self.body).ok();
+        } else if mime == "application/x-www-form-urlencoded" {
+            self.form = Some(parse_params(self.body.as_bytes()));
+        } else if mime == "multipart/form-data" {
+            self.form = multipart_boundary(self.header("content-type").unwrap_or(""))
+                .map(|boundary| parse_multipart(&self.body, &boundary));
+        }
+    }
+}
+
+/// Parses `application/x-www-form-urlencoded` data, like query strings.
+fn parse_params(input: &[u8]) -> Params {
+    let mut params = Params::new();
+    for (name, value) in form_urlencoded::parse(input) {
+        params
+            .entry(name.into_owned())
+            .or_default()
+            .push(value.into_owned());
+    }
+
+    params
+}
+
+/// Extracts the boundary parameter of a `multipart/form-data` content type.
+fn multipart_boundary(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let mut kv = param.trim().splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some(name), Some(value)) if name.eq_ignore_ascii_case("boundary") => {
+                Some(value.trim_matches('"').to_string())
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Parses a `multipart/form-data` body. Files are contained with their content
+/// like all other fields.
+fn parse_multipart(body: &str, boundary: &str) -> Params {
+    let mut params = Params::new();
+    let delimiter = format!("--{}", boundary);
+
+    // the preamble before the first and the epilogue after the last delimiter
+    // are skipped
+    for part in body.split(delimiter.as_str()).skip(1) {
+        if part.starts_with("--") {
+            break;
+        }
+
+        let part = part.strip_prefix("\r\n").unwrap_or(part);
+        let part = part.strip_suffix("\r\n").unwrap_or(part);
+        let mut sections = part.splitn(2, "\r\n\r\n");
+        let (headers, value) = match (sections.next(), sections.next()) {
+            (Some(headers), Some(value)) => (headers, value),
+            _ => continue,
+        };
+
+        let name = headers
+            .split("\r\n")
+            .filter(|h| h.to_ascii_lowercase().starts_with("content-disposition:"))
+            .flat_map(|h| h.split(';').skip(1))
+            .find_map(|param| {
+                let mut kv = param.trim().splitn(2, '=');
+                match (kv.next(), kv.next()) {
+                    (Some("name"), Some(name)) => Some(name.trim_matches('"').to_string()),
+                    _ => None,
+                }
+            });
+
+        if let Some(name) = name {
+            params.entry(name).or_default().push(value.to_string());
+        }
+    }
+
+    params
 }
 
 impl TryFrom<http::Request<Vec<u8>>> for Request {
@@ -29,14 +213,26 @@ impl TryFrom<http::Request<Vec<u8>>> for Request {
     fn try_from(req: http::Request<Vec<u8>>) -> Result<Self, Self::Error> {
         let (parts, body) = req.into_parts();
 
-        Ok(Request {
+        let mut request = Request {
             body: String::from_utf8(body)?,
             headers: try_header_map_to_hashmap(parts.headers)?,
             method: parts.method.to_string(),
             path: parts.uri.path().to_string(),
             query: parts.uri.query().and_then(|x| Some(x.to_string())),
             version: version_to_string(parts.version),
-        })
+            query_params: Params::new(),
+            json: None,
+            form: None,
+            remote_addr: None,
+            trusted_proxy: false,
+            host: None,
+            scheme: None,
+            processor_id: None,
+            path_params: HashMap::new(),
+        };
+        request.parse();
+
+        Ok(request)
     }
 }
 
@@ -11,6 +11,10 @@ pub struct Response {
     pub headers: HashMap<String, Vec<String>>,
     pub body: Option<String>,
     pub version: String,
+    /// Body as received, if it was received from another service. `body`
+    /// holds it decoded as UTF-8, where invalid sequences are replaced.
+    #[serde(skip)]
+    pub raw_body: Option<Vec<u8>>,
 }
 
 impl Response {
@@ -21,9 +25,12 @@ impl Response {
             body: None,
             headers: try_header_map_to_hashmap(resp.headers().clone())?,
             version: version_to_string(resp.version()),
+            raw_body: None,
         };
 
-        r.body = Some(String::from_utf8(resp.bytes().await?.to_vec())?);
+        let bytes = resp.bytes().await?.to_vec();
+        r.body = Some(String::from_utf8_lossy(&bytes).into_owned());
+        r.raw_body = Some(bytes);
 
         Ok(r)
     }
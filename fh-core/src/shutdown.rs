@@ -0,0 +1,57 @@
+//! Coordination of the graceful shutdown. Long running tasks like the web
+//! server, workers and managers receive a [`Shutdown`] handle and stop, once
+//! the corresponding [`ShutdownTrigger`] is fired.
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Creates a connected pair of [`ShutdownTrigger`] and [`Shutdown`].
+pub fn channel() -> (ShutdownTrigger, Shutdown) {
+    let (tx, rx) = watch::channel(false);
+
+    (ShutdownTrigger { tx }, Shutdown { rx })
+}
+
+/// Fires the shutdown of all connected [`Shutdown`] handles.
+#[derive(Debug)]
+pub struct ShutdownTrigger {
+    tx: watch::Sender<bool>,
+}
+
+impl ShutdownTrigger {
+    pub fn trigger(&self) {
+        // all handles might be gone already, which is fine
+        let _ = self.tx.broadcast(true);
+    }
+}
+
+/// Cheap clonable handle, which is notified on shutdown. Dropping the
+/// [`ShutdownTrigger`] counts as shutdown as well.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    rx: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    /// Checks, if shutdown was requested, without waiting.
+    pub fn is_requested(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Resolves, once shutdown was requested.
+    pub async fn requested(&mut self) {
+        while !self.is_requested() {
+            if self.rx.recv().await.is_none() {
+                return;
+            }
+        }
+    }
+
+    /// Waits for `duration` or until shutdown was requested, whatever happens
+    /// first. Returns `true`, if shutdown was requested.
+    pub async fn sleep(&mut self, duration: Duration) -> bool {
+        tokio::select! {
+            _ = tokio::time::delay_for(duration) => self.is_requested(),
+            _ = self.requested() => true,
+        }
+    }
+}
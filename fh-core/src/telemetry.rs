@@ -0,0 +1,55 @@
+//! Helpers for tracing, which carry the trace context across the command
+//! channels and HTTP boundaries. The W3C trace context (`traceparent` and
+//! `tracestate` headers) is used for the latter.
+use opentelemetry::{global, Context};
+use std::collections::HashMap;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Envelope of a command, which is sent over a channel to a manager. Carries
+/// the span of the sender, so the manager can process the command within a
+/// child span of it.
+#[derive(Debug)]
+pub struct Traced<T> {
+    pub span: Span,
+    pub inner: T,
+}
+
+impl<T> Traced<T> {
+    /// Wraps `inner` together with the current span.
+    pub fn new(inner: T) -> Self {
+        Self {
+            span: Span::current(),
+            inner,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// Extracts the trace context of an incoming request from its headers. The
+/// context is empty, if the client did not send one.
+pub fn extract_context(headers: &HashMap<String, Vec<String>>) -> Context {
+    let carrier: HashMap<String, String> = headers
+        .iter()
+        .filter_map(|(name, values)| {
+            values
+                .first()
+                .map(|value| (name.to_ascii_lowercase(), value.clone()))
+        })
+        .collect();
+
+    global::get_text_map_propagator(|propagator| propagator.extract(&carrier))
+}
+
+/// Returns the headers, which propagate the trace context of `span` to an
+/// outbound request.
+pub fn context_headers(span: &Span) -> HashMap<String, String> {
+    let mut carrier = HashMap::new();
+    let context = span.context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut carrier));
+
+    carrier
+}
@@ -1,12 +1,36 @@
-use self::request_processor::RequestProcessor;
-use anyhow::{Context, Error, Result};
-use fh_core::{DbPool, DbType, Responder, TypedPool};
-use request_conversation::{AuditItem, RequestConversation};
-use std::env;
+use self::request_processor::{RequestProcessor, RequestProcessorConfig};
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use fh_core::{
+    metrics::{Queue, Queued, AUDIT_ITEMS},
+    respond,
+    shutdown::Shutdown,
+    telemetry::Traced,
+    DbPool, DbType, ReqReceiver, Responder, TypedPool,
+};
+use outbound_delivery::{DeliveryStatus, OutboundDelivery};
+use pipeline::Pipeline;
+use processor_job::{ProcessorJob, ProcessorJobStatus};
+use processor_kv::KvEntry;
+use processor_route::ProcessorRoute;
+use processor_schedule::ProcessorSchedule;
+use processor_secret::{ProcessorSecretInfo, SecretValue};
+use processor_usage::{Usage, UsageQuota, UsageReport};
+use request_conversation::{AuditItem, ConversationStatus, LogLevel, RequestConversation};
+use std::{collections::HashMap, env};
+use strum_macros::AsRefStr;
 use thiserror::Error;
-use tokio::sync::mpsc;
+use tracing::Instrument;
 use uuid::Uuid;
 
+pub mod outbound_delivery;
+pub mod pipeline;
+pub mod processor_job;
+pub mod processor_kv;
+pub mod processor_route;
+pub mod processor_schedule;
+pub mod processor_secret;
+pub mod processor_usage;
 pub mod request_conversation;
 pub mod request_processor;
 
@@ -56,6 +80,47 @@ pub enum RequestProcessorError {
     /// cases.
     #[error("{0}")]
     EmptyDbField(String),
+
+    /// Happens when user provided data is syntactically fine, but not
+    /// acceptable (e.g. an empty key for the key-value store).
+    #[error("Invalid input: {0}")]
+    Validation(String),
+
+    /// Happens when the code of a RequestProcessor fails, e.g. by throwing an
+    /// uncaught exception. The error is recorded on the conversation as well.
+    #[error("{error}")]
+    Script {
+        conversation_id: Uuid,
+        error: fh_core::error::ScriptError,
+    },
+
+    /// Happens when the processing of a request takes too long.
+    #[error("Processing timed out after {0} ms")]
+    Timeout(u64),
+
+    /// Happens when the webhook signature of an incoming request is invalid.
+    /// The rejection is recorded as a conversation.
+    #[error("Webhook verification failed: {reason}")]
+    WebhookVerification {
+        conversation_id: Uuid,
+        reason: String,
+    },
+
+    /// Happens when a storage or usage quota would be exceeded.
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    /// Happens when an entity clashes with an existing one, e.g. two routes
+    /// for the same path.
+    #[error("Conflict: {0}")]
+    Conflict(String),
+}
+
+/// Formats a timestamp for storing it in the database. In contrast to
+/// [`DateTime::to_rfc3339`], the output always has the same length, so stored
+/// timestamps can be compared lexicographically in SQL queries.
+pub(crate) fn to_db_timestamp(dt: &DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Micros, true)
 }
 
 /// Central Command Enum, which contains all Commands to be sent to the `fh_db`
@@ -65,7 +130,7 @@ pub enum RequestProcessorError {
 /// Each variant of the ReqCmd responds data back using a [`Responder`] type
 /// which, by convention is given by the variant field `cmd_tx`. The Responder
 /// is the transmitter of a [`tokio::sync::oneshot`] channel.
-#[derive(Debug)]
+#[derive(Debug, AsRefStr)]
 pub enum ReqCmd {
     CreateRequestProcessor {
         proc: RequestProcessor,
@@ -84,8 +149,16 @@ pub enum ReqCmd {
         id: Uuid,
         cmd_tx: Responder<Result<(), RequestProcessorError>>,
     },
+    UpdateRequestProcessorConfig {
+        id: Uuid,
+        config: RequestProcessorConfig,
+        cmd_tx: Responder<Result<RequestProcessorConfig, RequestProcessorError>>,
+    },
+    /// Creates a conversation, optionally as child of the conversation, which
+    /// issued the invocation.
     CreateRequestConversation {
         request_processor_id: Uuid,
+        parent_id: Option<Uuid>,
         cmd_tx: Responder<Result<RequestConversation, RequestProcessorError>>,
     },
     CreateAuditLogEntry {
@@ -94,59 +167,246 @@ pub enum ReqCmd {
     },
     GetRequestConversation {
         id: Uuid,
+        level: Option<LogLevel>,
         cmd_tx: Responder<Result<RequestConversation, RequestProcessorError>>,
     },
+    FinishRequestConversation {
+        id: Uuid,
+        status: ConversationStatus,
+        status_code: Option<u16>,
+        error: Option<String>,
+        cmd_tx: Responder<Result<(), RequestProcessorError>>,
+    },
     GetRequestConversationAuditItems {
         id: Uuid,
+        level: Option<LogLevel>,
         cmd_tx: Responder<Result<Vec<AuditItem>, RequestProcessorError>>,
     },
+    GetKvEntry {
+        request_processor_id: Uuid,
+        key: String,
+        cmd_tx: Responder<Result<Option<KvEntry>, RequestProcessorError>>,
+    },
+    SetKvEntry {
+        request_processor_id: Uuid,
+        key: String,
+        value: serde_json::Value,
+        ttl: Option<i64>,
+        cmd_tx: Responder<Result<KvEntry, RequestProcessorError>>,
+    },
+    DeleteKvEntry {
+        request_processor_id: Uuid,
+        key: String,
+        cmd_tx: Responder<Result<bool, RequestProcessorError>>,
+    },
+    ListKvKeys {
+        request_processor_id: Uuid,
+        prefix: Option<String>,
+        cmd_tx: Responder<Result<Vec<String>, RequestProcessorError>>,
+    },
+    SetProcessorSecret {
+        request_processor_id: Uuid,
+        name: String,
+        value: SecretValue,
+        cmd_tx: Responder<Result<ProcessorSecretInfo, RequestProcessorError>>,
+    },
+    DeleteProcessorSecret {
+        request_processor_id: Uuid,
+        name: String,
+        cmd_tx: Responder<Result<(), RequestProcessorError>>,
+    },
+    ListProcessorSecrets {
+        request_processor_id: Uuid,
+        cmd_tx: Responder<Result<Vec<ProcessorSecretInfo>, RequestProcessorError>>,
+    },
+    GetProcessorSecretValues {
+        request_processor_id: Uuid,
+        cmd_tx: Responder<Result<HashMap<String, SecretValue>, RequestProcessorError>>,
+    },
+    CreateProcessorSchedule {
+        schedule: ProcessorSchedule,
+        cmd_tx: Responder<Result<ProcessorSchedule, RequestProcessorError>>,
+    },
+    ListProcessorSchedules {
+        request_processor_id: Uuid,
+        cmd_tx: Responder<Result<Vec<ProcessorSchedule>, RequestProcessorError>>,
+    },
+    GetEnabledProcessorSchedules {
+        cmd_tx: Responder<Result<Vec<ProcessorSchedule>, RequestProcessorError>>,
+    },
+    UpdateProcessorScheduleLastRun {
+        id: Uuid,
+        last_run_at: DateTime<Utc>,
+        cmd_tx: Responder<Result<(), RequestProcessorError>>,
+    },
+    DeleteProcessorSchedule {
+        id: Uuid,
+        cmd_tx: Responder<Result<(), RequestProcessorError>>,
+    },
+    CreateProcessorJob {
+        job: ProcessorJob,
+        cmd_tx: Responder<Result<ProcessorJob, RequestProcessorError>>,
+    },
+    ClaimNextProcessorJob {
+        cmd_tx: Responder<Result<Option<ProcessorJob>, RequestProcessorError>>,
+    },
+    FinishProcessorJob {
+        id: Uuid,
+        status: ProcessorJobStatus,
+        error: Option<String>,
+        cmd_tx: Responder<Result<(), RequestProcessorError>>,
+    },
+    RequeueRunningProcessorJobs {
+        cmd_tx: Responder<Result<u64, RequestProcessorError>>,
+    },
+    CreateOutboundDelivery {
+        delivery: OutboundDelivery,
+        cmd_tx: Responder<Result<OutboundDelivery, RequestProcessorError>>,
+    },
+    GetOutboundDelivery {
+        id: Uuid,
+        cmd_tx: Responder<Result<OutboundDelivery, RequestProcessorError>>,
+    },
+    ListOutboundDeliveries {
+        status: Option<DeliveryStatus>,
+        cmd_tx: Responder<Result<Vec<OutboundDelivery>, RequestProcessorError>>,
+    },
+    GetDueOutboundDeliveries {
+        limit: i32,
+        cmd_tx: Responder<Result<Vec<OutboundDelivery>, RequestProcessorError>>,
+    },
+    RecordDeliveryAttempt {
+        id: Uuid,
+        error: Option<String>,
+        cmd_tx: Responder<Result<OutboundDelivery, RequestProcessorError>>,
+    },
+    RetryOutboundDelivery {
+        id: Uuid,
+        cmd_tx: Responder<Result<OutboundDelivery, RequestProcessorError>>,
+    },
+    DeleteOutboundDelivery {
+        id: Uuid,
+        cmd_tx: Responder<Result<(), RequestProcessorError>>,
+    },
+    CreateProcessorRoute {
+        route: ProcessorRoute,
+        cmd_tx: Responder<Result<ProcessorRoute, RequestProcessorError>>,
+    },
+    /// Lists the routes of a single or, without id, of all RequestProcessors.
+    ListProcessorRoutes {
+        request_processor_id: Option<Uuid>,
+        cmd_tx: Responder<Result<Vec<ProcessorRoute>, RequestProcessorError>>,
+    },
+    DeleteProcessorRoute {
+        id: Uuid,
+        cmd_tx: Responder<Result<(), RequestProcessorError>>,
+    },
+    CreatePipeline {
+        pipeline: Pipeline,
+        cmd_tx: Responder<Result<Pipeline, RequestProcessorError>>,
+    },
+    GetPipeline {
+        id: Uuid,
+        cmd_tx: Responder<Result<Pipeline, RequestProcessorError>>,
+    },
+    UpdatePipeline {
+        id: Uuid,
+        pipeline: Pipeline,
+        cmd_tx: Responder<Result<Pipeline, RequestProcessorError>>,
+    },
+    DeletePipeline {
+        id: Uuid,
+        cmd_tx: Responder<Result<(), RequestProcessorError>>,
+    },
+    RecordUsage {
+        request_processor_id: Uuid,
+        usage: Usage,
+        cmd_tx: Responder<Result<(), RequestProcessorError>>,
+    },
+    GetUsageReport {
+        request_processor_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+        cmd_tx: Responder<Result<UsageReport, RequestProcessorError>>,
+    },
+    CheckUsageQuota {
+        request_processor_id: Uuid,
+        quota: UsageQuota,
+        cmd_tx: Responder<Result<(), RequestProcessorError>>,
+    },
+    /// Checks, that the database is reachable. Used for readiness checks.
+    Ping {
+        cmd_tx: Responder<Result<(), RequestProcessorError>>,
+    },
+}
+
+impl Queued for ReqCmd {
+    const QUEUE: Queue = Queue::Db;
 }
 
-/// Async function which can be run e.g. by tokio which loops forever and
-/// receives [`ReqCmd`] commands via the given Receiver.
-pub async fn request_manager(rx: &mut mpsc::Receiver<ReqCmd>) -> anyhow::Result<()> {
+/// Async function which can be run e.g. by tokio which loops until shutdown
+/// and receives [`ReqCmd`] commands via the given Receiver. On shutdown, no
+/// further commands are accepted, but already queued ones are processed.
+pub async fn request_manager(
+    rx: &mut ReqReceiver<ReqCmd>,
+    mut shutdown: Shutdown,
+) -> anyhow::Result<()> {
     let pool = TypedPool::connect(&env::var("DATABASE_URL")?)
         .await
         .context("Connection to DB failed")?;
 
+    loop {
+        let cmd = tokio::select! {
+            cmd = rx.recv() => cmd,
+            _ = shutdown.requested() => break,
+        };
+        match cmd {
+            Some(cmd) => process_traced_command(cmd, &pool).await?,
+            None => break,
+        }
+    }
+
+    // pending commands are written, before the connections are closed
+    rx.close();
     while let Some(cmd) = rx.recv().await {
-        process_command(cmd, &pool).await?;
+        process_traced_command(cmd, &pool).await?;
     }
+    pool.close().await;
 
     Ok(())
 }
 
+/// Processes a received command within a child span of its sender's span.
+async fn process_traced_command(cmd: Traced<ReqCmd>, pool: &DbPool<DbType>) -> Result<()> {
+    Queue::Db.dequeued();
+    let span = tracing::info_span!(parent: &cmd.span, "db_command", command = cmd.inner.as_ref());
+
+    process_command(cmd.into_inner(), pool)
+        .instrument(span)
+        .await
+}
+
 /// Actual `ReqCmd` command processor which matches the given variant and calls
 /// the underlying functions in the submodules (e.g.
 /// [`crate::request_processor'] or [`crate::request_conversation`]).
 async fn process_command(cmd: ReqCmd, pool: &DbPool<DbType>) -> Result<()> {
     match cmd {
         ReqCmd::CreateRequestProcessor {
-            proc: processor,
+            proc: mut processor,
             cmd_tx,
         } => {
             let res = self::request_processor::create_request_processor(
                 &mut pool.acquire().await?,
-                &processor,
+                &mut processor,
             )
             .await;
 
-            cmd_tx.send(res.and(Ok(processor))).map_err(|e| {
-                Error::msg(format!(
-                    "Unable to send Response to server handler: {:?}",
-                    e
-                ))
-            })?;
+            respond(cmd_tx, res.and(Ok(processor)));
         }
         ReqCmd::GetRequestProcessor { id, cmd_tx } => {
             let p = self::request_processor::get_request_processor(&mut pool.acquire().await?, &id)
                 .await;
-            cmd_tx.send(p).map_err(|e| {
-                Error::msg(format!(
-                    "Unable to send Response to server handler: {:?}",
-                    e
-                ))
-            })?;
+            respond(cmd_tx, p);
         }
         ReqCmd::UpdateRequestProcessor {
             id,
@@ -159,64 +419,455 @@ async fn process_command(cmd: ReqCmd, pool: &DbPool<DbType>) -> Result<()> {
                 &mut processor,
             )
             .await;
-            cmd_tx.send(res.and(Ok(processor))).map_err(|e| {
-                Error::msg(format!(
-                    "Unable to send Response to server handler: {:?}",
-                    e
-                ))
-            })?;
+            respond(cmd_tx, res.and(Ok(processor)));
         }
         ReqCmd::DeleteRequestProcessor { id, cmd_tx } => {
             let p =
                 self::request_processor::delete_request_processor(&mut pool.acquire().await?, &id)
                     .await;
-            cmd_tx
-                .send(p)
-                .map_err(|_| Error::msg(format!("Unable to send () to server handler")))?;
+            respond(cmd_tx, p);
+        }
+        ReqCmd::UpdateRequestProcessorConfig { id, config, cmd_tx } => {
+            let res = self::request_processor::update_request_processor_config(
+                &mut pool.acquire().await?,
+                &id,
+                &config,
+            )
+            .await;
+
+            respond(cmd_tx, res.and(Ok(config)));
         }
         ReqCmd::CreateRequestConversation {
             request_processor_id,
+            parent_id,
             cmd_tx,
         } => {
             let conv = self::request_conversation::create_request_conversation(
                 &mut pool.acquire().await?,
                 &request_processor_id,
+                parent_id,
             )
             .await;
 
-            cmd_tx
-                .send(conv)
-                .map_err(|_| Error::msg(format!("Unable to send () to server handler")))?;
+            respond(cmd_tx, conv);
         }
         ReqCmd::CreateAuditLogEntry { item, cmd_tx } => {
+            AUDIT_ITEMS.with_label_values(&[item.kind()]).inc();
             let item =
                 self::request_conversation::create_audit_item(&mut pool.acquire().await?, item)
                     .await;
 
-            cmd_tx
-                .send(item)
-                .map_err(|_| Error::msg(format!("Unable to send () to server handler")))?;
+            respond(cmd_tx, item);
         }
-        ReqCmd::GetRequestConversationAuditItems { id, cmd_tx } => {
+        ReqCmd::FinishRequestConversation {
+            id,
+            status,
+            status_code,
+            error,
+            cmd_tx,
+        } => {
+            let res = self::request_conversation::finish_request_conversation(
+                &mut pool.acquire().await?,
+                &id,
+                status,
+                status_code,
+                error,
+            )
+            .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::GetRequestConversationAuditItems { id, level, cmd_tx } => {
             let items =
-                self::request_conversation::get_audit_items(&mut pool.acquire().await?, &id).await;
+                self::request_conversation::get_audit_items(&mut pool.acquire().await?, &id, level)
+                    .await;
 
-            cmd_tx
-                .send(items)
-                .map_err(|_| Error::msg(format!("Unable to send () to server handler")))?;
+            respond(cmd_tx, items);
         }
-        ReqCmd::GetRequestConversation { id, cmd_tx } => {
+        ReqCmd::GetRequestConversation { id, level, cmd_tx } => {
             let items = self::request_conversation::get_request_conversation(
                 &mut pool.acquire().await?,
                 &id,
+                level,
+            )
+            .await;
+
+            respond(cmd_tx, items);
+        }
+        ReqCmd::GetKvEntry {
+            request_processor_id,
+            key,
+            cmd_tx,
+        } => {
+            let entry = self::processor_kv::get_kv_entry(
+                &mut pool.acquire().await?,
+                &request_processor_id,
+                &key,
             )
             .await;
 
-            cmd_tx
-                .send(items)
-                .map_err(|_| Error::msg(format!("Unable to send () to server handler")))?;
+            respond(cmd_tx, entry);
+        }
+        ReqCmd::SetKvEntry {
+            request_processor_id,
+            key,
+            value,
+            ttl,
+            cmd_tx,
+        } => {
+            let entry = self::processor_kv::set_kv_entry(
+                &mut pool.acquire().await?,
+                &request_processor_id,
+                &key,
+                &value,
+                ttl,
+            )
+            .await;
+
+            respond(cmd_tx, entry);
+        }
+        ReqCmd::DeleteKvEntry {
+            request_processor_id,
+            key,
+            cmd_tx,
+        } => {
+            let deleted = self::processor_kv::delete_kv_entry(
+                &mut pool.acquire().await?,
+                &request_processor_id,
+                &key,
+            )
+            .await;
+
+            respond(cmd_tx, deleted);
+        }
+        ReqCmd::ListKvKeys {
+            request_processor_id,
+            prefix,
+            cmd_tx,
+        } => {
+            let keys = self::processor_kv::list_kv_keys(
+                &mut pool.acquire().await?,
+                &request_processor_id,
+                prefix.as_deref(),
+            )
+            .await;
+
+            respond(cmd_tx, keys);
+        }
+        ReqCmd::SetProcessorSecret {
+            request_processor_id,
+            name,
+            value,
+            cmd_tx,
+        } => {
+            let info = self::processor_secret::set_processor_secret(
+                &mut pool.acquire().await?,
+                &request_processor_id,
+                &name,
+                &value,
+            )
+            .await;
+
+            respond(cmd_tx, info);
+        }
+        ReqCmd::DeleteProcessorSecret {
+            request_processor_id,
+            name,
+            cmd_tx,
+        } => {
+            let res = self::processor_secret::delete_processor_secret(
+                &mut pool.acquire().await?,
+                &request_processor_id,
+                &name,
+            )
+            .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::ListProcessorSecrets {
+            request_processor_id,
+            cmd_tx,
+        } => {
+            let infos = self::processor_secret::list_processor_secrets(
+                &mut pool.acquire().await?,
+                &request_processor_id,
+            )
+            .await;
+
+            respond(cmd_tx, infos);
+        }
+        ReqCmd::GetProcessorSecretValues {
+            request_processor_id,
+            cmd_tx,
+        } => {
+            let secrets = self::processor_secret::get_processor_secret_values(
+                &mut pool.acquire().await?,
+                &request_processor_id,
+            )
+            .await;
+
+            respond(cmd_tx, secrets);
+        }
+        ReqCmd::CreateProcessorSchedule { schedule, cmd_tx } => {
+            let res = self::processor_schedule::create_processor_schedule(
+                &mut pool.acquire().await?,
+                &schedule,
+            )
+            .await;
+
+            respond(cmd_tx, res.and(Ok(schedule)));
+        }
+        ReqCmd::ListProcessorSchedules {
+            request_processor_id,
+            cmd_tx,
+        } => {
+            let schedules = self::processor_schedule::list_processor_schedules(
+                &mut pool.acquire().await?,
+                &request_processor_id,
+            )
+            .await;
+
+            respond(cmd_tx, schedules);
+        }
+        ReqCmd::GetEnabledProcessorSchedules { cmd_tx } => {
+            let schedules = self::processor_schedule::get_enabled_processor_schedules(
+                &mut pool.acquire().await?,
+            )
+            .await;
+
+            respond(cmd_tx, schedules);
+        }
+        ReqCmd::UpdateProcessorScheduleLastRun {
+            id,
+            last_run_at,
+            cmd_tx,
+        } => {
+            let res = self::processor_schedule::update_processor_schedule_last_run(
+                &mut pool.acquire().await?,
+                &id,
+                &last_run_at,
+            )
+            .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::DeleteProcessorSchedule { id, cmd_tx } => {
+            let res = self::processor_schedule::delete_processor_schedule(
+                &mut pool.acquire().await?,
+                &id,
+            )
+            .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::CreateProcessorJob { job, cmd_tx } => {
+            let res =
+                self::processor_job::create_processor_job(&mut pool.acquire().await?, &job).await;
+
+            respond(cmd_tx, res.and(Ok(job)));
+        }
+        ReqCmd::ClaimNextProcessorJob { cmd_tx } => {
+            let job =
+                self::processor_job::claim_next_processor_job(&mut pool.acquire().await?).await;
+
+            respond(cmd_tx, job);
+        }
+        ReqCmd::FinishProcessorJob {
+            id,
+            status,
+            error,
+            cmd_tx,
+        } => {
+            let res = self::processor_job::finish_processor_job(
+                &mut pool.acquire().await?,
+                &id,
+                status,
+                error,
+            )
+            .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::RequeueRunningProcessorJobs { cmd_tx } => {
+            let res =
+                self::processor_job::requeue_running_processor_jobs(&mut pool.acquire().await?)
+                    .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::CreateOutboundDelivery { delivery, cmd_tx } => {
+            let res = self::outbound_delivery::create_outbound_delivery(
+                &mut pool.acquire().await?,
+                &delivery,
+            )
+            .await;
+
+            respond(cmd_tx, res.and(Ok(delivery)));
+        }
+        ReqCmd::GetOutboundDelivery { id, cmd_tx } => {
+            let res =
+                self::outbound_delivery::get_outbound_delivery(&mut pool.acquire().await?, &id)
+                    .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::ListOutboundDeliveries { status, cmd_tx } => {
+            let res = self::outbound_delivery::list_outbound_deliveries(
+                &mut pool.acquire().await?,
+                status,
+            )
+            .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::GetDueOutboundDeliveries { limit, cmd_tx } => {
+            let res = self::outbound_delivery::get_due_outbound_deliveries(
+                &mut pool.acquire().await?,
+                &Utc::now(),
+                limit,
+            )
+            .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::RecordDeliveryAttempt { id, error, cmd_tx } => {
+            let res = self::outbound_delivery::record_delivery_attempt(
+                &mut pool.acquire().await?,
+                &id,
+                error,
+            )
+            .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::RetryOutboundDelivery { id, cmd_tx } => {
+            let res =
+                self::outbound_delivery::retry_outbound_delivery(&mut pool.acquire().await?, &id)
+                    .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::DeleteOutboundDelivery { id, cmd_tx } => {
+            let res =
+                self::outbound_delivery::delete_outbound_delivery(&mut pool.acquire().await?, &id)
+                    .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::CreateProcessorRoute { mut route, cmd_tx } => {
+            let res = self::processor_route::create_processor_route(
+                &mut pool.acquire().await?,
+                &mut route,
+            )
+            .await;
+
+            respond(cmd_tx, res.and(Ok(route)));
+        }
+        ReqCmd::ListProcessorRoutes {
+            request_processor_id,
+            cmd_tx,
+        } => {
+            let res = self::processor_route::list_processor_routes(
+                &mut pool.acquire().await?,
+                request_processor_id,
+            )
+            .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::DeleteProcessorRoute { id, cmd_tx } => {
+            let res =
+                self::processor_route::delete_processor_route(&mut pool.acquire().await?, &id)
+                    .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::CreatePipeline { pipeline, cmd_tx } => {
+            let res = self::pipeline::create_pipeline(&mut pool.acquire().await?, &pipeline).await;
+
+            respond(cmd_tx, res.and(Ok(pipeline)));
+        }
+        ReqCmd::GetPipeline { id, cmd_tx } => {
+            let res = self::pipeline::get_pipeline(&mut pool.acquire().await?, &id).await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::UpdatePipeline {
+            id,
+            mut pipeline,
+            cmd_tx,
+        } => {
+            let res =
+                self::pipeline::update_pipeline(&mut pool.acquire().await?, &id, &mut pipeline)
+                    .await;
+
+            respond(cmd_tx, res.and(Ok(pipeline)));
+        }
+        ReqCmd::DeletePipeline { id, cmd_tx } => {
+            let res = self::pipeline::delete_pipeline(&mut pool.acquire().await?, &id).await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::RecordUsage {
+            request_processor_id,
+            usage,
+            cmd_tx,
+        } => {
+            let res = self::processor_usage::record_usage(
+                &mut pool.acquire().await?,
+                &request_processor_id,
+                &usage,
+            )
+            .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::GetUsageReport {
+            request_processor_id,
+            from,
+            to,
+            cmd_tx,
+        } => {
+            let res = self::processor_usage::get_usage_report(
+                &mut pool.acquire().await?,
+                &request_processor_id,
+                from,
+                to,
+            )
+            .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::CheckUsageQuota {
+            request_processor_id,
+            quota,
+            cmd_tx,
+        } => {
+            let res = self::processor_usage::check_usage_quota(
+                &mut pool.acquire().await?,
+                &request_processor_id,
+                &quota,
+            )
+            .await;
+
+            respond(cmd_tx, res);
+        }
+        ReqCmd::Ping { cmd_tx } => {
+            let res = ping(pool).await;
+
+            respond(cmd_tx, res);
         }
     }
 
     Ok(())
 }
+
+/// Runs a trivial query. Errors are responded instead of stopping the
+/// manager, as an unreachable database is exactly what the ping reports.
+async fn ping(pool: &DbPool<DbType>) -> Result<(), RequestProcessorError> {
+    sqlx::query("SELECT 1")
+        .execute(&mut pool.acquire().await?)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,366 @@
+//! Database structs and functions for the OutboundDelivery entity. An
+//! OutboundDelivery is a queued outbound request of a
+//! [`crate::request_processor::RequestProcessor`], which failed and is retried
+//! with exponential backoff.
+use super::{request_conversation::redact_value, to_db_timestamp, RequestProcessorError};
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use fh_core::{
+    request::{Request, RetryPolicy},
+    DbConnection,
+};
+use serde::{Deserialize, Serialize};
+use std::{convert::AsRef, str::FromStr};
+use strum_macros::{self, AsRefStr, EnumString};
+use uuid::Uuid;
+
+/// Upper bound for the delay between two attempts.
+pub const MAX_BACKOFF_MS: i64 = 60 * 60 * 1000;
+
+/// Variants of the states, an OutboundDelivery passes through. Deliveries,
+/// which exhausted their attempts, end up as `dead`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, AsRefStr, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Dead,
+}
+
+/// Failed outbound request, which is retried in the background. Every attempt
+/// is recorded on the conversation, which issued the request originally.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboundDelivery {
+    pub id: Uuid,
+    pub request_processor_id: Uuid,
+    pub conversation_id: Uuid,
+    /// Increment of the request in the issuing conversation. Every attempt
+    /// is recorded with it, so attempts can be matched to the request.
+    pub inc: i32,
+    pub url: String,
+    pub request: Request,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub backoff_ms: i64,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+}
+
+impl OutboundDelivery {
+    /// Creates a new OutboundDelivery from a first, failed attempt.
+    pub fn new(
+        request_processor_id: Uuid,
+        conversation_id: Uuid,
+        inc: i32,
+        url: String,
+        request: Request,
+        policy: &RetryPolicy,
+        error: String,
+    ) -> Self {
+        let now = Utc::now();
+        let mut delivery = Self {
+            id: Uuid::new_v4(),
+            request_processor_id,
+            conversation_id,
+            inc,
+            url,
+            request,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            max_attempts: policy.max_attempts.max(1),
+            backoff_ms: policy.backoff_ms.max(0),
+            next_attempt_at: now,
+            created_at: now,
+            updated_at: now,
+            last_error: None,
+        };
+        delivery.record_attempt(Some(error), now);
+
+        delivery
+    }
+
+    /// Replaces all occurrences of the given secret values with `[REDACTED]`,
+    /// like [`crate::request_conversation::AuditItem::redact`]. The stored
+    /// request must contain the secrets to be sent, so deliveries are only
+    /// redacted before they are returned to clients.
+    pub fn redact(self, secrets: &[&str]) -> Result<Self, RequestProcessorError> {
+        let secrets: Vec<&str> = secrets.iter().filter(|s| !s.is_empty()).cloned().collect();
+        if secrets.is_empty() {
+            return Ok(self);
+        }
+
+        let mut value = serde_json::to_value(&self)?;
+        redact_value(&mut value, &secrets);
+
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Computes the delay after the current number of attempts, which doubles
+    /// with every attempt and is capped at [`MAX_BACKOFF_MS`].
+    pub fn backoff(&self) -> Duration {
+        let exp = (self.attempts - 1).max(0).min(30) as u32;
+        let ms = self.backoff_ms.saturating_mul(2_i64.pow(exp));
+        Duration::milliseconds(ms.min(MAX_BACKOFF_MS))
+    }
+
+    /// Updates the state after an attempt. An attempt without error marks the
+    /// delivery as delivered, otherwise the next attempt is scheduled or, if
+    /// all attempts are used up, the delivery is marked as dead.
+    fn record_attempt(&mut self, error: Option<String>, now: DateTime<Utc>) {
+        self.attempts += 1;
+        self.updated_at = now;
+        match error {
+            None => self.status = DeliveryStatus::Delivered,
+            Some(error) => {
+                self.last_error = Some(error);
+                if self.attempts >= self.max_attempts {
+                    self.status = DeliveryStatus::Dead;
+                } else {
+                    self.status = DeliveryStatus::Pending;
+                    self.next_attempt_at = now + self.backoff();
+                }
+            }
+        }
+    }
+}
+
+/// Maps a row of the `outbound_delivery` table. The rows of the different
+/// `query!` invocations are distinct types, so this can't be a function.
+macro_rules! delivery_from_row {
+    ($row: expr) => {{
+        let row = $row;
+        OutboundDelivery {
+            id: Uuid::from_str(&row.id)?,
+            request_processor_id: Uuid::from_str(&row.request_processor)?,
+            conversation_id: Uuid::from_str(&row.request_conversation)?,
+            inc: row.inc,
+            url: row.url,
+            request: serde_json::from_str(&row.request)?,
+            status: DeliveryStatus::from_str(&row.status)?,
+            attempts: row.attempts,
+            max_attempts: row.max_attempts,
+            backoff_ms: row.backoff_ms,
+            next_attempt_at: DateTime::parse_from_rfc3339(&row.next_attempt_at)?
+                .with_timezone(&Utc),
+            created_at: DateTime::parse_from_rfc3339(&row.created_at)?.with_timezone(&Utc),
+            updated_at: DateTime::parse_from_rfc3339(&row.updated_at)?.with_timezone(&Utc),
+            last_error: row.last_error,
+        }
+    }};
+}
+
+/// Stores a new OutboundDelivery to the underlying database.
+pub(crate) async fn create_outbound_delivery(
+    conn: &mut DbConnection,
+    delivery: &OutboundDelivery,
+) -> Result<(), RequestProcessorError> {
+    let id_str = delivery.id.to_string();
+    let req_id_str = delivery.request_processor_id.to_string();
+    let conv_id_str = delivery.conversation_id.to_string();
+    let request = serde_json::to_string(&delivery.request)?;
+    let status = delivery.status.as_ref();
+    let next_attempt_at_str = to_db_timestamp(&delivery.next_attempt_at);
+    let created_at_str = delivery.created_at.to_rfc3339();
+    let updated_at_str = delivery.updated_at.to_rfc3339();
+    sqlx::query!(
+        r#"INSERT INTO outbound_delivery
+                    (id, request_processor, request_conversation, inc, url, request, status,
+                     attempts, max_attempts, backoff_ms, next_attempt_at, created_at, updated_at,
+                     last_error)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)"#,
+        id_str,
+        req_id_str,
+        conv_id_str,
+        delivery.inc,
+        delivery.url,
+        request,
+        status,
+        delivery.attempts,
+        delivery.max_attempts,
+        delivery.backoff_ms,
+        next_attempt_at_str,
+        created_at_str,
+        updated_at_str,
+        delivery.last_error,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Fetches an OutboundDelivery by its Uuid.
+pub(crate) async fn get_outbound_delivery(
+    conn: &mut DbConnection,
+    id: &Uuid,
+) -> Result<OutboundDelivery, RequestProcessorError> {
+    let id_str = id.to_string();
+    let row = sqlx::query!(
+        r#"SELECT id, request_processor, request_conversation, inc AS "inc: i32", url, request,
+                  status, attempts AS "attempts: i32", max_attempts AS "max_attempts: i32",
+                  backoff_ms AS "backoff_ms: i64", next_attempt_at, created_at, updated_at,
+                  last_error
+           FROM outbound_delivery
+           WHERE id = ?1"#,
+        id_str,
+    )
+    .fetch_optional(conn)
+    .await?
+    .ok_or(RequestProcessorError::NotFound {
+        kind: "OutboundDelivery".to_string(),
+        id: *id,
+    })?;
+
+    Ok(delivery_from_row!(row))
+}
+
+/// Lists all OutboundDeliveries, optionally filtered by status, newest first.
+pub(crate) async fn list_outbound_deliveries(
+    conn: &mut DbConnection,
+    status: Option<DeliveryStatus>,
+) -> Result<Vec<OutboundDelivery>, RequestProcessorError> {
+    let status = status.as_ref().map(|s| s.as_ref());
+    let rows = sqlx::query!(
+        r#"SELECT id, request_processor, request_conversation, inc AS "inc: i32", url, request,
+                  status, attempts AS "attempts: i32", max_attempts AS "max_attempts: i32",
+                  backoff_ms AS "backoff_ms: i64", next_attempt_at, created_at, updated_at,
+                  last_error
+           FROM outbound_delivery
+           WHERE ?1 IS NULL OR status = ?1
+           ORDER BY created_at DESC"#,
+        status,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut deliveries = Vec::new();
+    for row in rows {
+        deliveries.push(delivery_from_row!(row));
+    }
+
+    Ok(deliveries)
+}
+
+/// Fetches up to `limit` pending OutboundDeliveries, which are due at `now`.
+pub(crate) async fn get_due_outbound_deliveries(
+    conn: &mut DbConnection,
+    now: &DateTime<Utc>,
+    limit: i32,
+) -> Result<Vec<OutboundDelivery>, RequestProcessorError> {
+    let pending = DeliveryStatus::Pending.as_ref();
+    let now_str = to_db_timestamp(now);
+    let rows = sqlx::query!(
+        r#"SELECT id, request_processor, request_conversation, inc AS "inc: i32", url, request,
+                  status, attempts AS "attempts: i32", max_attempts AS "max_attempts: i32",
+                  backoff_ms AS "backoff_ms: i64", next_attempt_at, created_at, updated_at,
+                  last_error
+           FROM outbound_delivery
+           WHERE status = ?1 AND next_attempt_at <= ?2
+           ORDER BY next_attempt_at
+           LIMIT ?3"#,
+        pending,
+        now_str,
+        limit,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut deliveries = Vec::new();
+    for row in rows {
+        deliveries.push(delivery_from_row!(row));
+    }
+
+    Ok(deliveries)
+}
+
+/// Stores the state of an OutboundDelivery.
+async fn update_outbound_delivery(
+    conn: &mut DbConnection,
+    delivery: &OutboundDelivery,
+) -> Result<(), RequestProcessorError> {
+    let id_str = delivery.id.to_string();
+    let status = delivery.status.as_ref();
+    let next_attempt_at_str = to_db_timestamp(&delivery.next_attempt_at);
+    let updated_at_str = delivery.updated_at.to_rfc3339();
+    sqlx::query!(
+        r#"UPDATE outbound_delivery
+           SET status=?1, attempts=?2, next_attempt_at=?3, updated_at=?4, last_error=?5
+           WHERE id=?6"#,
+        status,
+        delivery.attempts,
+        next_attempt_at_str,
+        updated_at_str,
+        delivery.last_error,
+        id_str,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Records the outcome of a delivery attempt, see
+/// [`OutboundDelivery::record_attempt`]. `error` is `None` for a successful
+/// attempt.
+pub(crate) async fn record_delivery_attempt(
+    conn: &mut DbConnection,
+    id: &Uuid,
+    error: Option<String>,
+) -> Result<OutboundDelivery, RequestProcessorError> {
+    let mut delivery = get_outbound_delivery(conn, id).await?;
+    delivery.record_attempt(error, Utc::now());
+    update_outbound_delivery(conn, &delivery).await?;
+
+    Ok(delivery)
+}
+
+/// Schedules an OutboundDelivery for an immediate attempt. A dead delivery,
+/// which already used up its attempts, gets exactly one more attempt.
+pub(crate) async fn retry_outbound_delivery(
+    conn: &mut DbConnection,
+    id: &Uuid,
+) -> Result<OutboundDelivery, RequestProcessorError> {
+    let mut delivery = get_outbound_delivery(conn, id).await?;
+    if delivery.status == DeliveryStatus::Delivered {
+        return Err(RequestProcessorError::Validation(format!(
+            "OutboundDelivery {} was already delivered",
+            id
+        )));
+    }
+
+    let now = Utc::now();
+    delivery.status = DeliveryStatus::Pending;
+    delivery.next_attempt_at = now;
+    delivery.updated_at = now;
+    update_outbound_delivery(conn, &delivery).await?;
+
+    Ok(delivery)
+}
+
+/// Deletes an OutboundDelivery with the given Uuid.
+pub(crate) async fn delete_outbound_delivery(
+    conn: &mut DbConnection,
+    id: &Uuid,
+) -> Result<(), RequestProcessorError> {
+    let id_str = id.to_string();
+    let res = sqlx::query!(
+        r#"DELETE FROM outbound_delivery
+           WHERE id=?1"#,
+        id_str,
+    )
+    .execute(conn)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err(RequestProcessorError::NotFound {
+            kind: "OutboundDelivery".to_string(),
+            id: *id,
+        });
+    }
+
+    Ok(())
+}
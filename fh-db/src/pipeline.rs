@@ -0,0 +1,211 @@
+//! Database structs and functions for the Pipeline entity, which chains
+//! [`crate::request_processor::RequestProcessor`]s declaratively: the response
+//! of a step is the request of the next one.
+use super::{request_processor::get_request_processor, RequestProcessorError};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use fh_core::DbConnection;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Ordered list of RequestProcessors, which are run one after another.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pipeline {
+    #[serde(skip_deserializing)]
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub name: String,
+    pub steps: Vec<PipelineStep>,
+    #[serde(skip_deserializing)]
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// A single RequestProcessor run of a [`Pipeline`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineStep {
+    pub request_processor_id: Uuid,
+    /// Runs the code with prelude and sequel, like
+    /// `/processor/{processor_id}/run_with_prelude`.
+    #[serde(default = "default_prelude")]
+    pub prelude: bool,
+    /// Additional attempts, if the step fails.
+    #[serde(default)]
+    pub retries: u32,
+    #[serde(default)]
+    pub on_error: StepErrorPolicy,
+}
+
+fn default_prelude() -> bool {
+    true
+}
+
+/// Decides, what happens after a step failed on all attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepErrorPolicy {
+    /// Stops the pipeline and responds with the error of the step.
+    Abort,
+    /// Skips the step, so the next step receives the input of the failed one.
+    Continue,
+}
+
+impl Default for StepErrorPolicy {
+    fn default() -> Self {
+        StepErrorPolicy::Abort
+    }
+}
+
+/// Upper bound of [`PipelineStep::retries`].
+const MAX_RETRIES: u32 = 10;
+
+/// Validates the pipeline and checks, that all RequestProcessors of its steps
+/// exist and may be chained.
+async fn validate(conn: &mut DbConnection, data: &Pipeline) -> Result<(), RequestProcessorError> {
+    if data.name.trim().is_empty() {
+        return Err(RequestProcessorError::Validation(
+            "The name of a pipeline must not be empty".to_string(),
+        ));
+    }
+    if data.steps.is_empty() {
+        return Err(RequestProcessorError::Validation(
+            "A pipeline needs at least one step".to_string(),
+        ));
+    }
+
+    for (idx, step) in data.steps.iter().enumerate() {
+        if step.retries > MAX_RETRIES {
+            return Err(RequestProcessorError::Validation(format!(
+                "Step {}: at most {} retries are allowed",
+                idx, MAX_RETRIES
+            )));
+        }
+
+        match get_request_processor(conn, &step.request_processor_id).await {
+            Err(RequestProcessorError::NotFound { kind, id }) => {
+                return Err(RequestProcessorError::Validation(format!(
+                    "Step {}: {} with id {} not found",
+                    idx, kind, id
+                )))
+            }
+            Err(e) => return Err(e),
+            Ok(processor) => {
+                if let Err(RequestProcessorError::Validation(reason)) = processor.check_chainable()
+                {
+                    return Err(RequestProcessorError::Validation(format!(
+                        "Step {}: {}",
+                        idx, reason
+                    )));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Stores a new Pipeline to the underlying database.
+pub(crate) async fn create_pipeline(
+    conn: &mut DbConnection,
+    data: &Pipeline,
+) -> Result<(), RequestProcessorError> {
+    validate(conn, data).await?;
+
+    let id_str = data.id.to_string();
+    let steps = serde_json::to_string(&data.steps)?;
+    let created_at_str = data.created_at.to_rfc3339();
+    sqlx::query!(
+        r#"INSERT INTO pipeline
+                    (id, name, steps, created_at)
+                    VALUES (?1, ?2, ?3, ?4)"#,
+        id_str,
+        data.name,
+        steps,
+        created_at_str,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Fetches a Pipeline with the given Uuid.
+pub(crate) async fn get_pipeline(
+    conn: &mut DbConnection,
+    id: &Uuid,
+) -> Result<Pipeline, RequestProcessorError> {
+    let id_str = id.to_string();
+    let row = sqlx::query!(
+        r#"SELECT id, name, steps, created_at
+           FROM pipeline
+           WHERE id = ?1"#,
+        id_str,
+    )
+    .fetch_optional(conn)
+    .await?
+    .ok_or_else(|| RequestProcessorError::NotFound {
+        kind: "Pipeline".to_string(),
+        id: *id,
+    })?;
+
+    Ok(Pipeline {
+        id: Uuid::from_str(&row.id)?,
+        name: row.name,
+        steps: serde_json::from_str(&row.steps)?,
+        created_at: DateTime::parse_from_rfc3339(&row.created_at)?.with_timezone(&Utc),
+    })
+}
+
+/// Replaces name and steps of an existing Pipeline.
+pub(crate) async fn update_pipeline(
+    conn: &mut DbConnection,
+    id: &Uuid,
+    data: &mut Pipeline,
+) -> Result<(), RequestProcessorError> {
+    let existing = get_pipeline(conn, id).await?;
+    validate(conn, data).await?;
+
+    let id_str = id.to_string();
+    let steps = serde_json::to_string(&data.steps)?;
+    sqlx::query!(
+        r#"UPDATE pipeline
+           SET name=?1, steps=?2
+           WHERE id=?3"#,
+        data.name,
+        steps,
+        id_str,
+    )
+    .execute(conn)
+    .await?;
+
+    data.id = *id;
+    data.created_at = existing.created_at;
+
+    Ok(())
+}
+
+/// Deletes a Pipeline with the given Uuid.
+pub(crate) async fn delete_pipeline(
+    conn: &mut DbConnection,
+    id: &Uuid,
+) -> Result<(), RequestProcessorError> {
+    let id_str = id.to_string();
+    let res = sqlx::query!(
+        r#"DELETE FROM pipeline
+           WHERE id=?1"#,
+        id_str,
+    )
+    .execute(conn)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err(RequestProcessorError::NotFound {
+            kind: "Pipeline".to_string(),
+            id: *id,
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,201 @@
+//! Database structs and functions for the ProcessorJob entity. A ProcessorJob
+//! is a durable queue entry for an asynchronous invocation of a
+//! [`crate::request_processor::RequestProcessor`].
+use super::RequestProcessorError;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use fh_core::DbConnection;
+use serde::{Deserialize, Serialize};
+use std::{convert::AsRef, str::FromStr};
+use strum_macros::{self, AsRefStr, EnumString};
+use uuid::Uuid;
+
+/// Variants of the states, a ProcessorJob passes through.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, AsRefStr, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ProcessorJobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// Queued asynchronous invocation of a RequestProcessor. The conversation is
+/// created upfront, so its Uuid can be returned to the caller immediately.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessorJob {
+    pub id: Uuid,
+    pub request_processor_id: Uuid,
+    pub conversation_id: Uuid,
+    pub prelude: bool,
+    pub request: fh_core::request::Request,
+    pub status: ProcessorJobStatus,
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub error: Option<String>,
+}
+
+impl ProcessorJob {
+    /// Creates a new, queued ProcessorJob.
+    pub fn new(
+        request_processor_id: Uuid,
+        conversation_id: Uuid,
+        prelude: bool,
+        request: fh_core::request::Request,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            request_processor_id,
+            conversation_id,
+            prelude,
+            request,
+            status: ProcessorJobStatus::Queued,
+            attempts: 0,
+            created_at: now,
+            updated_at: now,
+            error: None,
+        }
+    }
+}
+
+/// Stores a new ProcessorJob to the underlying database.
+pub(crate) async fn create_processor_job(
+    conn: &mut DbConnection,
+    job: &ProcessorJob,
+) -> Result<(), RequestProcessorError> {
+    let id_str = job.id.to_string();
+    let req_id_str = job.request_processor_id.to_string();
+    let conv_id_str = job.conversation_id.to_string();
+    let request = serde_json::to_string(&job.request)?;
+    let status = job.status.as_ref();
+    let created_at_str = job.created_at.to_rfc3339();
+    let updated_at_str = job.updated_at.to_rfc3339();
+    sqlx::query!(
+        r#"INSERT INTO processor_job
+                    (id, request_processor, request_conversation, prelude, request, status,
+                     attempts, created_at, updated_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"#,
+        id_str,
+        req_id_str,
+        conv_id_str,
+        job.prelude,
+        request,
+        status,
+        job.attempts,
+        created_at_str,
+        updated_at_str,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Fetches the oldest queued ProcessorJob and marks it as running. Returns
+/// `None`, if the queue is empty.
+pub(crate) async fn claim_next_processor_job(
+    conn: &mut DbConnection,
+) -> Result<Option<ProcessorJob>, RequestProcessorError> {
+    let queued = ProcessorJobStatus::Queued.as_ref();
+    let row = sqlx::query!(
+        r#"SELECT id, request_processor, request_conversation, prelude AS "prelude: bool",
+                  request, status, attempts AS "attempts: i32", created_at, updated_at, error
+           FROM processor_job
+           WHERE status = ?1
+           ORDER BY created_at
+           LIMIT 1"#,
+        queued,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let row = match row {
+        None => return Ok(None),
+        Some(row) => row,
+    };
+
+    let now = Utc::now();
+    let mut job = ProcessorJob {
+        id: Uuid::from_str(&row.id)?,
+        request_processor_id: Uuid::from_str(&row.request_processor)?,
+        conversation_id: Uuid::from_str(&row.request_conversation)?,
+        prelude: row.prelude,
+        request: serde_json::from_str(&row.request)?,
+        status: ProcessorJobStatus::from_str(&row.status)?,
+        attempts: row.attempts,
+        created_at: DateTime::parse_from_rfc3339(&row.created_at)?.with_timezone(&Utc),
+        updated_at: DateTime::parse_from_rfc3339(&row.updated_at)?.with_timezone(&Utc),
+        error: row.error,
+    };
+
+    job.status = ProcessorJobStatus::Running;
+    job.attempts += 1;
+    job.updated_at = now;
+
+    let running = job.status.as_ref();
+    let updated_at_str = now.to_rfc3339();
+    sqlx::query!(
+        r#"UPDATE processor_job
+           SET status=?1, attempts=?2, updated_at=?3
+           WHERE id=?4"#,
+        running,
+        job.attempts,
+        updated_at_str,
+        row.id,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(Some(job))
+}
+
+/// Marks a ProcessorJob as finished, either succeeded or failed.
+pub(crate) async fn finish_processor_job(
+    conn: &mut DbConnection,
+    id: &Uuid,
+    status: ProcessorJobStatus,
+    error: Option<String>,
+) -> Result<(), RequestProcessorError> {
+    let id_str = id.to_string();
+    let status = status.as_ref();
+    let updated_at_str = Utc::now().to_rfc3339();
+    sqlx::query!(
+        r#"UPDATE processor_job
+           SET status=?1, error=?2, updated_at=?3
+           WHERE id=?4"#,
+        status,
+        error,
+        updated_at_str,
+        id_str,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Puts all running ProcessorJobs back into the queue. This is needed on
+/// startup, when jobs were interrupted by a crash or restart. Returns the
+/// number of requeued jobs.
+pub(crate) async fn requeue_running_processor_jobs(
+    conn: &mut DbConnection,
+) -> Result<u64, RequestProcessorError> {
+    let queued = ProcessorJobStatus::Queued.as_ref();
+    let running = ProcessorJobStatus::Running.as_ref();
+    let updated_at_str = Utc::now().to_rfc3339();
+    let res = sqlx::query!(
+        r#"UPDATE processor_job
+           SET status=?1, updated_at=?2
+           WHERE status=?3"#,
+        queued,
+        updated_at_str,
+        running,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(res.rows_affected())
+}
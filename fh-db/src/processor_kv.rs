@@ -0,0 +1,202 @@
+//! Database structs and functions for the per-processor key-value store. Each
+//! [`crate::request_processor::RequestProcessor`] owns its own namespace of
+//! keys, so values survive between single invocations.
+use super::{to_db_timestamp, RequestProcessorError};
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use fh_core::DbConnection;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Maximum length of a single key in bytes.
+pub const KV_MAX_KEY_LENGTH: usize = 512;
+
+/// Maximum size of a single JSON serialized value in bytes.
+pub const KV_MAX_VALUE_SIZE: usize = 64 * 1024;
+
+/// Maximum number of (non-expired) keys per RequestProcessor.
+pub const KV_MAX_ENTRIES: i32 = 1000;
+
+/// A single entry of the key-value store. The value can be any JSON value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KvEntry {
+    pub key: String,
+    pub value: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Fetches a single, non-expired entry for the given RequestProcessor Uuid and
+/// key. Returns `None` if the key does not exist (anymore).
+pub(crate) async fn get_kv_entry(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+    key: &str,
+) -> Result<Option<KvEntry>, RequestProcessorError> {
+    let req_id_str = request_processor_id.to_string();
+    let now_str = to_db_timestamp(&Utc::now());
+    let row = sqlx::query!(
+        r#"SELECT * FROM processor_kv
+           WHERE request_processor = ?1 AND key = ?2
+             AND (expires_at IS NULL OR expires_at > ?3)"#,
+        req_id_str,
+        key,
+        now_str,
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    match row {
+        None => Ok(None),
+        Some(row) => Ok(Some(KvEntry {
+            key: row.key,
+            value: serde_json::from_str(&row.value)?,
+            created_at: DateTime::parse_from_rfc3339(&row.created_at)?.with_timezone(&Utc),
+            updated_at: DateTime::parse_from_rfc3339(&row.updated_at)?.with_timezone(&Utc),
+            expires_at: match row.expires_at {
+                Some(x) => Some(DateTime::parse_from_rfc3339(&x)?.with_timezone(&Utc)),
+                None => None,
+            },
+        })),
+    }
+}
+
+/// Creates or overwrites an entry. If `ttl` (in seconds) is given, the entry
+/// expires after that time. Enforces the key-, value- and entry-count quotas.
+pub(crate) async fn set_kv_entry(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+    key: &str,
+    value: &serde_json::Value,
+    ttl: Option<i64>,
+) -> Result<KvEntry, RequestProcessorError> {
+    if key.is_empty() {
+        return Err(RequestProcessorError::Validation(
+            "Key must not be empty".to_string(),
+        ));
+    }
+
+    if key.len() > KV_MAX_KEY_LENGTH {
+        return Err(RequestProcessorError::QuotaExceeded(format!(
+            "Key exceeds the maximum length of {} bytes",
+            KV_MAX_KEY_LENGTH
+        )));
+    }
+
+    if let Some(ttl) = ttl {
+        if ttl <= 0 {
+            return Err(RequestProcessorError::Validation(
+                "TTL must be a positive number of seconds".to_string(),
+            ));
+        }
+    }
+
+    let value_str = serde_json::to_string(value)?;
+    if value_str.len() > KV_MAX_VALUE_SIZE {
+        return Err(RequestProcessorError::QuotaExceeded(format!(
+            "Value exceeds the maximum size of {} bytes",
+            KV_MAX_VALUE_SIZE
+        )));
+    }
+
+    let req_id_str = request_processor_id.to_string();
+    let now = Utc::now();
+    let now_str = to_db_timestamp(&now);
+
+    // expired entries are purged lazily, so they don't count against the quota
+    sqlx::query!(
+        r#"DELETE FROM processor_kv
+           WHERE request_processor = ?1 AND expires_at IS NOT NULL AND expires_at <= ?2"#,
+        req_id_str,
+        now_str,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let count = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count: i32" FROM processor_kv
+           WHERE request_processor = ?1 AND key != ?2"#,
+        req_id_str,
+        key,
+    )
+    .fetch_one(&mut *conn)
+    .await?
+    .count;
+
+    if count >= KV_MAX_ENTRIES {
+        return Err(RequestProcessorError::QuotaExceeded(format!(
+            "RequestProcessor {} already stores the maximum of {} keys",
+            request_processor_id, KV_MAX_ENTRIES
+        )));
+    }
+
+    let expires_at = ttl.map(|ttl| now + Duration::seconds(ttl));
+    let expires_at_str = expires_at.as_ref().map(to_db_timestamp);
+    sqlx::query!(
+        r#"INSERT INTO processor_kv
+                    (request_processor, key, value, created_at, updated_at, expires_at)
+                    VALUES (?1, ?2, ?3, ?4, ?4, ?5)
+           ON CONFLICT(request_processor, key) DO UPDATE
+           SET value=excluded.value, updated_at=excluded.updated_at, expires_at=excluded.expires_at"#,
+        req_id_str,
+        key,
+        value_str,
+        now_str,
+        expires_at_str,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    get_kv_entry(conn, request_processor_id, key)
+        .await?
+        .ok_or(RequestProcessorError::Custom(format!(
+            "KvEntry '{}' vanished after storing it",
+            key
+        )))
+}
+
+/// Deletes an entry. Returns `true`, if there was an entry to delete.
+pub(crate) async fn delete_kv_entry(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+    key: &str,
+) -> Result<bool, RequestProcessorError> {
+    let req_id_str = request_processor_id.to_string();
+    let res = sqlx::query!(
+        r#"DELETE FROM processor_kv
+           WHERE request_processor = ?1 AND key = ?2"#,
+        req_id_str,
+        key,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Lists all non-expired keys of a RequestProcessor, optionally filtered by
+/// the given key prefix. The output is sorted alphabetically.
+pub(crate) async fn list_kv_keys(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+    prefix: Option<&str>,
+) -> Result<Vec<String>, RequestProcessorError> {
+    let req_id_str = request_processor_id.to_string();
+    let now_str = to_db_timestamp(&Utc::now());
+    let prefix = prefix.unwrap_or("");
+    let rows = sqlx::query!(
+        r#"SELECT key FROM processor_kv
+           WHERE request_processor = ?1
+             AND (expires_at IS NULL OR expires_at > ?2)
+             AND substr(key, 1, length(?3)) = ?3
+           ORDER BY key"#,
+        req_id_str,
+        now_str,
+        prefix,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.key).collect())
+}
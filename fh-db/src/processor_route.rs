@@ -0,0 +1,347 @@
+//! Database structs and functions for the ProcessorRoute entity, which makes a
+//! [`crate::request_processor::RequestProcessor`] reachable at a custom host
+//! and path, besides `/processor/{processor_id}/run`.
+use super::{request_processor::get_request_processor, RequestProcessorError};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use fh_core::DbConnection;
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+use uuid::Uuid;
+
+/// First path segments of the built-in endpoints. Routes below them would
+/// never be reached.
+pub const RESERVED_PREFIXES: &[&str] = &[
+    "admin",
+    "conversation",
+    "healthz",
+    "hello",
+    "metrics",
+    "pipeline",
+    "processor",
+    "readyz",
+];
+
+/// Binding of a host and path pattern to a RequestProcessor. Parameters in the
+/// pattern, like `{device}` in `/hooks/ttn/{device}`, match a single path
+/// segment and are passed to the code as `path_params`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessorRoute {
+    #[serde(skip_deserializing)]
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    #[serde(skip_deserializing)]
+    #[serde(default = "Uuid::nil")]
+    pub request_processor_id: Uuid,
+    /// Host without port, e.g. `hooks.example.com`. Routes without host match
+    /// requests to any host.
+    #[serde(default)]
+    pub host: Option<String>,
+    pub path: String,
+    /// Allowed HTTP methods. An empty list allows all methods.
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// Runs the code with prelude and sequel, like
+    /// `/processor/{processor_id}/run_with_prelude`.
+    #[serde(default = "default_prelude")]
+    pub prelude: bool,
+    #[serde(skip_deserializing)]
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+}
+
+fn default_prelude() -> bool {
+    true
+}
+
+/// A single segment of a path pattern.
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+}
+
+impl ProcessorRoute {
+    /// Validates the route and brings host and methods into their canonical
+    /// form.
+    fn normalize(&mut self) -> Result<(), RequestProcessorError> {
+        parse_pattern(&self.path)?;
+
+        self.host = match self.host.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(host) => {
+                if host.contains(|c: char| c == ':' || c == '/' || c.is_whitespace()) {
+                    return Err(RequestProcessorError::Validation(format!(
+                        "Invalid host '{}', expected a host name without scheme or port",
+                        host
+                    )));
+                }
+                Some(host.to_ascii_lowercase())
+            }
+        };
+
+        let mut methods = Vec::new();
+        for method in &self.methods {
+            let method = method.trim().to_ascii_uppercase();
+            if method.is_empty() || !method.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(RequestProcessorError::Validation(format!(
+                    "Invalid HTTP method '{}'",
+                    method
+                )));
+            }
+            if !methods.contains(&method) {
+                methods.push(method);
+            }
+        }
+        self.methods = methods;
+
+        Ok(())
+    }
+
+    /// Matches the host and path of a request. Returns the percent-decoded
+    /// values of the path parameters, if the route matches. The port of the
+    /// host is ignored.
+    pub fn matches(&self, host: Option<&str>, path: &str) -> Option<HashMap<String, String>> {
+        if let Some(route_host) = &self.host {
+            match host.map(strip_port) {
+                Some(host) if host.eq_ignore_ascii_case(route_host) => {}
+                _ => return None,
+            }
+        }
+
+        let pattern = parse_pattern(&self.path).ok()?;
+        let segments = split_path(path);
+        if pattern.len() != segments.len() {
+            return None;
+        }
+
+        let mut params = HashMap::new();
+        for (expected, actual) in pattern.iter().zip(segments) {
+            match expected {
+                Segment::Literal(literal) if literal == actual => {}
+                Segment::Literal(_) => return None,
+                Segment::Param(_) if actual.is_empty() => return None,
+                Segment::Param(name) => {
+                    let value = percent_decode_str(actual).decode_utf8().ok()?;
+                    params.insert(name.clone(), value.into_owned());
+                }
+            }
+        }
+
+        Some(params)
+    }
+
+    /// Checks, if the route accepts requests with the given HTTP method.
+    pub fn allows_method(&self, method: &str) -> bool {
+        self.methods.is_empty() || self.methods.iter().any(|m| m.eq_ignore_ascii_case(method))
+    }
+
+    /// Routes conflict, if they could match the same request with the same
+    /// precedence: same host, patterns of the same shape and overlapping
+    /// methods.
+    pub fn conflicts_with(&self, other: &ProcessorRoute) -> bool {
+        if self.host != other.host {
+            return false;
+        }
+
+        let shapes_equal = match (parse_pattern(&self.path), parse_pattern(&other.path)) {
+            (Ok(a), Ok(b)) => {
+                a.len() == b.len()
+                    && a.iter().zip(b.iter()).all(|pair| match pair {
+                        (Segment::Literal(a), Segment::Literal(b)) => a == b,
+                        (Segment::Param(_), Segment::Param(_)) => true,
+                        _ => false,
+                    })
+            }
+            _ => false,
+        };
+
+        shapes_equal
+            && (self.methods.is_empty()
+                || other.methods.is_empty()
+                || self.methods.iter().any(|m| other.methods.contains(m)))
+    }
+
+    /// Sort key, which puts more specific routes first: routes with a host
+    /// before routes for any host, literal segments before parameters.
+    pub fn precedence(&self) -> (bool, Vec<bool>) {
+        let params = parse_pattern(&self.path)
+            .unwrap_or_default()
+            .iter()
+            .map(|segment| matches!(segment, Segment::Param(_)))
+            .collect();
+
+        (self.host.is_none(), params)
+    }
+}
+
+/// Removes the port of a `Host` header value, also for IPv6 addresses.
+fn strip_port(host: &str) -> &str {
+    match host.find(']') {
+        Some(end) if host.starts_with('[') => &host[..=end],
+        _ => host.split(':').next().unwrap_or(host),
+    }
+}
+
+/// Splits a path into its segments. A trailing slash is ignored.
+fn split_path(path: &str) -> Vec<&str> {
+    let path = path.trim_start_matches('/').trim_end_matches('/');
+    if path.is_empty() {
+        return Vec::new();
+    }
+
+    path.split('/').collect()
+}
+
+/// Parses a path pattern like `/hooks/ttn/{device}`.
+fn parse_pattern(path: &str) -> Result<Vec<Segment>, RequestProcessorError> {
+    let invalid = |reason: &str| {
+        RequestProcessorError::Validation(format!("Invalid path '{}': {}", path, reason))
+    };
+
+    if !path.starts_with('/') {
+        return Err(invalid("must start with '/'"));
+    }
+
+    let mut names = HashSet::new();
+    let mut segments = Vec::new();
+    for segment in split_path(path) {
+        if segment.is_empty() {
+            return Err(invalid("contains an empty segment"));
+        }
+
+        if segment.starts_with('{') && segment.ends_with('}') {
+            let name = &segment[1..segment.len() - 1];
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(invalid(
+                    "parameter names may only contain letters, digits and '_'",
+                ));
+            }
+            if !names.insert(name) {
+                return Err(invalid("parameter names must be unique"));
+            }
+            segments.push(Segment::Param(name.to_string()));
+        } else if segment.contains(|c| c == '{' || c == '}') {
+            return Err(invalid("parameters must span a whole segment"));
+        } else {
+            segments.push(Segment::Literal(segment.to_string()));
+        }
+    }
+
+    if let Some(Segment::Literal(first)) = segments.first() {
+        if RESERVED_PREFIXES.contains(&first.as_str()) {
+            return Err(invalid("is reserved for built-in endpoints"));
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Stores a new ProcessorRoute to the underlying database. Fails with
+/// [`RequestProcessorError::Conflict`], if it conflicts with an existing
+/// route of any RequestProcessor.
+pub(crate) async fn create_processor_route(
+    conn: &mut DbConnection,
+    data: &mut ProcessorRoute,
+) -> Result<(), RequestProcessorError> {
+    data.normalize()?;
+    let _ = get_request_processor(conn, &data.request_processor_id).await?;
+
+    if let Some(existing) = list_processor_routes(conn, None)
+        .await?
+        .into_iter()
+        .find(|route| route.conflicts_with(data))
+    {
+        return Err(RequestProcessorError::Conflict(format!(
+            "Route conflicts with route {} of RequestProcessor {}",
+            existing.id, existing.request_processor_id
+        )));
+    }
+
+    let id_str = data.id.to_string();
+    let req_id_str = data.request_processor_id.to_string();
+    let methods = serde_json::to_string(&data.methods)?;
+    let created_at_str = data.created_at.to_rfc3339();
+    sqlx::query!(
+        r#"INSERT INTO processor_route
+                    (id, request_processor, host, path, methods, prelude, created_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"#,
+        id_str,
+        req_id_str,
+        data.host,
+        data.path,
+        methods,
+        data.prelude,
+        created_at_str,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Fetches the ProcessorRoutes of a RequestProcessor or, without Uuid, of all
+/// RequestProcessors.
+pub(crate) async fn list_processor_routes(
+    conn: &mut DbConnection,
+    request_processor_id: Option<Uuid>,
+) -> Result<Vec<ProcessorRoute>, RequestProcessorError> {
+    if let Some(id) = &request_processor_id {
+        let _ = get_request_processor(conn, id).await?;
+    }
+
+    let req_id_str = request_processor_id.map(|id| id.to_string());
+    let rows = sqlx::query!(
+        r#"SELECT id, request_processor, host, path, methods, prelude AS "prelude: bool",
+                  created_at
+           FROM processor_route
+           WHERE ?1 IS NULL OR request_processor = ?1
+           ORDER BY created_at"#,
+        req_id_str,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut routes = Vec::new();
+    for row in rows {
+        routes.push(ProcessorRoute {
+            id: Uuid::from_str(&row.id)?,
+            request_processor_id: Uuid::from_str(&row.request_processor)?,
+            host: row.host,
+            path: row.path,
+            methods: serde_json::from_str(&row.methods)?,
+            prelude: row.prelude,
+            created_at: DateTime::parse_from_rfc3339(&row.created_at)?.with_timezone(&Utc),
+        });
+    }
+
+    Ok(routes)
+}
+
+/// Deletes a ProcessorRoute with the given Uuid.
+pub(crate) async fn delete_processor_route(
+    conn: &mut DbConnection,
+    id: &Uuid,
+) -> Result<(), RequestProcessorError> {
+    let id_str = id.to_string();
+    let res = sqlx::query!(
+        r#"DELETE FROM processor_route
+           WHERE id=?1"#,
+        id_str,
+    )
+    .execute(conn)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err(RequestProcessorError::NotFound {
+            kind: "ProcessorRoute".to_string(),
+            id: *id,
+        });
+    }
+
+    Ok(())
+}
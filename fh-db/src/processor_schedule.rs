@@ -0,0 +1,198 @@
+//! Database structs and functions for the ProcessorSchedule entity, which
+//! triggers a [`crate::request_processor::RequestProcessor`] periodically.
+use super::{request_processor::get_request_processor, RequestProcessorError};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use fh_core::DbConnection;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// A cron expression attached to a RequestProcessor. The cron expression has
+/// the format `sec min hour day-of-month month day-of-week [year]`. The classic
+/// five field format without seconds is accepted as well.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessorSchedule {
+    #[serde(skip_deserializing)]
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    #[serde(skip_deserializing)]
+    #[serde(default = "Uuid::nil")]
+    pub request_processor_id: Uuid,
+    pub cron: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(skip_deserializing)]
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_deserializing)]
+    pub last_run_at: Option<DateTime<Utc>>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl ProcessorSchedule {
+    /// Computes the next point in time, when the schedule is due. The
+    /// computation starts at the last run or, if it never ran, at the
+    /// schedule's creation time. So a due time in the past means, that the
+    /// schedule has to be run now.
+    pub fn next_run(&self) -> Result<Option<DateTime<Utc>>, RequestProcessorError> {
+        let since = self.last_run_at.unwrap_or(self.created_at);
+        Ok(parse_cron(&self.cron)?.after(&since).next())
+    }
+}
+
+/// Parses a cron expression. Expressions with five fields get a leading
+/// seconds field `0`.
+fn parse_cron(expr: &str) -> Result<Schedule, RequestProcessorError> {
+    let expr = expr.trim();
+    let expr = if expr.split_whitespace().count() == 5 {
+        format!("0 {}", expr)
+    } else {
+        expr.to_string()
+    };
+
+    Schedule::from_str(&expr).map_err(|e| {
+        RequestProcessorError::Validation(format!("Invalid cron expression '{}': {}", expr, e))
+    })
+}
+
+/// Stores a new ProcessorSchedule to the underlying database.
+pub(crate) async fn create_processor_schedule(
+    conn: &mut DbConnection,
+    data: &ProcessorSchedule,
+) -> Result<(), RequestProcessorError> {
+    parse_cron(&data.cron)?;
+    let _ = get_request_processor(conn, &data.request_processor_id).await?;
+
+    let id_str = data.id.to_string();
+    let req_id_str = data.request_processor_id.to_string();
+    let created_at_str = data.created_at.to_rfc3339();
+    sqlx::query!(
+        r#"INSERT INTO processor_schedule
+                    (id, request_processor, cron, enabled, created_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5)"#,
+        id_str,
+        req_id_str,
+        data.cron,
+        data.enabled,
+        created_at_str,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Fetches all ProcessorSchedules of a RequestProcessor.
+pub(crate) async fn list_processor_schedules(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+) -> Result<Vec<ProcessorSchedule>, RequestProcessorError> {
+    let _ = get_request_processor(conn, request_processor_id).await?;
+    let req_id_str = request_processor_id.to_string();
+    let rows = sqlx::query!(
+        r#"SELECT id, request_processor, cron, enabled AS "enabled: bool", created_at, last_run_at
+           FROM processor_schedule
+           WHERE request_processor = ?1
+           ORDER BY created_at"#,
+        req_id_str,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut schedules = Vec::new();
+    for row in rows {
+        schedules.push(ProcessorSchedule {
+            id: Uuid::from_str(&row.id)?,
+            request_processor_id: Uuid::from_str(&row.request_processor)?,
+            cron: row.cron,
+            enabled: row.enabled,
+            created_at: DateTime::parse_from_rfc3339(&row.created_at)?.with_timezone(&Utc),
+            last_run_at: match row.last_run_at {
+                Some(x) => Some(DateTime::parse_from_rfc3339(&x)?.with_timezone(&Utc)),
+                None => None,
+            },
+        });
+    }
+
+    Ok(schedules)
+}
+
+/// Fetches all enabled ProcessorSchedules of all RequestProcessors.
+pub(crate) async fn get_enabled_processor_schedules(
+    conn: &mut DbConnection,
+) -> Result<Vec<ProcessorSchedule>, RequestProcessorError> {
+    let rows = sqlx::query!(
+        r#"SELECT id, request_processor, cron, enabled AS "enabled: bool", created_at, last_run_at
+           FROM processor_schedule
+           WHERE enabled = 1"#,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut schedules = Vec::new();
+    for row in rows {
+        schedules.push(ProcessorSchedule {
+            id: Uuid::from_str(&row.id)?,
+            request_processor_id: Uuid::from_str(&row.request_processor)?,
+            cron: row.cron,
+            enabled: row.enabled,
+            created_at: DateTime::parse_from_rfc3339(&row.created_at)?.with_timezone(&Utc),
+            last_run_at: match row.last_run_at {
+                Some(x) => Some(DateTime::parse_from_rfc3339(&x)?.with_timezone(&Utc)),
+                None => None,
+            },
+        });
+    }
+
+    Ok(schedules)
+}
+
+/// Stores the time of the last run of a ProcessorSchedule.
+pub(crate) async fn update_processor_schedule_last_run(
+    conn: &mut DbConnection,
+    id: &Uuid,
+    last_run_at: &DateTime<Utc>,
+) -> Result<(), RequestProcessorError> {
+    let id_str = id.to_string();
+    let last_run_at_str = last_run_at.to_rfc3339();
+    sqlx::query!(
+        r#"UPDATE processor_schedule
+           SET last_run_at=?1
+           WHERE id=?2"#,
+        last_run_at_str,
+        id_str,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Deletes a ProcessorSchedule with the given Uuid.
+pub(crate) async fn delete_processor_schedule(
+    conn: &mut DbConnection,
+    id: &Uuid,
+) -> Result<(), RequestProcessorError> {
+    let id_str = id.to_string();
+    let res = sqlx::query!(
+        r#"DELETE FROM processor_schedule
+           WHERE id=?1"#,
+        id_str,
+    )
+    .execute(conn)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err(RequestProcessorError::NotFound {
+            kind: "ProcessorSchedule".to_string(),
+            id: *id,
+        });
+    }
+
+    Ok(())
+}
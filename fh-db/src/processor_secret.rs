@@ -0,0 +1,273 @@
+//! Database structs and functions for per-processor secrets. Secret values are
+//! encrypted at rest using AES-256-GCM with a server key, which is read from
+//! the environment variable [`SECRET_KEY_ENV`].
+use super::{request_processor::get_request_processor, to_db_timestamp, RequestProcessorError};
+use aes_gcm::aead::{generic_array::GenericArray, Aead, NewAead, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use fh_core::DbConnection;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env, fmt};
+use uuid::Uuid;
+
+/// Name of the environment variable, which contains the base64 encoded 256 bit
+/// server key.
+pub const SECRET_KEY_ENV: &str = "FH_SECRET_KEY";
+
+/// Wrapper type for plaintext secret values. Makes sure, that a secret value
+/// never shows up in debug output, e.g. when a [`crate::ReqCmd`] is logged.
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct SecretValue(String);
+
+impl SecretValue {
+    pub fn new(value: String) -> Self {
+        Self(value)
+    }
+
+    /// Exposes the plaintext secret value.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretValue(***)")
+    }
+}
+
+/// Metadata of a stored secret. The value itself is never part of this struct,
+/// so it can't be read back via the admin API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessorSecretInfo {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Checks, that a valid server key is set. Called at startup, so a missing key
+/// is noticed before the first secret is handled.
+pub fn check_secret_key() -> Result<(), RequestProcessorError> {
+    cipher().map(|_| ())
+}
+
+/// Creates the cipher from the server key in the environment.
+fn cipher() -> Result<Aes256Gcm, RequestProcessorError> {
+    let key = env::var(SECRET_KEY_ENV)
+        .ok()
+        .filter(|key| !key.trim().is_empty())
+        .ok_or_else(|| {
+            RequestProcessorError::Custom(format!(
+                "Environment variable '{}' must be set to handle secrets",
+                SECRET_KEY_ENV
+            ))
+        })?;
+    let key = base64::decode(key.trim()).map_err(|e| {
+        RequestProcessorError::Custom(format!(
+            "Environment variable '{}' is not valid base64: {}",
+            SECRET_KEY_ENV, e
+        ))
+    })?;
+
+    if key.len() != 32 {
+        return Err(RequestProcessorError::Custom(format!(
+            "Environment variable '{}' must contain exactly 32 bytes",
+            SECRET_KEY_ENV
+        )));
+    }
+
+    Ok(Aes256Gcm::new(GenericArray::from_slice(&key)))
+}
+
+/// The associated data binds a ciphertext to its RequestProcessor and name, so
+/// stored values can't be swapped between secrets.
+fn associated_data(request_processor_id: &Uuid, name: &str) -> String {
+    format!("{}/{}", request_processor_id, name)
+}
+
+/// Checks, that a secret name only consists of ASCII alphanumerics, `_` and
+/// `-`.
+fn validate_name(name: &str) -> Result<(), RequestProcessorError> {
+    if name.is_empty()
+        || name.len() > 128
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(RequestProcessorError::Validation(format!(
+            "Invalid secret name '{}': use 1 to 128 of the characters [A-Za-z0-9_-]",
+            name
+        )));
+    }
+
+    Ok(())
+}
+
+/// Encrypts and stores a secret. Overwrites an existing secret with the same
+/// name.
+pub(crate) async fn set_processor_secret(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+    name: &str,
+    value: &SecretValue,
+) -> Result<ProcessorSecretInfo, RequestProcessorError> {
+    validate_name(name)?;
+    let _ = get_request_processor(conn, request_processor_id).await?;
+
+    let nonce = rand::random::<[u8; 12]>();
+    let aad = associated_data(request_processor_id, name);
+    let ciphertext = cipher()?
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: value.expose().as_bytes(),
+                aad: aad.as_bytes(),
+            },
+        )
+        .map_err(|_| RequestProcessorError::Custom("Unable to encrypt secret".to_string()))?;
+
+    let req_id_str = request_processor_id.to_string();
+    let value_str = base64::encode(ciphertext);
+    let nonce_str = base64::encode(nonce);
+    let now = Utc::now();
+    let now_str = to_db_timestamp(&now);
+    sqlx::query!(
+        r#"INSERT INTO processor_secret
+                    (request_processor, name, value, nonce, created_at, updated_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+           ON CONFLICT(request_processor, name) DO UPDATE
+           SET value=excluded.value, nonce=excluded.nonce, updated_at=excluded.updated_at"#,
+        req_id_str,
+        name,
+        value_str,
+        nonce_str,
+        now_str,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let row = sqlx::query!(
+        r#"SELECT created_at FROM processor_secret
+           WHERE request_processor = ?1 AND name = ?2"#,
+        req_id_str,
+        name,
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(ProcessorSecretInfo {
+        name: name.to_string(),
+        created_at: DateTime::parse_from_rfc3339(&row.created_at)?.with_timezone(&Utc),
+        updated_at: now,
+    })
+}
+
+/// Deletes a secret.
+pub(crate) async fn delete_processor_secret(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+    name: &str,
+) -> Result<(), RequestProcessorError> {
+    let req_id_str = request_processor_id.to_string();
+    let res = sqlx::query!(
+        r#"DELETE FROM processor_secret
+           WHERE request_processor = ?1 AND name = ?2"#,
+        req_id_str,
+        name,
+    )
+    .execute(conn)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err(RequestProcessorError::NotFound {
+            kind: format!("ProcessorSecret '{}' of RequestProcessor", name),
+            id: *request_processor_id,
+        });
+    }
+
+    Ok(())
+}
+
+/// Lists the metadata of all secrets of a RequestProcessor, sorted by name.
+pub(crate) async fn list_processor_secrets(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+) -> Result<Vec<ProcessorSecretInfo>, RequestProcessorError> {
+    let _ = get_request_processor(conn, request_processor_id).await?;
+    let req_id_str = request_processor_id.to_string();
+    let rows = sqlx::query!(
+        r#"SELECT name, created_at, updated_at FROM processor_secret
+           WHERE request_processor = ?1
+           ORDER BY name"#,
+        req_id_str,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut infos = Vec::new();
+    for row in rows {
+        infos.push(ProcessorSecretInfo {
+            name: row.name,
+            created_at: DateTime::parse_from_rfc3339(&row.created_at)?.with_timezone(&Utc),
+            updated_at: DateTime::parse_from_rfc3339(&row.updated_at)?.with_timezone(&Utc),
+        });
+    }
+
+    Ok(infos)
+}
+
+/// Fetches and decrypts all secrets of a RequestProcessor. This is only meant
+/// to be used by the runtime, never by the admin API.
+pub(crate) async fn get_processor_secret_values(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+) -> Result<HashMap<String, SecretValue>, RequestProcessorError> {
+    let req_id_str = request_processor_id.to_string();
+    let rows = sqlx::query!(
+        r#"SELECT name, value, nonce FROM processor_secret
+           WHERE request_processor = ?1"#,
+        req_id_str,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut secrets = HashMap::new();
+    if rows.is_empty() {
+        return Ok(secrets);
+    }
+
+    let cipher = cipher()?;
+    for row in rows {
+        let nonce =
+            base64::decode(&row.nonce).map_err(|e| RequestProcessorError::Custom(e.to_string()))?;
+        let ciphertext =
+            base64::decode(&row.value).map_err(|e| RequestProcessorError::Custom(e.to_string()))?;
+        let aad = associated_data(request_processor_id, &row.name);
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                RequestProcessorError::Custom(format!(
+                    "Unable to decrypt secret '{}', was the server key changed?",
+                    row.name
+                ))
+            })?;
+
+        secrets.insert(
+            row.name,
+            SecretValue::new(
+                String::from_utf8(plaintext)
+                    .map_err(|e| RequestProcessorError::Custom(e.to_string()))?,
+            ),
+        );
+    }
+
+    Ok(secrets)
+}
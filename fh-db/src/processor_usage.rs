@@ -0,0 +1,241 @@
+//! Database structs and functions for the usage accounting of
+//! [`crate::request_processor::RequestProcessor`]s. Usage is aggregated per
+//! processor and day (UTC) and can be limited by a [`UsageQuota`].
+use super::RequestProcessorError;
+use anyhow::Result;
+use chrono::{NaiveDate, Utc};
+use fh_core::DbConnection;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Resources consumed by a RequestProcessor. Used both for the daily totals
+/// and for the increments, which are added to them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub invocations: i64,
+    /// CPU time spent executing the code in V8 in milliseconds. Time spent
+    /// awaiting operations, e.g. outbound requests, is not included.
+    pub cpu_ms: i64,
+    pub outbound_requests: i64,
+    /// Size of the bodies, which were sent and received by outbound requests.
+    pub outbound_bytes: i64,
+    /// Size of the stored audit item payloads.
+    pub audit_bytes: i64,
+}
+
+/// Usage of a RequestProcessor on a single day.
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyUsage {
+    pub day: NaiveDate,
+    #[serde(flatten)]
+    pub usage: Usage,
+}
+
+/// Usage of a RequestProcessor in a range of days, as returned by the admin
+/// endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    pub request_processor_id: Uuid,
+    /// Storage used by all audit items of the RequestProcessor, regardless of
+    /// the requested range.
+    pub storage_bytes: i64,
+    pub days: Vec<DailyUsage>,
+}
+
+/// Limits of a RequestProcessor's usage. Invocations are rejected, once one
+/// of the limits is reached. The daily limits are reset at midnight (UTC).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UsageQuota {
+    #[serde(default)]
+    pub daily_invocations: Option<i64>,
+    #[serde(default)]
+    pub daily_cpu_ms: Option<i64>,
+    #[serde(default)]
+    pub daily_outbound_requests: Option<i64>,
+    #[serde(default)]
+    pub daily_outbound_bytes: Option<i64>,
+    /// Limit of the storage used by all audit items.
+    #[serde(default)]
+    pub storage_bytes: Option<i64>,
+}
+
+fn today() -> NaiveDate {
+    Utc::now().naive_utc().date()
+}
+
+/// Adds `usage` to today's totals of the given RequestProcessor.
+pub(crate) async fn record_usage(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+    usage: &Usage,
+) -> Result<(), RequestProcessorError> {
+    let req_id_str = request_processor_id.to_string();
+    let day_str = today().to_string();
+    sqlx::query!(
+        r#"INSERT INTO processor_usage
+                    (request_processor, day, invocations, cpu_ms, outbound_requests,
+                     outbound_bytes, audit_bytes)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+           ON CONFLICT(request_processor, day) DO UPDATE
+           SET invocations = invocations + excluded.invocations,
+               cpu_ms = cpu_ms + excluded.cpu_ms,
+               outbound_requests = outbound_requests + excluded.outbound_requests,
+               outbound_bytes = outbound_bytes + excluded.outbound_bytes,
+               audit_bytes = audit_bytes + excluded.audit_bytes"#,
+        req_id_str,
+        day_str,
+        usage.invocations,
+        usage.cpu_ms,
+        usage.outbound_requests,
+        usage.outbound_bytes,
+        usage.audit_bytes,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Fetches the daily usage of a RequestProcessor from `from` until `to`
+/// (inclusive), ordered by day. Days without usage are omitted.
+pub(crate) async fn get_usage_report(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<UsageReport, RequestProcessorError> {
+    if from > to {
+        return Err(RequestProcessorError::Validation(
+            "`from` must not be after `to`".to_string(),
+        ));
+    }
+
+    let req_id_str = request_processor_id.to_string();
+    let from_str = from.to_string();
+    let to_str = to.to_string();
+    let rows = sqlx::query!(
+        r#"SELECT * FROM processor_usage
+           WHERE request_processor = ?1 AND day >= ?2 AND day <= ?3
+           ORDER BY day"#,
+        req_id_str,
+        from_str,
+        to_str,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut days = Vec::with_capacity(rows.len());
+    for row in rows {
+        days.push(DailyUsage {
+            day: row.day.parse()?,
+            usage: Usage {
+                invocations: row.invocations,
+                cpu_ms: row.cpu_ms,
+                outbound_requests: row.outbound_requests,
+                outbound_bytes: row.outbound_bytes,
+                audit_bytes: row.audit_bytes,
+            },
+        });
+    }
+
+    Ok(UsageReport {
+        request_processor_id: *request_processor_id,
+        storage_bytes: get_storage_bytes(conn, request_processor_id).await?,
+        days,
+    })
+}
+
+/// Adds `bytes` to the storage used by the audit items of a RequestProcessor.
+/// The total is kept as a running counter, as summing up the stored items
+/// would slow down the quota check of every invocation.
+pub(crate) async fn record_storage(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+    bytes: i64,
+) -> Result<(), RequestProcessorError> {
+    let req_id_str = request_processor_id.to_string();
+    sqlx::query!(
+        r#"INSERT INTO processor_storage (request_processor, storage_bytes)
+                    VALUES (?1, ?2)
+           ON CONFLICT(request_processor) DO UPDATE
+           SET storage_bytes = storage_bytes + excluded.storage_bytes"#,
+        req_id_str,
+        bytes,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Storage used by all audit items of a RequestProcessor, see
+/// [`record_storage`].
+async fn get_storage_bytes(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+) -> Result<i64, RequestProcessorError> {
+    let req_id_str = request_processor_id.to_string();
+    let row = sqlx::query!(
+        r#"SELECT storage_bytes AS "storage_bytes: i64" FROM processor_storage
+           WHERE request_processor = ?1"#,
+        req_id_str,
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.map_or(0, |row| row.storage_bytes))
+}
+
+/// Fails with [`RequestProcessorError::QuotaExceeded`], if any limit of the
+/// quota is reached.
+pub(crate) async fn check_usage_quota(
+    conn: &mut DbConnection,
+    request_processor_id: &Uuid,
+    quota: &UsageQuota,
+) -> Result<(), RequestProcessorError> {
+    let today = today();
+    let report = get_usage_report(conn, request_processor_id, today, today).await?;
+    let usage = report
+        .days
+        .into_iter()
+        .next()
+        .map(|daily| daily.usage)
+        .unwrap_or_default();
+
+    let limits = [
+        ("invocations", quota.daily_invocations, usage.invocations),
+        ("CPU milliseconds", quota.daily_cpu_ms, usage.cpu_ms),
+        (
+            "outbound requests",
+            quota.daily_outbound_requests,
+            usage.outbound_requests,
+        ),
+        (
+            "outbound bytes",
+            quota.daily_outbound_bytes,
+            usage.outbound_bytes,
+        ),
+    ];
+    for (name, limit, used) in limits.iter() {
+        if let Some(limit) = limit {
+            if used >= limit {
+                return Err(RequestProcessorError::QuotaExceeded(format!(
+                    "Daily limit of {} {} reached",
+                    limit, name
+                )));
+            }
+        }
+    }
+
+    if let Some(limit) = quota.storage_bytes {
+        if report.storage_bytes >= limit {
+            return Err(RequestProcessorError::QuotaExceeded(format!(
+                "Storage limit of {} bytes reached",
+                limit
+            )));
+        }
+    }
+
+    Ok(())
+}
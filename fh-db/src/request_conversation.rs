@@ -1,6 +1,10 @@
 //! Database structs and functions for the [`RequestConversation`] and
 //! subsequent [`AuditItem`] entities.
-use super::{request_processor::get_request_processor, RequestProcessorError};
+use super::{
+    processor_usage::{record_storage, record_usage, Usage},
+    request_processor::get_request_processor,
+    to_db_timestamp, RequestProcessorError,
+};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use fh_core::DbConnection;
@@ -8,7 +12,8 @@ use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use sqlx::Row;
-use std::str::FromStr;
+use std::{convert::AsRef, str::FromStr};
+use strum_macros::{self, AsRefStr, EnumString};
 use uuid::Uuid;
 
 /// Represents all possible Item variants, which can be handled/stored for a
@@ -45,16 +50,52 @@ pub enum AuditItem {
         request_id: Uuid,
         payload: fh_core::response::Response,
     },
-    /// Represents a simple log entry (string) to be stored in an [`AuditItem`].
+    /// Represents a log entry (string) to be stored in an [`AuditItem`]. The
+    /// optional `fields` contain arbitrary, structured JSON metadata.
     #[serde(rename = "log")]
     Log {
         id: Uuid,
         created_at: DateTime<Utc>,
         conversation_id: Uuid,
+        #[serde(default)]
+        level: LogLevel,
         payload: String,
+        #[serde(default)]
+        fields: Option<serde_json::Value>,
+    },
+    /// Represents an error, which aborted the processing, e.g. an uncaught
+    /// JavaScript exception.
+    ///
+    /// Uses [`fh_core::error::ScriptError`] to serialize the error details as
+    /// JSON.
+    #[serde(rename = "error")]
+    Error {
+        id: Uuid,
+        created_at: DateTime<Utc>,
+        conversation_id: Uuid,
+        payload: fh_core::error::ScriptError,
     },
 }
 
+/// Variants of log levels, ordered by severity.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, AsRefStr, EnumString,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Default for LogLevel {
+    fn default() -> Self {
+        Self::Info
+    }
+}
+
 impl AuditItem {
     /// Gets the Uuid of the underlying variant.
     pub fn get_id(&self) -> Uuid {
@@ -62,6 +103,27 @@ impl AuditItem {
             Self::Response { id, .. } => *id,
             Self::Request { id, .. } => *id,
             Self::Log { id, .. } => *id,
+            Self::Error { id, .. } => *id,
+        }
+    }
+
+    /// Gets the kind of the underlying variant, as it is serialized.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Response { .. } => "response",
+            Self::Request { .. } => "request",
+            Self::Log { .. } => "log",
+            Self::Error { .. } => "error",
+        }
+    }
+
+    /// Gets the creation time of the underlying variant.
+    pub fn get_created_at(&self) -> DateTime<Utc> {
+        match self {
+            Self::Response { created_at, .. } => *created_at,
+            Self::Request { created_at, .. } => *created_at,
+            Self::Log { created_at, .. } => *created_at,
+            Self::Error { created_at, .. } => *created_at,
         }
     }
 
@@ -96,8 +158,34 @@ impl AuditItem {
     }
 
     /// Helper method to create a new [`AuditItem::Log`] variant.
-    pub fn new_log(conversation_id: Uuid, payload: String) -> Self {
+    pub fn new_log(
+        conversation_id: Uuid,
+        level: LogLevel,
+        payload: String,
+        fields: Option<serde_json::Value>,
+    ) -> Self {
         Self::Log {
+            id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            conversation_id,
+            level,
+            payload,
+            fields,
+        }
+    }
+
+    /// Checks, if the item passes the given minimum log level. Only
+    /// [`AuditItem::Log`] items are affected by the level.
+    pub fn has_min_level(&self, min_level: LogLevel) -> bool {
+        match self {
+            Self::Log { level, .. } => *level >= min_level,
+            _ => true,
+        }
+    }
+
+    /// Helper method to create a new [`AuditItem::Error`] variant.
+    pub fn new_error(conversation_id: Uuid, payload: fh_core::error::ScriptError) -> Self {
+        Self::Error {
             id: Uuid::new_v4(),
             created_at: Utc::now(),
             conversation_id,
@@ -105,6 +193,20 @@ impl AuditItem {
         }
     }
 
+    /// Replaces all occurrences of the given secret values in the item's
+    /// payload with `[REDACTED]`. Empty values are ignored.
+    pub fn redact(self, secrets: &[&str]) -> Result<Self, RequestProcessorError> {
+        let secrets: Vec<&str> = secrets.iter().filter(|s| !s.is_empty()).cloned().collect();
+        if secrets.is_empty() {
+            return Ok(self);
+        }
+
+        let mut value = serde_json::to_value(&self)?;
+        redact_value(&mut value, &secrets);
+
+        Ok(serde_json::from_value(value)?)
+    }
+
     /// Convert a [`DbAuditItem`] back to a [`AuditItem`].
     fn from_db_audit_item(item: &DbAuditItem) -> Result<Self, RequestProcessorError> {
         Ok(match item.kind.as_str() {
@@ -130,7 +232,21 @@ impl AuditItem {
                 id: item.id,
                 created_at: item.created_at,
                 conversation_id: item.conversation_id,
+                level: match &item.level {
+                    Some(level) => LogLevel::from_str(level)?,
+                    None => LogLevel::default(),
+                },
                 payload: item.payload.clone(),
+                fields: match &item.fields {
+                    Some(fields) => Some(serde_json::from_str(fields)?),
+                    None => None,
+                },
+            },
+            "error" => Self::Error {
+                id: item.id,
+                created_at: item.created_at,
+                conversation_id: item.conversation_id,
+                payload: serde_json::from_str(&item.payload)?,
             },
             s => {
                 return Err(RequestProcessorError::Custom(format!(
@@ -158,6 +274,8 @@ impl AuditItem {
                 inc: Some(*inc),
                 payload: serde_json::to_string(payload)?,
                 request_id: None,
+                level: None,
+                fields: None,
             },
             AuditItem::Response {
                 id,
@@ -173,12 +291,16 @@ impl AuditItem {
                 inc: None,
                 payload: serde_json::to_string(payload)?,
                 request_id: Some(*request_id),
+                level: None,
+                fields: None,
             },
             AuditItem::Log {
                 id,
                 created_at,
                 conversation_id,
+                level,
                 payload,
+                fields,
             } => DbAuditItem {
                 kind: "log".to_string(),
                 id: *id,
@@ -187,11 +309,52 @@ impl AuditItem {
                 inc: None,
                 payload: payload.clone(),
                 request_id: None,
+                level: Some(level.as_ref().to_string()),
+                fields: match fields {
+                    Some(fields) => Some(serde_json::to_string(fields)?),
+                    None => None,
+                },
+            },
+            AuditItem::Error {
+                id,
+                created_at,
+                conversation_id,
+                payload,
+            } => DbAuditItem {
+                kind: "error".to_string(),
+                id: *id,
+                conversation_id: *conversation_id,
+                created_at: *created_at,
+                inc: None,
+                payload: serde_json::to_string(payload)?,
+                request_id: None,
+                level: None,
+                fields: None,
             },
         })
     }
 }
 
+/// Recursively replaces the given secrets in all strings of a JSON value.
+pub(crate) fn redact_value(value: &mut serde_json::Value, secrets: &[&str]) {
+    match value {
+        serde_json::Value::String(s) => {
+            for secret in secrets {
+                if s.contains(secret) {
+                    *s = s.replace(secret, "[REDACTED]");
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            items.iter_mut().for_each(|v| redact_value(v, secrets));
+        }
+        serde_json::Value::Object(map) => {
+            map.values_mut().for_each(|v| redact_value(v, secrets));
+        }
+        _ => {}
+    }
+}
+
 /// Represents an AuditItem which can be stored to the database. This struct is
 /// needed because the Payload varies for Request/Response/Log and possible
 /// further ones. Conversion from/to [`AuditItem`] happens via
@@ -205,6 +368,8 @@ struct DbAuditItem {
     conversation_id: Uuid,
     request_id: Option<Uuid>,
     payload: String,
+    level: Option<String>,
+    fields: Option<String>,
 }
 
 /// A RequestConversation is created on each request to a
@@ -216,29 +381,59 @@ pub struct RequestConversation {
     pub id: Uuid,
     created_at: chrono::DateTime<Utc>,
     request_processor_id: Uuid,
+    pub status: ConversationStatus,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Total processing time in milliseconds.
+    pub duration_ms: Option<i64>,
+    /// Status code of the final response.
+    pub status_code: Option<u16>,
+    /// Error message, if the processing failed or timed out.
+    pub error: Option<String>,
+    /// Conversation of the invoking RequestProcessor for nested invocations
+    /// (`fh.invoke`) or of the previous step for pipeline steps.
+    #[serde(default)]
+    pub parent_id: Option<Uuid>,
+    /// Conversations of the invocations, which were issued by this one.
+    #[serde(default)]
+    pub children: Vec<Uuid>,
     audit_items: Vec<AuditItem>,
 }
 
+/// Variants of the states, a RequestConversation passes through.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, AsRefStr, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ConversationStatus {
+    Running,
+    Succeeded,
+    Failed,
+    TimedOut,
+}
+
 /// Physically writes a [`RequestConversation`] struct to the underlying
-/// database.
+/// database. Nested invocations and pipeline steps pass the conversation,
+/// which issued them, as `parent_id`.
 pub(crate) async fn create_request_conversation(
     conn: &mut DbConnection,
     request_processor_id: &Uuid,
+    parent_id: Option<Uuid>,
 ) -> Result<RequestConversation, RequestProcessorError> {
     let conversation_id = Uuid::new_v4();
 
     let _p = get_request_processor(conn, request_processor_id).await?;
     let id_str = conversation_id.to_string();
     let req_id_str = request_processor_id.to_string();
+    let parent_str = parent_id.map(|id| id.to_string());
     let now = Utc::now();
     let now_str = now.to_rfc3339();
     sqlx::query!(
         r#"INSERT INTO request_conversation
-                    (id, created_at, request_processor)
-                    VALUES (?1, ?2, ?3)"#,
+                    (id, created_at, request_processor, parent)
+                    VALUES (?1, ?2, ?3, ?4)"#,
         id_str,
         now_str,
         req_id_str,
+        parent_str,
     )
     .execute(conn)
     .await?;
@@ -247,6 +442,13 @@ pub(crate) async fn create_request_conversation(
         id: conversation_id,
         created_at: now,
         request_processor_id: *request_processor_id,
+        status: ConversationStatus::Running,
+        finished_at: None,
+        duration_ms: None,
+        status_code: None,
+        error: None,
+        parent_id,
+        children: Vec::new(),
         audit_items: Vec::new(),
     })
 }
@@ -256,6 +458,7 @@ pub(crate) async fn create_request_conversation(
 pub(crate) async fn get_request_conversation(
     conn: &mut DbConnection,
     id: &Uuid,
+    min_level: Option<LogLevel>,
 ) -> Result<RequestConversation, RequestProcessorError> {
     let id_str = id.to_string();
     let row = sqlx::query!(
@@ -279,24 +482,107 @@ pub(crate) async fn get_request_conversation(
             id: *id,
             created_at: DateTime::parse_from_rfc3339(&row.created_at)?.with_timezone(&Utc),
             request_processor_id: Uuid::from_str(&row.request_processor)?,
-            audit_items: get_audit_items(conn, id).await?,
+            status: ConversationStatus::from_str(&row.status)?,
+            finished_at: match row.finished_at {
+                Some(x) => Some(DateTime::parse_from_rfc3339(&x)?.with_timezone(&Utc)),
+                None => None,
+            },
+            duration_ms: row.duration_ms,
+            status_code: row.status_code.map(|c| c as u16),
+            error: row.error,
+            parent_id: row.parent.as_deref().map(Uuid::from_str).transpose()?,
+            children: get_child_conversation_ids(conn, id).await?,
+            audit_items: get_audit_items(conn, id, min_level).await?,
         }),
     }
 }
 
+/// Fetches the Uuids of all conversations, whose parent is the given one,
+/// ordered by creation.
+async fn get_child_conversation_ids(
+    conn: &mut DbConnection,
+    id: &Uuid,
+) -> Result<Vec<Uuid>, RequestProcessorError> {
+    let id_str = id.to_string();
+    let rows = sqlx::query!(
+        r#"SELECT id FROM request_conversation
+           WHERE parent = ?1
+           ORDER BY created_at"#,
+        id_str
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut ids = Vec::with_capacity(rows.len());
+    for row in rows {
+        ids.push(Uuid::from_str(&row.id)?);
+    }
+
+    Ok(ids)
+}
+
+/// Marks a RequestConversation as finished. The finish time is set to now,
+/// the duration is computed from the conversation's creation time.
+pub(crate) async fn finish_request_conversation(
+    conn: &mut DbConnection,
+    id: &Uuid,
+    status: ConversationStatus,
+    status_code: Option<u16>,
+    error: Option<String>,
+) -> Result<(), RequestProcessorError> {
+    let id_str = id.to_string();
+    let row = sqlx::query!(
+        r#"SELECT created_at FROM request_conversation WHERE id = ?1"#,
+        id_str
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(RequestProcessorError::NotFound {
+        id: *id,
+        kind: "RequestConversation".to_string(),
+    })?;
+
+    let created_at = DateTime::parse_from_rfc3339(&row.created_at)?.with_timezone(&Utc);
+    let finished_at = Utc::now();
+    let duration_ms = (finished_at - created_at).num_milliseconds();
+    let status = status.as_ref();
+    let finished_at_str = finished_at.to_rfc3339();
+    let status_code = status_code.map(|c| c as i32);
+    sqlx::query!(
+        r#"UPDATE request_conversation
+           SET status=?1, finished_at=?2, duration_ms=?3, status_code=?4, error=?5
+           WHERE id=?6"#,
+        status,
+        finished_at_str,
+        duration_ms,
+        status_code,
+        error,
+        id_str,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
 /// Fetches all AuditItem instances for a single Conversation Uuid.
-/// The output is chronologically sorted.
+/// The output is chronologically sorted. If `min_level` is given, log items
+/// below this level are omitted.
 pub(crate) async fn get_audit_items(
     conn: &mut DbConnection,
     conversation_id: &Uuid,
+    min_level: Option<LogLevel>,
 ) -> Result<Vec<AuditItem>, RequestProcessorError> {
     let conv_id_str = conversation_id.to_string();
     let mut items = Vec::new();
 
-    let mut rows =
-        sqlx::query("SELECT * FROM conversation_audit_item WHERE request_conversation = ?1")
-            .bind(conv_id_str)
-            .fetch(conn);
+    let mut rows = sqlx::query(
+        r#"SELECT * FROM conversation_audit_item
+           WHERE request_conversation = ?1
+           ORDER BY created_at, rowid"#,
+    )
+    .bind(conv_id_str)
+    .fetch(conn);
 
     while let Some(row) = rows.try_next().await? {
         let req_id: Option<String> = row.try_get("parent")?;
@@ -309,26 +595,17 @@ pub(crate) async fn get_audit_items(
             inc: row.try_get("inc")?,
             payload: row.try_get("payload")?,
             request_id: req_id.and_then(|x| Uuid::from_str(&x).ok()),
+            level: row.try_get("level")?,
+            fields: row.try_get("fields")?,
         };
 
-        items.push(AuditItem::from_db_audit_item(&i)?);
+        let item = AuditItem::from_db_audit_item(&i)?;
+        if item.has_min_level(min_level.unwrap_or(LogLevel::Debug)) {
+            items.push(item);
+        }
     }
 
-    items.sort_by(|a, b| {
-        let a_created_at = match a {
-            AuditItem::Log { created_at, .. } => created_at,
-            AuditItem::Response { created_at, .. } => created_at,
-            AuditItem::Request { created_at, .. } => created_at,
-        };
-
-        let b_created_at = match b {
-            AuditItem::Log { created_at, .. } => created_at,
-            AuditItem::Response { created_at, .. } => created_at,
-            AuditItem::Request { created_at, .. } => created_at,
-        };
-
-        a_created_at.cmp(&b_created_at)
-    });
+    items.sort_by_key(|item| item.get_created_at());
 
     Ok(items)
 }
@@ -339,17 +616,21 @@ pub(crate) async fn create_audit_item(
     item: AuditItem,
 ) -> Result<AuditItem, RequestProcessorError> {
     let db_item = item.to_audit_db_item()?;
-    let conv = get_request_conversation(conn, &db_item.conversation_id).await?;
+    let conv = get_request_conversation(conn, &db_item.conversation_id, None).await?;
     let item_id_str = db_item.id.to_string();
     let conv_id_str = conv.id.to_string();
-    let created_at = Utc::now().to_rfc3339();
+    // items may be stored later than created, e.g. the buffered `console`
+    // entries, so they keep their own timestamp to retain the order
+    let created_at = to_db_timestamp(&db_item.created_at);
     let payload = db_item.payload;
     let request_id_str = db_item.request_id.and_then(|x| Some(x.to_string()));
+    let audit_bytes = payload.len() + db_item.fields.as_ref().map_or(0, String::len);
 
     sqlx::query!(
         r#"INSERT INTO conversation_audit_item
-                    (id, kind, created_at, inc, request_conversation, parent, payload)
-                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"#,
+                    (id, kind, created_at, inc, request_conversation, parent, payload, level,
+                     fields)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"#,
         item_id_str,
         db_item.kind,
         created_at,
@@ -357,9 +638,28 @@ pub(crate) async fn create_audit_item(
         conv_id_str,
         request_id_str,
         payload,
+        db_item.level,
+        db_item.fields,
     )
-    .execute(conn)
+    .execute(&mut *conn)
     .await?;
 
+    let usage = Usage {
+        audit_bytes: audit_bytes as i64,
+        ..Usage::default()
+    };
+    // the item is stored, so a failed accounting must not fail the request
+    let recorded = match record_usage(conn, &conv.request_processor_id, &usage).await {
+        Ok(()) => record_storage(conn, &conv.request_processor_id, usage.audit_bytes).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = recorded {
+        tracing::error!(
+            "Unable to record usage of RequestProcessor {}: {:?}",
+            conv.request_processor_id,
+            e
+        );
+    }
+
     Ok(item)
 }
@@ -1,5 +1,5 @@
 //! Database structs and functions for the RequestProcessor entity.
-use super::RequestProcessorError;
+use super::{processor_usage::UsageQuota, request_conversation::LogLevel, RequestProcessorError};
 use anyhow::Result;
 use fh_core::DbConnection;
 use serde::{self, Deserialize, Serialize};
@@ -19,6 +19,183 @@ pub struct RequestProcessor {
     pub language: RequestProcessorLanguage,
     pub runtime: RequestProcessorRuntime,
     pub code: String,
+    /// User-defined configuration, which is exposed as `fh.config` to the
+    /// code. Allows reusing the same code for different targets.
+    #[serde(default)]
+    pub config: RequestProcessorConfig,
+    /// Log entries below this level are not stored.
+    #[serde(default = "default_min_log_level")]
+    pub min_log_level: LogLevel,
+    /// If set, the signatures of incoming requests are verified, before the
+    /// code is run.
+    #[serde(default)]
+    pub webhook_verification: Option<WebhookVerification>,
+    /// If set, invocations are rejected, once the usage reaches a limit.
+    #[serde(default)]
+    pub quota: Option<UsageQuota>,
+    /// If set, cross-origin requests from browsers are allowed according to
+    /// this policy.
+    #[serde(default)]
+    pub cors: Option<CorsPolicy>,
+}
+
+impl RequestProcessor {
+    /// Fails with [`RequestProcessorError::Validation`], if the
+    /// RequestProcessor must not be run by other RequestProcessors via
+    /// `fh.invoke` or by Pipelines. Webhook signatures are only verified for
+    /// requests from clients, so chaining would bypass them. CORS policies
+    /// only restrict browsers and don't prevent chaining.
+    pub fn check_chainable(&self) -> Result<(), RequestProcessorError> {
+        let reason = match &self.webhook_verification {
+            Some(_) => "verifies webhook signatures",
+            None => return Ok(()),
+        };
+
+        Err(RequestProcessorError::Validation(format!(
+            "RequestProcessor {} {} and can only be run by clients",
+            self.id, reason
+        )))
+    }
+}
+
+fn default_min_log_level() -> LogLevel {
+    LogLevel::Debug
+}
+
+/// Type alias for the JSON configuration object of a [`RequestProcessor`].
+pub type RequestProcessorConfig = serde_json::Map<String, serde_json::Value>;
+
+/// Declarative verification of webhook signatures. The signing secret is one
+/// of the RequestProcessor's secrets, referenced by its name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookVerification {
+    pub provider: WebhookProvider,
+    /// Name of the secret, which contains the signing secret.
+    pub secret: String,
+    /// Only for provider `generic`: name of the header, which contains the
+    /// signature. Defaults to `X-Signature`.
+    #[serde(default)]
+    pub header: Option<String>,
+}
+
+/// Providers, whose webhook signatures can be verified.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookProvider {
+    /// `X-Hub-Signature-256: sha256=<hex>`, HMAC-SHA256 of the body.
+    Github,
+    /// `Stripe-Signature: t=<timestamp>,v1=<hex>`, HMAC-SHA256 of
+    /// `<timestamp>.<body>`.
+    Stripe,
+    /// `X-Slack-Signature: v0=<hex>` and `X-Slack-Request-Timestamp`,
+    /// HMAC-SHA256 of `v0:<timestamp>:<body>`.
+    Slack,
+    /// Hex encoded HMAC-SHA256 of the body in a configurable header, optionally
+    /// prefixed with `sha256=`.
+    Generic,
+}
+
+/// Cross-Origin Resource Sharing policy of a RequestProcessor. Preflight
+/// requests are answered by the server without running the code.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorsPolicy {
+    /// Origins like `https://app.example.com`, `*` allows any origin.
+    pub allowed_origins: Vec<String>,
+    /// An empty list allows all methods.
+    #[serde(default)]
+    pub allowed_methods: Vec<String>,
+    /// Request headers besides the CORS-safelisted ones, `*` allows any
+    /// header.
+    #[serde(default)]
+    pub allowed_headers: Vec<String>,
+    /// Response headers, which are readable by the client, besides
+    /// `FH-Conversation-Id`.
+    #[serde(default)]
+    pub expose_headers: Vec<String>,
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight response.
+    #[serde(default)]
+    pub max_age_secs: Option<u32>,
+}
+
+impl CorsPolicy {
+    /// Validates the policy and brings the origins and methods into their
+    /// canonical form.
+    fn normalize(&mut self) -> Result<(), RequestProcessorError> {
+        if self.allowed_origins.is_empty() {
+            return Err(RequestProcessorError::Validation(
+                "CORS policy must allow at least one origin".to_string(),
+            ));
+        }
+        for origin in self.allowed_origins.iter_mut() {
+            *origin = origin.trim().trim_end_matches('/').to_ascii_lowercase();
+            if origin.as_str() != "*"
+                && !origin.starts_with("http://")
+                && !origin.starts_with("https://")
+            {
+                return Err(RequestProcessorError::Validation(format!(
+                    "Invalid origin '{}', expected '*' or e.g. 'https://example.com'",
+                    origin
+                )));
+            }
+        }
+        if self.allow_credentials && self.allows_any_origin() {
+            return Err(RequestProcessorError::Validation(
+                "CORS policy must not allow credentials for any origin".to_string(),
+            ));
+        }
+
+        for method in self.allowed_methods.iter_mut() {
+            *method = method.trim().to_ascii_uppercase();
+            if method.is_empty() || !method.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(RequestProcessorError::Validation(format!(
+                    "Invalid HTTP method '{}'",
+                    method
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|o| o == "*")
+    }
+
+    /// Checks the `Origin` header of a request, ignoring the case.
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.allows_any_origin()
+            || self
+                .allowed_origins
+                .iter()
+                .any(|o| o.eq_ignore_ascii_case(origin.trim_end_matches('/')))
+    }
+
+    pub fn allows_method(&self, method: &str) -> bool {
+        self.allowed_methods.is_empty()
+            || self
+                .allowed_methods
+                .iter()
+                .any(|m| m.eq_ignore_ascii_case(method))
+    }
+
+    pub fn allows_header(&self, header: &str) -> bool {
+        self.allowed_headers
+            .iter()
+            .any(|h| h == "*" || h.eq_ignore_ascii_case(header))
+    }
+}
+
+/// Validates the parts of a RequestProcessor, which are not checked by
+/// deserializing it.
+fn normalize(data: &mut RequestProcessor) -> Result<(), RequestProcessorError> {
+    if let Some(cors) = &mut data.cors {
+        cors.normalize()?;
+    }
+
+    Ok(())
 }
 
 /// Variantes of supported language snippets.
@@ -37,23 +214,42 @@ pub enum RequestProcessorRuntime {
     //WASM,
 }
 
+/// Serializes an optional value for a nullable JSON column.
+fn to_json_column<T: Serialize>(
+    value: &Option<T>,
+) -> Result<Option<String>, RequestProcessorError> {
+    Ok(value.as_ref().map(serde_json::to_string).transpose()?)
+}
+
 /// Stores a new RequestProcessor to the underlying database.
 pub(crate) async fn create_request_processor(
     conn: &mut DbConnection,
-    data: &RequestProcessor,
+    data: &mut RequestProcessor,
 ) -> Result<(), RequestProcessorError> {
+    normalize(data)?;
     let id_str = data.id.to_string();
     let language = data.language.as_ref();
     let runtime = data.runtime.as_ref();
+    let config = serde_json::to_string(&data.config)?;
+    let min_log_level = data.min_log_level.as_ref();
+    let webhook_verification = to_json_column(&data.webhook_verification)?;
+    let quota = to_json_column(&data.quota)?;
+    let cors = to_json_column(&data.cors)?;
     sqlx::query!(
         r#"INSERT INTO request_processor
-                    (id, name, language, runtime, code)
-                    VALUES (?1, ?2, ?3, ?4, ?5)"#,
+                    (id, name, language, runtime, code, config, min_log_level, webhook_verification,
+                     quota, cors)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"#,
         id_str,
         data.name,
         language,
         runtime,
-        data.code
+        data.code,
+        config,
+        min_log_level,
+        webhook_verification,
+        quota,
+        cors
     )
     .execute(conn)
     .await?;
@@ -87,6 +283,14 @@ pub(crate) async fn get_request_processor(
             language: RequestProcessorLanguage::from_str(&row.language)?,
             runtime: RequestProcessorRuntime::from_str(&row.runtime)?,
             code: row.code,
+            config: serde_json::from_str(&row.config)?,
+            min_log_level: LogLevel::from_str(&row.min_log_level)?,
+            webhook_verification: row
+                .webhook_verification
+                .map(|v| serde_json::from_str(&v))
+                .transpose()?,
+            quota: row.quota.map(|v| serde_json::from_str(&v)).transpose()?,
+            cors: row.cors.map(|v| serde_json::from_str(&v)).transpose()?,
         }),
     }
 }
@@ -98,17 +302,29 @@ pub(crate) async fn update_request_processor(
     data: &mut RequestProcessor,
 ) -> Result<(), RequestProcessorError> {
     let _ = get_request_processor(conn, id).await?;
+    normalize(data)?;
     let id_str = id.to_string();
     let language = data.language.as_ref();
     let runtime = data.runtime.as_ref();
+    let config = serde_json::to_string(&data.config)?;
+    let min_log_level = data.min_log_level.as_ref();
+    let webhook_verification = to_json_column(&data.webhook_verification)?;
+    let quota = to_json_column(&data.quota)?;
+    let cors = to_json_column(&data.cors)?;
     sqlx::query!(
         r#"UPDATE request_processor
-           SET name=?1, language=?2, runtime=?3, code=?4
-           WHERE id=?5"#,
+           SET name=?1, language=?2, runtime=?3, code=?4, config=?5, min_log_level=?6,
+               webhook_verification=?7, quota=?8, cors=?9
+           WHERE id=?10"#,
         data.name,
         language,
         runtime,
         data.code,
+        config,
+        min_log_level,
+        webhook_verification,
+        quota,
+        cors,
         id_str,
     )
     .execute(conn)
@@ -119,6 +335,28 @@ pub(crate) async fn update_request_processor(
     Ok(())
 }
 
+/// Replaces the configuration object of a RequestProcessor.
+pub(crate) async fn update_request_processor_config(
+    conn: &mut DbConnection,
+    id: &Uuid,
+    config: &RequestProcessorConfig,
+) -> Result<(), RequestProcessorError> {
+    let _ = get_request_processor(conn, id).await?;
+    let id_str = id.to_string();
+    let config = serde_json::to_string(config)?;
+    sqlx::query!(
+        r#"UPDATE request_processor
+           SET config=?1
+           WHERE id=?2"#,
+        config,
+        id_str,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
 /// Deletes a RequestProcessor with the given Uuid.
 pub(crate) async fn delete_request_processor(
     conn: &mut DbConnection,
@@ -1,7 +1,7 @@
 import os
 
 from authlib.integrations.starlette_client import OAuth
-from fastapi import FastAPI, Request
+from fastapi import FastAPI, HTTPException, Request
 from fastapi.param_functions import Depends
 from fastapi_cloudauth.auth0 import Auth0, Auth0Claims, Auth0CurrentUser
 from fh.gateway.config import Config
@@ -27,6 +27,20 @@ oauth.register(
 auth0 = Auth0(domain=config.auth0.domain)
 get_current_user = Auth0CurrentUser(domain=config.auth0.domain)
 
+# The built-in endpoints of the core, see `RESERVED_PREFIXES` in
+# `fh-db/src/processor_route.rs`. They are only exposed by their own endpoints
+# below, the operational ones like `/metrics` not at all.
+RESERVED_PREFIXES = {
+    "admin",
+    "conversation",
+    "healthz",
+    "hello",
+    "metrics",
+    "pipeline",
+    "processor",
+    "readyz",
+}
+
 
 # HINT: using @app.route() fails, because it does somehow not resolve the
 # dependencies (in this case, the Auth0Claims)
@@ -79,6 +93,18 @@ async def processor(request: Request):
     return r
 
 
+@app.get("/pipeline/{tail:path}")
+@app.post("/pipeline/{tail:path}")
+@app.put("/pipeline/{tail:path}")
+@app.delete("/pipeline/{tail:path}")
+async def pipeline(request: Request):
+    """
+    Proxies all requests to the `/pipeline` endpoint upstream.
+    """
+    r = await proxy_fh_request(config.core.upstream, request)
+    return r
+
+
 @app.get("/auth/auth0")
 async def auth_auth0(request: Request):
     """
@@ -98,3 +124,25 @@ async def login(request: Request):
     """
     redirect_uri = request.url_for("auth_auth0")
     return await oauth.auth0.authorize_redirect(request, redirect_uri)
+
+
+# HINT: this has to be the last endpoint, as it matches every path
+@app.get("/{tail:path}")
+@app.post("/{tail:path}")
+@app.put("/{tail:path}")
+@app.patch("/{tail:path}")
+@app.delete("/{tail:path}")
+@app.head("/{tail:path}")
+@app.options("/{tail:path}")
+async def route(request: Request, tail: str):
+    """
+    Proxies all other requests upstream, where they are matched against the
+    custom routes of the processors. Requests to the built-in endpoints, which
+    were not matched by the endpoints above, e.g. because of their method, are
+    rejected.
+    """
+    if tail.split("/", 1)[0] in RESERVED_PREFIXES:
+        raise HTTPException(status_code=404)
+
+    r = await proxy_fh_request(config.core.upstream, request)
+    return r
@@ -35,6 +35,9 @@ async def proxy_fh_request(
         )
 
     url = patch_url(base_url, request)
+    # custom routes of processors may be bound to the host
+    if "host" in headers:
+        headers["x-forwarded-host"] = headers["host"]
     del headers["host"]
     headers["connection"] = "keep-alive"
     data = await request.body()
@@ -0,0 +1,99 @@
+//! Worker, which processes the queue of asynchronous RequestProcessor
+//! invocations, see [`fh_db::processor_job::ProcessorJob`].
+use crate::server::AppContext;
+use anyhow::Result;
+use fh_core::{send_cmd, shutdown::Shutdown, telemetry::extract_context};
+use fh_db::{
+    processor_job::{ProcessorJob, ProcessorJobStatus},
+    ReqCmd,
+};
+use fh_v8::ProcessorCmd;
+use std::time::Duration;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Time to wait before polling the queue again, if it was empty.
+const IDLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Async function to be run by an executor like tokio, which loops until
+/// shutdown and runs queued ProcessorJobs one after another. Jobs, which are
+/// still queued on shutdown, are run after the next start.
+pub(crate) async fn job_worker(ctx: AppContext, mut shutdown: Shutdown) {
+    // jobs, which were running during a crash or restart, are run again
+    match send_cmd(&ctx.tx_db, |cmd_tx| ReqCmd::RequeueRunningProcessorJobs {
+        cmd_tx,
+    })
+    .await
+    {
+        Ok(Ok(n)) if n > 0 => log::info!("Requeued {} interrupted ProcessorJobs", n),
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => log::error!("Unable to requeue ProcessorJobs: {:?}", e),
+        Err(e) => log::error!("Unable to requeue ProcessorJobs: {:?}", e),
+    }
+
+    while !shutdown.is_requested() {
+        match run_next_job(&ctx).await {
+            Ok(true) => {}
+            Ok(false) => {
+                shutdown.sleep(IDLE_INTERVAL).await;
+            }
+            Err(e) => {
+                log::error!("Unable to process ProcessorJob queue: {:?}", e);
+                shutdown.sleep(IDLE_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// Claims and runs the next queued job. Returns `false`, if the queue was
+/// empty.
+async fn run_next_job(ctx: &AppContext) -> Result<bool> {
+    let job = match send_cmd(&ctx.tx_db, |cmd_tx| ReqCmd::ClaimNextProcessorJob {
+        cmd_tx,
+    })
+    .await??
+    {
+        None => return Ok(false),
+        Some(job) => job,
+    };
+
+    let (status, error) = match run_job(ctx, &job).await {
+        Ok(()) => (ProcessorJobStatus::Succeeded, None),
+        Err(e) => (ProcessorJobStatus::Failed, Some(e.to_string())),
+    };
+
+    send_cmd(&ctx.tx_db, |cmd_tx| ReqCmd::FinishProcessorJob {
+        id: job.id,
+        status,
+        error,
+        cmd_tx,
+    })
+    .await??;
+
+    Ok(true)
+}
+
+/// Runs a single job within its previously created conversation. The job is
+/// traced as child of the trace of the client, which queued it.
+async fn run_job(ctx: &AppContext, job: &ProcessorJob) -> Result<()> {
+    let span = tracing::info_span!(
+        "processor_job",
+        job_id = %job.id,
+        processor_id = %job.request_processor_id,
+    );
+    span.set_parent(&extract_context(&job.request.headers));
+
+    let tx_db = ctx.tx_db.clone();
+    send_cmd(&ctx.tx_proc, |cmd_tx| ProcessorCmd::RunRequestProcessor {
+        id: job.request_processor_id,
+        request: job.request.clone(),
+        cmd_tx,
+        tx_db,
+        prelude: job.prelude,
+        conversation_id: Some(job.conversation_id),
+    })
+    .instrument(span)
+    .await??;
+
+    Ok(())
+}
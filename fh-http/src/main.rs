@@ -1,41 +1,117 @@
+mod job_worker;
+mod scheduler;
 mod server;
+mod telemetry;
 
+use crate::job_worker::job_worker;
+use crate::scheduler::scheduler;
 use crate::server::web_server;
 use anyhow::Result;
 use dotenv::dotenv;
-use fh_db::request_manager;
-use fh_v8::request_processing_manager;
+use fh_core::shutdown;
+use fh_db::{processor_secret, request_manager};
+use fh_v8::{delivery_worker, request_processing_manager};
 use server::{AppContext, Config};
 use std::{
-    env,
     sync::{Arc, Mutex},
+    time::Duration,
 };
-use tokio::sync::mpsc;
+use tokio::{signal, sync::mpsc};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv()?;
-    pretty_env_logger::init();
 
-    let config = Config {
-        port: env::var("CORE_PORT")
-            .unwrap_or("3030".into())
-            .parse::<u16>()?,
+    let config = Config::load()?;
+    processor_secret::check_secret_key()?;
+    // exports pending spans, when main returns
+    let _telemetry = telemetry::init(&config)?;
+
+    let (tx_db, mut rx_db) = mpsc::channel(config.db_channel_capacity);
+    let (tx_v8, mut rx_v8) = mpsc::channel(config.processor_channel_capacity);
+    let ctx = AppContext::new(
+        Arc::new(Mutex::new(tx_db)),
+        Arc::new(Mutex::new(tx_v8)),
+        config,
+    );
+
+    let shutdown_timeout = Duration::from_secs(ctx.config.shutdown_timeout_secs);
+
+    // shutdown happens in stages: first everything, which issues invocations,
+    // is stopped. Then running invocations finish and finally all pending
+    // database commands are written.
+    let (shutdown_trigger, shutdown) = shutdown::channel();
+    let (proc_shutdown_trigger, proc_shutdown) = shutdown::channel();
+    let (db_shutdown_trigger, db_shutdown) = shutdown::channel();
+
+    let tx_db = ctx.tx_db.clone();
+    let processing_ctx = ctx.processing.clone();
+    let workers = async {
+        tokio::join!(
+            web_server(ctx.clone(), shutdown.clone()),
+            scheduler(ctx.clone(), shutdown.clone()),
+            job_worker(ctx, shutdown.clone()),
+            delivery_worker(tx_db, processing_ctx.clone(), shutdown.clone())
+        );
+        proc_shutdown_trigger.trigger();
+    };
+    let processing = async {
+        let res =
+            request_processing_manager(&mut rx_v8, processing_ctx.clone(), proc_shutdown).await;
+        db_shutdown_trigger.trigger();
+        res
+    };
+    let app = async {
+        let (_workers, req_proc_manager, req_manager) = tokio::join!(
+            workers,
+            processing,
+            request_manager(&mut rx_db, db_shutdown)
+        );
+
+        req_manager?;
+        req_proc_manager?;
+
+        Ok::<_, anyhow::Error>(())
     };
+    tokio::pin!(app);
 
-    // TODO is 4096 enough? make this configurable!
-    let (tx_db, mut rx_db) = mpsc::channel(4096);
-    let (tx_v8, mut rx_v8) = mpsc::channel(4096);
-    let ctx = AppContext::new(Arc::new(Mutex::new(tx_db)), Arc::new(Mutex::new(tx_v8)));
+    tokio::select! {
+        res = &mut app => return res,
+        res = shutdown_signal() => res?,
+    }
 
-    let (_web_server, req_manager, req_proc_manager) = tokio::join!(
-        web_server(ctx, &config),
-        request_manager(&mut rx_db),
-        request_processing_manager(&mut rx_v8)
+    log::info!(
+        "Shutting down, waiting up to {}s for in-flight requests",
+        shutdown_timeout.as_secs()
     );
+    shutdown_trigger.trigger();
+
+    match tokio::time::timeout(shutdown_timeout, app).await {
+        Ok(res) => res,
+        Err(_) => {
+            log::warn!("Shutdown timeout exceeded, exiting without waiting any longer");
+            Ok(())
+        }
+    }
+}
+
+/// Resolves, once the process receives `SIGINT` (Ctrl+C) or `SIGTERM`.
+#[cfg(unix)]
+async fn shutdown_signal() -> Result<()> {
+    let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+
+    tokio::select! {
+        res = signal::ctrl_c() => res?,
+        _ = terminate.recv() => {}
+    }
+
+    Ok(())
+}
 
-    req_manager?;
-    req_proc_manager?;
+/// Resolves, once the process receives Ctrl+C.
+#[cfg(not(unix))]
+async fn shutdown_signal() -> Result<()> {
+    signal::ctrl_c().await?;
 
     Ok(())
 }
@@ -0,0 +1,125 @@
+//! Scheduler, which runs RequestProcessors according to the cron expressions
+//! of their [`fh_db::processor_schedule::ProcessorSchedule`]s.
+use crate::server::AppContext;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use fh_core::{request::Request, send_cmd, shutdown::Shutdown};
+use fh_db::{processor_schedule::ProcessorSchedule, ReqCmd};
+use fh_v8::ProcessorCmd;
+use std::{convert::TryFrom, time::Duration};
+use tracing::Instrument;
+use warp::http;
+
+/// Interval, in which all schedules are checked for being due.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Async function to be run by an executor like tokio, which loops until
+/// shutdown and triggers all due schedules.
+pub(crate) async fn scheduler(ctx: AppContext, mut shutdown: Shutdown) {
+    let mut interval = tokio::time::interval(TICK_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.requested() => break,
+        }
+
+        if let Err(e) = run_due_schedules(&ctx).await {
+            log::error!("Unable to run schedules: {:?}", e);
+        }
+    }
+}
+
+/// Fetches all enabled schedules and runs the due ones in the background.
+async fn run_due_schedules(ctx: &AppContext) -> Result<()> {
+    let schedules = send_cmd(&ctx.tx_db, |cmd_tx| ReqCmd::GetEnabledProcessorSchedules {
+        cmd_tx,
+    })
+    .await??;
+
+    let now = Utc::now();
+    for schedule in schedules {
+        match schedule.next_run() {
+            Ok(Some(next_run)) if next_run <= now => {}
+            Ok(_) => continue,
+            Err(e) => {
+                log::warn!("Skipping ProcessorSchedule {}: {}", schedule.id, e);
+                continue;
+            }
+        }
+
+        // mark the schedule as run before running it, so a slow processor
+        // is not triggered multiple times
+        send_cmd(&ctx.tx_db, |cmd_tx| {
+            ReqCmd::UpdateProcessorScheduleLastRun {
+                id: schedule.id,
+                last_run_at: now,
+                cmd_tx,
+            }
+        })
+        .await??;
+
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = run_schedule(&ctx, &schedule, now).await {
+                log::error!("Unable to run ProcessorSchedule {}: {:?}", schedule.id, e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Runs the RequestProcessor of a single schedule with a synthetic request.
+/// The conversation is recorded as usual.
+async fn run_schedule(
+    ctx: &AppContext,
+    schedule: &ProcessorSchedule,
+    scheduled_at: DateTime<Utc>,
+) -> Result<()> {
+    let request = scheduled_request(schedule, scheduled_at)?;
+    let tx_db = ctx.tx_db.clone();
+    let response = send_cmd(&ctx.tx_proc, |cmd_tx| ProcessorCmd::RunRequestProcessor {
+        id: schedule.request_processor_id,
+        request,
+        cmd_tx,
+        tx_db,
+        prelude: true,
+        conversation_id: None,
+    })
+    .instrument(tracing::info_span!(
+        "processor_schedule",
+        schedule_id = %schedule.id,
+        processor_id = %schedule.request_processor_id,
+    ))
+    .await??;
+
+    log::info!(
+        "ProcessorSchedule {} ran RequestProcessor {} with status code {}",
+        schedule.id,
+        schedule.request_processor_id,
+        response.code
+    );
+
+    Ok(())
+}
+
+/// Builds the synthetic request, which is passed to a scheduled
+/// RequestProcessor. The `FH-Schedule-Id` and `FH-Scheduled-At` headers allow
+/// the code to distinguish scheduled from regular runs.
+fn scheduled_request(schedule: &ProcessorSchedule, scheduled_at: DateTime<Utc>) -> Result<Request> {
+    let req = http::Request::builder()
+        .method(http::Method::POST)
+        .uri(format!(
+            "/processor/{}/run_with_prelude",
+            schedule.request_processor_id
+        ))
+        .header("FH-Schedule-Id", schedule.id.to_string())
+        .header("FH-Scheduled-At", scheduled_at.to_rfc3339())
+        .body(Vec::new())?;
+
+    let mut request = Request::try_from(req)?;
+    request.processor_id = Some(schedule.request_processor_id);
+
+    Ok(request)
+}
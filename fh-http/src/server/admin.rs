@@ -14,6 +14,26 @@ pub(crate) mod filters {
             .or(get_processor(ctx))
             .or(update_processor(ctx))
             .or(delete_processor(ctx))
+            .or(get_processor_config(ctx))
+            .or(update_processor_config(ctx))
+            .or(list_processor_secrets(ctx))
+            .or(set_processor_secret(ctx))
+            .or(delete_processor_secret(ctx))
+            .or(create_processor_schedule(ctx))
+            .or(list_processor_schedules(ctx))
+            .or(delete_processor_schedule(ctx))
+            .or(list_outbound_deliveries(ctx))
+            .or(get_outbound_delivery(ctx))
+            .or(retry_outbound_delivery(ctx))
+            .or(delete_outbound_delivery(ctx))
+            .or(get_processor_usage(ctx))
+            .or(create_processor_route(ctx))
+            .or(list_processor_routes(ctx))
+            .or(delete_processor_route(ctx))
+            .or(create_pipeline(ctx))
+            .or(get_pipeline(ctx))
+            .or(update_pipeline(ctx))
+            .or(delete_pipeline(ctx))
     }
 
     /// Create a RequestProcessor.
@@ -69,15 +89,378 @@ pub(crate) mod filters {
             .and(warp::delete())
             .and_then(super::handlers::delete_processor)
     }
+
+    /// Fetch the configuration object of a RequestProcessor.
+    ///
+    /// - method: GET
+    /// - path: /admin/processor/{processor_id}/config
+    pub fn get_processor_config(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "processor" / Uuid / "config")
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::get())
+            .and_then(super::handlers::get_processor_config)
+    }
+
+    /// Replace the configuration object of a RequestProcessor.
+    ///
+    /// - method: PUT
+    /// - path: /admin/processor/{processor_id}/config
+    pub fn update_processor_config(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "processor" / Uuid / "config")
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::put())
+            .and(warp::body::json())
+            .and_then(super::handlers::update_processor_config)
+    }
+
+    /// Fetch the daily usage of a RequestProcessor. Without a range, the last
+    /// 30 days are returned.
+    ///
+    /// - method: GET
+    /// - path: /admin/processor/{processor_id}/usage?from={YYYY-MM-DD}&to={YYYY-MM-DD}
+    pub fn get_processor_usage(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "processor" / Uuid / "usage")
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::get())
+            .and(warp::query::<super::handlers::UsageQuery>())
+            .and_then(super::handlers::get_processor_usage)
+    }
+
+    /// List the names of all secrets of a RequestProcessor. Secret values are
+    /// never returned.
+    ///
+    /// - method: GET
+    /// - path: /admin/processor/{processor_id}/secret
+    pub fn list_processor_secrets(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "processor" / Uuid / "secret")
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::get())
+            .and_then(super::handlers::list_processor_secrets)
+    }
+
+    /// Create or overwrite a secret of a RequestProcessor.
+    ///
+    /// - method: PUT
+    /// - path: /admin/processor/{processor_id}/secret/{name}
+    pub fn set_processor_secret(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "processor" / Uuid / "secret" / String)
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::put())
+            .and(warp::body::json())
+            .and_then(super::handlers::set_processor_secret)
+    }
+
+    /// Delete a secret of a RequestProcessor.
+    ///
+    /// - method: DELETE
+    /// - path: /admin/processor/{processor_id}/secret/{name}
+    pub fn delete_processor_secret(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "processor" / Uuid / "secret" / String)
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::delete())
+            .and_then(super::handlers::delete_processor_secret)
+    }
+
+    /// Create a ProcessorSchedule for a RequestProcessor.
+    ///
+    /// - method: POST
+    /// - path: /admin/processor/{processor_id}/schedule
+    pub fn create_processor_schedule(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "processor" / Uuid / "schedule")
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::post())
+            .and(warp::body::json())
+            .and_then(super::handlers::create_processor_schedule)
+    }
+
+    /// List all ProcessorSchedules of a RequestProcessor.
+    ///
+    /// - method: GET
+    /// - path: /admin/processor/{processor_id}/schedule
+    pub fn list_processor_schedules(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "processor" / Uuid / "schedule")
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::get())
+            .and_then(super::handlers::list_processor_schedules)
+    }
+
+    /// Delete a ProcessorSchedule by Uuid.
+    ///
+    /// - method: DELETE
+    /// - path: /admin/schedule/{schedule_id}
+    pub fn delete_processor_schedule(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "schedule" / Uuid)
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::delete())
+            .and_then(super::handlers::delete_processor_schedule)
+    }
+
+    /// Create a ProcessorRoute.
+    ///
+    /// - method: POST
+    /// - path: /admin/processor/{processor_id}/route
+    pub fn create_processor_route(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "processor" / Uuid / "route")
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::post())
+            .and(warp::body::json())
+            .and_then(super::handlers::create_processor_route)
+    }
+
+    /// List all ProcessorRoutes of a RequestProcessor.
+    ///
+    /// - method: GET
+    /// - path: /admin/processor/{processor_id}/route
+    pub fn list_processor_routes(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "processor" / Uuid / "route")
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::get())
+            .and_then(super::handlers::list_processor_routes)
+    }
+
+    /// Delete a ProcessorRoute by Uuid.
+    ///
+    /// - method: DELETE
+    /// - path: /admin/route/{route_id}
+    pub fn delete_processor_route(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "route" / Uuid)
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::delete())
+            .and_then(super::handlers::delete_processor_route)
+    }
+
+    /// Create a Pipeline.
+    ///
+    /// - method: POST
+    /// - path: /admin/pipeline
+    pub fn create_pipeline(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "pipeline")
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::post())
+            .and(warp::body::json())
+            .and_then(super::handlers::create_pipeline)
+    }
+
+    /// Fetch a Pipeline by Uuid.
+    ///
+    /// - method: GET
+    /// - path: /admin/pipeline/{pipeline_id}
+    pub fn get_pipeline(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "pipeline" / Uuid)
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::get())
+            .and_then(super::handlers::get_pipeline)
+    }
+
+    /// Replace name and steps of a Pipeline.
+    ///
+    /// - method: PUT
+    /// - path: /admin/pipeline/{pipeline_id}
+    pub fn update_pipeline(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "pipeline" / Uuid)
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::put())
+            .and(warp::body::json())
+            .and_then(super::handlers::update_pipeline)
+    }
+
+    /// Delete a Pipeline by Uuid.
+    ///
+    /// - method: DELETE
+    /// - path: /admin/pipeline/{pipeline_id}
+    pub fn delete_pipeline(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "pipeline" / Uuid)
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::delete())
+            .and_then(super::handlers::delete_pipeline)
+    }
+
+    /// List all OutboundDeliveries, optionally filtered by status.
+    ///
+    /// - method: GET
+    /// - path: /admin/delivery?status={pending|delivered|dead}
+    pub fn list_outbound_deliveries(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "delivery")
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::get())
+            .and(warp::query::<super::handlers::DeliveryQuery>())
+            .and_then(super::handlers::list_outbound_deliveries)
+    }
+
+    /// Fetch an OutboundDelivery by Uuid.
+    ///
+    /// - method: GET
+    /// - path: /admin/delivery/{delivery_id}
+    pub fn get_outbound_delivery(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "delivery" / Uuid)
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::get())
+            .and_then(super::handlers::get_outbound_delivery)
+    }
+
+    /// Schedule an OutboundDelivery for an immediate attempt.
+    ///
+    /// - method: POST
+    /// - path: /admin/delivery/{delivery_id}/retry
+    pub fn retry_outbound_delivery(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "delivery" / Uuid / "retry")
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::post())
+            .and_then(super::handlers::retry_outbound_delivery)
+    }
+
+    /// Drop an OutboundDelivery by Uuid.
+    ///
+    /// - method: DELETE
+    /// - path: /admin/delivery/{delivery_id}
+    pub fn delete_outbound_delivery(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("admin" / "delivery" / Uuid)
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::delete())
+            .and_then(super::handlers::delete_outbound_delivery)
+    }
 }
 
 pub(crate) mod handlers {
     use crate::server::{error::FhHttpError, AppContext};
+    use chrono::{Duration, NaiveDate, Utc};
     use fh_core::FhLockingError;
-    use fh_db::{request_processor::RequestProcessor, ReqCmd};
+    use fh_db::{
+        outbound_delivery::{DeliveryStatus, OutboundDelivery},
+        pipeline::Pipeline,
+        processor_route::ProcessorRoute,
+        processor_schedule::ProcessorSchedule,
+        processor_secret::SecretValue,
+        processor_usage::UsageQuota,
+        request_conversation::LogLevel,
+        request_processor::{
+            CorsPolicy, RequestProcessor, RequestProcessorConfig, RequestProcessorLanguage,
+            RequestProcessorRuntime, WebhookVerification,
+        },
+        ReqCmd,
+    };
+    use serde::{Deserialize, Deserializer};
     use tokio::sync::oneshot;
     use uuid::Uuid;
 
+    /// Query parameters for listing OutboundDeliveries.
+    #[derive(Debug, Deserialize)]
+    pub(crate) struct DeliveryQuery {
+        status: Option<DeliveryStatus>,
+    }
+
+    /// Query parameters for fetching the usage of a RequestProcessor.
+    #[derive(Debug, Deserialize)]
+    pub(crate) struct UsageQuery {
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    }
+
+    /// Number of days, whose usage is returned by default.
+    const USAGE_DAYS: i64 = 30;
+
+    /// JSON request body for updating a RequestProcessor. Only `name`,
+    /// `language`, `runtime` and `code` are required, omitted properties keep
+    /// their stored values. The optional policies are removed with an
+    /// explicit `null`.
+    #[derive(Debug, Deserialize)]
+    pub(crate) struct ProcessorUpdate {
+        name: String,
+        language: RequestProcessorLanguage,
+        runtime: RequestProcessorRuntime,
+        code: String,
+        config: Option<RequestProcessorConfig>,
+        min_log_level: Option<LogLevel>,
+        #[serde(default, deserialize_with = "present")]
+        webhook_verification: Option<Option<WebhookVerification>>,
+        #[serde(default, deserialize_with = "present")]
+        quota: Option<Option<UsageQuota>>,
+        #[serde(default, deserialize_with = "present")]
+        cors: Option<Option<CorsPolicy>>,
+    }
+
+    impl ProcessorUpdate {
+        /// Merges the update into the stored RequestProcessor.
+        fn apply(self, processor: &mut RequestProcessor) {
+            processor.name = self.name;
+            processor.language = self.language;
+            processor.runtime = self.runtime;
+            processor.code = self.code;
+            if let Some(config) = self.config {
+                processor.config = config;
+            }
+            if let Some(min_log_level) = self.min_log_level {
+                processor.min_log_level = min_log_level;
+            }
+            if let Some(webhook_verification) = self.webhook_verification {
+                processor.webhook_verification = webhook_verification;
+            }
+            if let Some(quota) = self.quota {
+                processor.quota = quota;
+            }
+            if let Some(cors) = self.cors {
+                processor.cors = cors;
+            }
+        }
+    }
+
+    /// Deserializes a property, which is part of the body, to `Some`. Together
+    /// with `#[serde(default)]`, an omitted property is `None`, while an
+    /// explicit `null` is `Some(None)`.
+    fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        T::deserialize(deserializer).map(Some)
+    }
+
+    /// JSON request body for setting a secret.
+    #[derive(Debug, Deserialize)]
+    pub(crate) struct SecretBody {
+        value: SecretValue,
+    }
+
     /// Creates a RequestProcessor
     pub(crate) async fn create_processor(
         ctx: AppContext,
@@ -107,12 +490,17 @@ pub(crate) mod handlers {
         Ok(warp::reply::json(&proc))
     }
 
-    /// Updates a RequestProcessor.
+    /// Updates a RequestProcessor. Properties, which are not part of the
+    /// body, keep their stored values, see [`ProcessorUpdate`].
     pub(crate) async fn update_processor(
         id: Uuid,
         ctx: AppContext,
-        processor: RequestProcessor,
+        update: ProcessorUpdate,
     ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let mut processor = db_cmd!(ctx, ReqCmd::GetRequestProcessor { id, cmd_tx }, cmd_rx);
+        update.apply(&mut processor);
+
         let (cmd_tx, cmd_rx) = oneshot::channel();
         let res = db_cmd!(
             ctx,
@@ -134,6 +522,351 @@ pub(crate) mod handlers {
     ) -> Result<impl warp::Reply, warp::Rejection> {
         let (cmd_tx, cmd_rx) = oneshot::channel();
         db_cmd!(ctx, ReqCmd::DeleteRequestProcessor { id, cmd_tx }, cmd_rx);
+        // the routes of the RequestProcessor are deleted with it
+        ctx.router.invalidate();
+
+        Ok(warp::reply())
+    }
+
+    /// Fetches the configuration object of a RequestProcessor.
+    pub(crate) async fn get_processor_config(
+        id: Uuid,
+        ctx: AppContext,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let proc = db_cmd!(ctx, ReqCmd::GetRequestProcessor { id, cmd_tx }, cmd_rx);
+
+        Ok(warp::reply::json(&proc.config))
+    }
+
+    /// Replaces the configuration object of a RequestProcessor.
+    pub(crate) async fn update_processor_config(
+        id: Uuid,
+        ctx: AppContext,
+        config: RequestProcessorConfig,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let res = db_cmd!(
+            ctx,
+            ReqCmd::UpdateRequestProcessorConfig { id, config, cmd_tx },
+            cmd_rx
+        );
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Fetches the daily usage of a RequestProcessor.
+    pub(crate) async fn get_processor_usage(
+        id: Uuid,
+        ctx: AppContext,
+        query: UsageQuery,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let to = query.to.unwrap_or_else(|| Utc::now().naive_utc().date());
+        let from = query
+            .from
+            .unwrap_or_else(|| to - Duration::days(USAGE_DAYS - 1));
+
+        // fails with 404 for unknown RequestProcessors
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let _ = db_cmd!(ctx, ReqCmd::GetRequestProcessor { id, cmd_tx }, cmd_rx);
+
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let res = db_cmd!(
+            ctx,
+            ReqCmd::GetUsageReport {
+                request_processor_id: id,
+                from,
+                to,
+                cmd_tx,
+            },
+            cmd_rx
+        );
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Lists the secrets of a RequestProcessor.
+    pub(crate) async fn list_processor_secrets(
+        id: Uuid,
+        ctx: AppContext,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let res = db_cmd!(
+            ctx,
+            ReqCmd::ListProcessorSecrets {
+                request_processor_id: id,
+                cmd_tx,
+            },
+            cmd_rx
+        );
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Sets a secret of a RequestProcessor.
+    pub(crate) async fn set_processor_secret(
+        id: Uuid,
+        name: String,
+        ctx: AppContext,
+        body: SecretBody,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let res = db_cmd!(
+            ctx,
+            ReqCmd::SetProcessorSecret {
+                request_processor_id: id,
+                name,
+                value: body.value,
+                cmd_tx,
+            },
+            cmd_rx
+        );
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Deletes a secret of a RequestProcessor.
+    pub(crate) async fn delete_processor_secret(
+        id: Uuid,
+        name: String,
+        ctx: AppContext,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        db_cmd!(
+            ctx,
+            ReqCmd::DeleteProcessorSecret {
+                request_processor_id: id,
+                name,
+                cmd_tx,
+            },
+            cmd_rx
+        );
+
+        Ok(warp::reply())
+    }
+
+    /// Creates a ProcessorSchedule.
+    pub(crate) async fn create_processor_schedule(
+        id: Uuid,
+        ctx: AppContext,
+        mut schedule: ProcessorSchedule,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        schedule.request_processor_id = id;
+
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let res = db_cmd!(
+            ctx,
+            ReqCmd::CreateProcessorSchedule { schedule, cmd_tx },
+            cmd_rx
+        );
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Lists the ProcessorSchedules of a RequestProcessor.
+    pub(crate) async fn list_processor_schedules(
+        id: Uuid,
+        ctx: AppContext,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let res = db_cmd!(
+            ctx,
+            ReqCmd::ListProcessorSchedules {
+                request_processor_id: id,
+                cmd_tx,
+            },
+            cmd_rx
+        );
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Deletes a ProcessorSchedule.
+    pub(crate) async fn delete_processor_schedule(
+        id: Uuid,
+        ctx: AppContext,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        db_cmd!(ctx, ReqCmd::DeleteProcessorSchedule { id, cmd_tx }, cmd_rx);
+
+        Ok(warp::reply())
+    }
+
+    /// Creates a ProcessorRoute.
+    pub(crate) async fn create_processor_route(
+        id: Uuid,
+        ctx: AppContext,
+        mut route: ProcessorRoute,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        route.request_processor_id = id;
+
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let res = db_cmd!(ctx, ReqCmd::CreateProcessorRoute { route, cmd_tx }, cmd_rx);
+        ctx.router.invalidate();
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Lists the ProcessorRoutes of a RequestProcessor.
+    pub(crate) async fn list_processor_routes(
+        id: Uuid,
+        ctx: AppContext,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let res = db_cmd!(
+            ctx,
+            ReqCmd::ListProcessorRoutes {
+                request_processor_id: Some(id),
+                cmd_tx,
+            },
+            cmd_rx
+        );
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Deletes a ProcessorRoute.
+    pub(crate) async fn delete_processor_route(
+        id: Uuid,
+        ctx: AppContext,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        db_cmd!(ctx, ReqCmd::DeleteProcessorRoute { id, cmd_tx }, cmd_rx);
+        ctx.router.invalidate();
+
+        Ok(warp::reply())
+    }
+
+    /// Creates a Pipeline.
+    pub(crate) async fn create_pipeline(
+        ctx: AppContext,
+        pipeline: Pipeline,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let res = db_cmd!(ctx, ReqCmd::CreatePipeline { pipeline, cmd_tx }, cmd_rx);
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Fetches a Pipeline.
+    pub(crate) async fn get_pipeline(
+        id: Uuid,
+        ctx: AppContext,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let res = db_cmd!(ctx, ReqCmd::GetPipeline { id, cmd_tx }, cmd_rx);
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Updates a Pipeline.
+    pub(crate) async fn update_pipeline(
+        id: Uuid,
+        ctx: AppContext,
+        pipeline: Pipeline,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let res = db_cmd!(
+            ctx,
+            ReqCmd::UpdatePipeline {
+                id,
+                pipeline,
+                cmd_tx,
+            },
+            cmd_rx
+        );
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Deletes a Pipeline.
+    pub(crate) async fn delete_pipeline(
+        id: Uuid,
+        ctx: AppContext,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        db_cmd!(ctx, ReqCmd::DeletePipeline { id, cmd_tx }, cmd_rx);
+
+        Ok(warp::reply())
+    }
+
+    /// Lists OutboundDeliveries.
+    pub(crate) async fn list_outbound_deliveries(
+        ctx: AppContext,
+        query: DeliveryQuery,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let deliveries = db_cmd!(
+            ctx,
+            ReqCmd::ListOutboundDeliveries {
+                status: query.status,
+                cmd_tx,
+            },
+            cmd_rx
+        );
+
+        let mut res = Vec::with_capacity(deliveries.len());
+        for delivery in deliveries {
+            res.push(redact_delivery(&ctx, delivery).await?);
+        }
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Fetches an OutboundDelivery.
+    pub(crate) async fn get_outbound_delivery(
+        id: Uuid,
+        ctx: AppContext,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let delivery = db_cmd!(ctx, ReqCmd::GetOutboundDelivery { id, cmd_tx }, cmd_rx);
+        let res = redact_delivery(&ctx, delivery).await?;
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Schedules an OutboundDelivery for an immediate attempt.
+    pub(crate) async fn retry_outbound_delivery(
+        id: Uuid,
+        ctx: AppContext,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let delivery = db_cmd!(ctx, ReqCmd::RetryOutboundDelivery { id, cmd_tx }, cmd_rx);
+        let res = redact_delivery(&ctx, delivery).await?;
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Replaces the secret values of the issuing RequestProcessor in an
+    /// OutboundDelivery. Its request is stored with the secrets, which are
+    /// sent on the next attempt, but they must never be readable via the API.
+    async fn redact_delivery(
+        ctx: &AppContext,
+        delivery: OutboundDelivery,
+    ) -> Result<OutboundDelivery, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let secrets = db_cmd!(
+            ctx,
+            ReqCmd::GetProcessorSecretValues {
+                request_processor_id: delivery.request_processor_id,
+                cmd_tx,
+            },
+            cmd_rx
+        );
+        let values: Vec<&str> = secrets.values().map(|s| s.expose()).collect();
+
+        delivery
+            .redact(&values)
+            .map_err(|e| warp::reject::custom(FhHttpError::new(e)))
+    }
+
+    /// Drops an OutboundDelivery.
+    pub(crate) async fn delete_outbound_delivery(
+        id: Uuid,
+        ctx: AppContext,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        db_cmd!(ctx, ReqCmd::DeleteOutboundDelivery { id, cmd_tx }, cmd_rx);
 
         Ok(warp::reply())
     }
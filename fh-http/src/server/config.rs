@@ -0,0 +1,209 @@
+//! Configuration of fh-http. Values are read from an optional TOML file,
+//! whose path is given by `CORE_CONFIG_FILE`, and environment variables,
+//! which take precedence over the file.
+use anyhow::{bail, Context, Result};
+use fh_core::rate_limit::RateLimit;
+use serde::Deserialize;
+use std::{
+    env, fs,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// Contains application specific configuration variables.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Local address, the HTTP server will bind to.
+    pub(crate) bind_address: IpAddr,
+    /// Local port, the HTTP server will bind to.
+    pub(crate) port: u16,
+    /// If set, the server only accepts HTTPS connections.
+    pub(crate) tls: Option<TlsConfig>,
+    /// Maximum size of request bodies in bytes.
+    pub(crate) body_limit: u64,
+    /// Capacity of the channel to the database manager.
+    pub(crate) db_channel_capacity: usize,
+    /// Capacity of the channel to the request processing manager.
+    pub(crate) processor_channel_capacity: usize,
+    /// Seconds to wait for in-flight requests, when shutting down.
+    pub(crate) shutdown_timeout_secs: u64,
+    /// Addresses of reverse proxies, whose `X-Forwarded-*` headers are
+    /// trusted.
+    pub(crate) trusted_proxies: Vec<IpAddr>,
+    /// If set, traces are exported to this OpenTelemetry collector, e.g.
+    /// `http://localhost:4317`.
+    pub(crate) otlp_endpoint: Option<String>,
+    /// Limits for invocations of RequestProcessors and outbound requests.
+    pub(crate) rate_limits: RateLimitConfig,
+}
+
+/// PEM encoded certificate chain and private key for HTTPS.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TlsConfig {
+    pub(crate) cert_path: PathBuf,
+    pub(crate) key_path: PathBuf,
+}
+
+/// Token bucket rate limits. Each limit is optional, by default nothing is
+/// limited.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct RateLimitConfig {
+    /// Limit of invocations per RequestProcessor.
+    pub(crate) processor: Option<RateLimit>,
+    /// Limit of invocations per client.
+    pub(crate) client: Option<RateLimit>,
+    /// Header, which identifies clients, e.g. an API key checked by a
+    /// gateway. Without it, clients are identified by their address.
+    pub(crate) client_key_header: Option<String>,
+    /// Limit of outbound requests per target host.
+    pub(crate) outbound: Option<RateLimit>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 3030,
+            tls: None,
+            body_limit: 2 * 1024 * 1024,
+            db_channel_capacity: 4096,
+            processor_channel_capacity: 4096,
+            shutdown_timeout_secs: 30,
+            trusted_proxies: Vec::new(),
+            otlp_endpoint: None,
+            rate_limits: RateLimitConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration from `CORE_CONFIG_FILE`, if given, and the
+    /// environment.
+    pub(crate) fn load() -> Result<Self> {
+        let mut config = match env_var("CORE_CONFIG_FILE") {
+            Some(path) => {
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("Cannot read config file '{}'", path))?;
+                toml::from_str(&content)
+                    .with_context(|| format!("Invalid config file '{}'", path))?
+            }
+            None => Self::default(),
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Overrides all values, for which an environment variable is set.
+    fn apply_env(&mut self) -> Result<()> {
+        parse_env("CORE_BIND_ADDRESS", &mut self.bind_address)?;
+        parse_env("CORE_PORT", &mut self.port)?;
+        parse_env("CORE_BODY_LIMIT", &mut self.body_limit)?;
+        parse_env("CORE_DB_CHANNEL_CAPACITY", &mut self.db_channel_capacity)?;
+        parse_env(
+            "CORE_PROCESSOR_CHANNEL_CAPACITY",
+            &mut self.processor_channel_capacity,
+        )?;
+        parse_env("CORE_SHUTDOWN_TIMEOUT", &mut self.shutdown_timeout_secs)?;
+
+        if let Some(proxies) = env_var("CORE_TRUSTED_PROXIES") {
+            self.trusted_proxies = proxies
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(str::parse::<IpAddr>)
+                .collect::<Result<_, _>>()
+                .context("Invalid value of CORE_TRUSTED_PROXIES")?;
+        }
+
+        if let Some(endpoint) = env_var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+            self.otlp_endpoint = Some(endpoint);
+        }
+
+        let limits = &mut self.rate_limits;
+        parse_rate_limit_env("CORE_RATE_LIMIT_PROCESSOR", &mut limits.processor)?;
+        parse_rate_limit_env("CORE_RATE_LIMIT_CLIENT", &mut limits.client)?;
+        parse_rate_limit_env("CORE_RATE_LIMIT_OUTBOUND", &mut limits.outbound)?;
+        if let Some(header) = env_var("CORE_RATE_LIMIT_CLIENT_HEADER") {
+            limits.client_key_header = Some(header);
+        }
+
+        match (env_var("CORE_TLS_CERT"), env_var("CORE_TLS_KEY")) {
+            (Some(cert_path), Some(key_path)) => {
+                self.tls = Some(TlsConfig {
+                    cert_path: cert_path.into(),
+                    key_path: key_path.into(),
+                })
+            }
+            (None, None) => {}
+            _ => bail!("CORE_TLS_CERT and CORE_TLS_KEY must be set together"),
+        }
+
+        Ok(())
+    }
+
+    /// Rejects values, which would prevent the server from working.
+    fn validate(&self) -> Result<()> {
+        if self.db_channel_capacity == 0 || self.processor_channel_capacity == 0 {
+            bail!("Channel capacities must be greater than 0");
+        }
+        if self.body_limit == 0 {
+            bail!("The body limit must be greater than 0");
+        }
+        let limits = &self.rate_limits;
+        for limit in [limits.processor, limits.client, limits.outbound]
+            .iter()
+            .flatten()
+        {
+            limit.validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Socket address, the HTTP server will bind to.
+    pub(crate) fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.port)
+    }
+}
+
+/// Returns the value of an environment variable, treating empty values as
+/// unset.
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+/// Parses an environment variable into `value`, if it is set.
+fn parse_env<T>(name: &str, value: &mut T) -> Result<()>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    if let Some(raw) = env_var(name) {
+        *value = raw
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid value of {}", name))?;
+    }
+
+    Ok(())
+}
+
+/// Parses an environment variable in the format `<rate>[,<burst>]` into
+/// `limit`, if it is set.
+fn parse_rate_limit_env(name: &str, limit: &mut Option<RateLimit>) -> Result<()> {
+    if let Some(raw) = env_var(name) {
+        *limit = Some(
+            raw.parse()
+                .with_context(|| format!("Invalid value of {}", name))?,
+        );
+    }
+
+    Ok(())
+}
@@ -13,29 +13,33 @@ pub(crate) mod filters {
         get_request_conversation_audit_items(ctx).or(get_request_conversation(ctx))
     }
 
-    /// Fetch a RequestConversation by Uuid.
+    /// Fetch a RequestConversation by Uuid. The optional `level` omits log
+    /// items below the given level.
     ///
     /// - method: GET
-    /// - path: /conversation/{conversation_id}
+    /// - path: /conversation/{conversation_id}?level={debug|info|warn|error}
     pub(crate) fn get_request_conversation(
         ctx: &AppContext,
     ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
         warp::path!("conversation" / Uuid)
             .and(util::with_ctx(ctx.clone()))
             .and(warp::get())
+            .and(warp::query::<super::handlers::LevelQuery>())
             .and_then(super::handlers::get_request_conversation)
     }
 
-    /// Fetch a RequestConversations AuditItems by Conversation Uuid.
+    /// Fetch a RequestConversations AuditItems by Conversation Uuid. The
+    /// optional `level` omits log items below the given level.
     ///
     /// - method: GET
-    /// - path: /conversation/{conversation_id}/audit_item
+    /// - path: /conversation/{conversation_id}/audit_item?level={debug|info|warn|error}
     pub(crate) fn get_request_conversation_audit_items(
         ctx: &AppContext,
     ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
         warp::path!("conversation" / Uuid / "audit_item")
             .and(util::with_ctx(ctx.clone()))
             .and(warp::get())
+            .and(warp::query::<super::handlers::LevelQuery>())
             .and_then(super::handlers::get_request_conversation_audit_items)
     }
 }
@@ -43,19 +47,31 @@ pub(crate) mod filters {
 pub(crate) mod handlers {
     use crate::server::{error::FhHttpError, AppContext};
     use fh_core::FhLockingError;
-    use fh_db::ReqCmd;
+    use fh_db::{request_conversation::LogLevel, ReqCmd};
+    use serde::Deserialize;
     use tokio::sync::oneshot;
     use uuid::Uuid;
 
+    /// Query parameters for filtering log items by level.
+    #[derive(Debug, Deserialize)]
+    pub(crate) struct LevelQuery {
+        level: Option<LogLevel>,
+    }
+
     /// Gets a RequestConversation.
     pub(crate) async fn get_request_conversation(
         id: Uuid,
         ctx: AppContext,
+        query: LevelQuery,
     ) -> Result<impl warp::Reply, warp::Rejection> {
         let (cmd_tx, cmd_rx) = oneshot::channel();
         let res = db_cmd!(
             ctx,
-            ReqCmd::GetRequestConversation { id, cmd_tx: cmd_tx },
+            ReqCmd::GetRequestConversation {
+                id,
+                level: query.level,
+                cmd_tx: cmd_tx
+            },
             cmd_rx
         );
 
@@ -66,11 +82,16 @@ pub(crate) mod handlers {
     pub(crate) async fn get_request_conversation_audit_items(
         id: Uuid,
         ctx: AppContext,
+        query: LevelQuery,
     ) -> Result<impl warp::Reply, warp::Rejection> {
         let (cmd_tx, cmd_rx) = oneshot::channel();
         let res = db_cmd!(
             ctx,
-            ReqCmd::GetRequestConversationAuditItems { id, cmd_tx: cmd_tx },
+            ReqCmd::GetRequestConversationAuditItems {
+                id,
+                level: query.level,
+                cmd_tx: cmd_tx
+            },
             cmd_rx
         );
 
@@ -0,0 +1,154 @@
+//! Cross-Origin Resource Sharing, which is configured declaratively per
+//! RequestProcessor. Preflight requests are answered without spending a V8
+//! isolate on them.
+use crate::server::error::CorsRejected;
+use fh_core::request::Request;
+use fh_db::request_processor::CorsPolicy;
+use warp::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    Rejection, Reply,
+};
+
+/// Response header, which is always readable by cross-origin clients.
+const CONVERSATION_ID_HEADER: &str = "FH-Conversation-Id";
+
+/// Outcome of applying a [`CorsPolicy`] to an incoming request.
+pub(crate) enum Cors {
+    /// A preflight request, which is answered right away.
+    Preflight(warp::reply::Response),
+    /// Headers to add to the response of the RequestProcessor. Empty for
+    /// same-origin requests and RequestProcessors without policy.
+    Headers(HeaderMap),
+}
+
+/// Returns the method, a preflight request asks for, or `None` if the request
+/// is not a preflight request.
+pub(crate) fn preflight_method(request: &Request) -> Option<&str> {
+    if !request.method.eq_ignore_ascii_case("OPTIONS") || request.header("origin").is_none() {
+        return None;
+    }
+
+    request.header("access-control-request-method")
+}
+
+/// Applies the CORS policy of a RequestProcessor. Cross-origin requests, which
+/// are not allowed by the policy, are rejected with `403 Forbidden`. Without
+/// policy, even preflight requests are passed to the RequestProcessor.
+pub(crate) fn apply(policy: Option<&CorsPolicy>, request: &Request) -> Result<Cors, Rejection> {
+    let (policy, origin) = match (policy, request.header("origin")) {
+        (Some(policy), Some(origin)) => (policy, origin),
+        _ => return Ok(Cors::Headers(HeaderMap::new())),
+    };
+
+    if is_same_origin(request, origin) {
+        return Ok(Cors::Headers(HeaderMap::new()));
+    }
+
+    if !policy.allows_origin(origin) {
+        return Err(reject(format!("Origin '{}' is not allowed", origin)));
+    }
+
+    let mut headers = HeaderMap::new();
+    if policy.allows_any_origin() {
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_ORIGIN,
+            HeaderValue::from_static("*"),
+        );
+    } else {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, header_value(origin)?);
+        headers.insert(header::VARY, HeaderValue::from_static("Origin"));
+    }
+    if policy.allow_credentials {
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+            HeaderValue::from_static("true"),
+        );
+    }
+
+    let method = match preflight_method(request) {
+        Some(method) => method,
+        None => {
+            if !policy.allows_method(&request.method) {
+                return Err(reject(format!(
+                    "Method '{}' is not allowed",
+                    request.method
+                )));
+            }
+
+            let mut exposed = vec![CONVERSATION_ID_HEADER];
+            exposed.extend(policy.expose_headers.iter().map(String::as_str));
+            headers.insert(
+                header::ACCESS_CONTROL_EXPOSE_HEADERS,
+                header_value(&exposed.join(", "))?,
+            );
+            return Ok(Cors::Headers(headers));
+        }
+    };
+
+    preflight(policy, request, method, headers).map(Cors::Preflight)
+}
+
+/// Browsers send the `Origin` header with same-origin requests as well, e.g.
+/// for `POST`. These are not subject to the policy.
+fn is_same_origin(request: &Request, origin: &str) -> bool {
+    match (&request.scheme, &request.host) {
+        (Some(scheme), Some(host)) => {
+            origin.eq_ignore_ascii_case(&format!("{}://{}", scheme, host))
+        }
+        _ => false,
+    }
+}
+
+/// Answers a preflight request with the allowed method and headers.
+fn preflight(
+    policy: &CorsPolicy,
+    request: &Request,
+    method: &str,
+    mut headers: HeaderMap,
+) -> Result<warp::reply::Response, Rejection> {
+    if !policy.allows_method(method) {
+        return Err(reject(format!("Method '{}' is not allowed", method)));
+    }
+
+    let requested_headers = request
+        .header("access-control-request-headers")
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>();
+    if let Some(name) = requested_headers
+        .iter()
+        .find(|name| !policy.allows_header(name))
+    {
+        return Err(reject(format!("Header '{}' is not allowed", name)));
+    }
+
+    headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, header_value(method)?);
+    if !requested_headers.is_empty() {
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_HEADERS,
+            header_value(&requested_headers.join(", "))?,
+        );
+    }
+    if let Some(max_age) = policy.max_age_secs {
+        headers.insert(header::ACCESS_CONTROL_MAX_AGE, max_age.into());
+    }
+    headers.append(
+        header::VARY,
+        HeaderValue::from_static("Access-Control-Request-Method, Access-Control-Request-Headers"),
+    );
+
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    response.headers_mut().extend(headers);
+    Ok(response)
+}
+
+/// Converts a value taken from the request back into a header value.
+fn header_value(value: &str) -> Result<HeaderValue, Rejection> {
+    HeaderValue::from_str(value).map_err(|_| reject(format!("Invalid header value '{}'", value)))
+}
+
+fn reject(reason: String) -> Rejection {
+    warp::reject::custom(CorsRejected::new(reason))
+}
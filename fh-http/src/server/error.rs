@@ -1,7 +1,10 @@
+use fh_core::error::ScriptError;
 use fh_db::{ReqCmd, RequestProcessorError};
 use fh_v8::ProcessorCmd;
 use serde::Serialize;
+use std::time::Duration;
 use tokio::sync::{mpsc::error::SendError, oneshot::error::RecvError};
+use uuid::Uuid;
 use warp::{http::StatusCode, reject::Reject, Rejection, Reply};
 
 /// General HTTP Response JSON error response envelope.
@@ -9,6 +12,13 @@ use warp::{http::StatusCode, reject::Reject, Rejection, Reply};
 struct ErrorMessage {
     code: u16,
     message: String,
+    /// Only for failed RequestProcessors: the conversation, which recorded
+    /// the error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conversation_id: Option<Uuid>,
+    /// Only for failed RequestProcessors: details of the error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ScriptError>,
 }
 
 /// HttpError wrapper for warp rejection.
@@ -29,6 +39,65 @@ impl Reject for FhHttpError<SendError<ReqCmd>> {}
 impl Reject for FhHttpError<SendError<ProcessorCmd>> {}
 impl Reject for FhHttpError<anyhow::Error> {}
 
+/// Rejection for request bodies, which exceed the configured limit.
+#[derive(Debug)]
+pub(crate) struct PayloadTooLarge {
+    limit: u64,
+}
+
+impl PayloadTooLarge {
+    pub(crate) fn new(limit: u64) -> Self {
+        Self { limit }
+    }
+}
+
+impl Reject for PayloadTooLarge {}
+
+/// Rejection for requests, which exceed a rate limit.
+#[derive(Debug)]
+pub(crate) struct RateLimited {
+    scope: &'static str,
+    retry_after: Duration,
+}
+
+impl RateLimited {
+    pub(crate) fn new(scope: &'static str, retry_after: Duration) -> Self {
+        Self { scope, retry_after }
+    }
+}
+
+impl Reject for RateLimited {}
+
+/// Rejection for requests to a custom route, which does not allow the
+/// request's method.
+#[derive(Debug)]
+pub(crate) struct RouteMethodNotAllowed {
+    allowed: Vec<String>,
+}
+
+impl RouteMethodNotAllowed {
+    pub(crate) fn new(allowed: Vec<String>) -> Self {
+        Self { allowed }
+    }
+}
+
+impl Reject for RouteMethodNotAllowed {}
+
+/// Rejection for cross-origin requests, which are not allowed by the CORS
+/// policy of a RequestProcessor.
+#[derive(Debug)]
+pub(crate) struct CorsRejected {
+    reason: String,
+}
+
+impl CorsRejected {
+    pub(crate) fn new(reason: String) -> Self {
+        Self { reason }
+    }
+}
+
+impl Reject for CorsRejected {}
+
 /// Fallback function which receives a rejection and detects various error types
 /// and returns a [`ErrorMessage`] to display useful HTTP errors to the
 /// requesting user.
@@ -37,6 +106,10 @@ pub(crate) async fn handle_rejections(
 ) -> Result<impl Reply, std::convert::Infallible> {
     let code;
     let message;
+    let mut conversation_id = None;
+    let mut error = None;
+    let mut retry_after = None;
+    let mut allow = None;
 
     if err.is_not_found() {
         code = StatusCode::NOT_FOUND;
@@ -50,16 +123,67 @@ pub(crate) async fn handle_rejections(
                 code = StatusCode::NOT_FOUND;
                 message = custom_error.err.to_string();
             }
+            RequestProcessorError::Validation(_) => {
+                code = StatusCode::BAD_REQUEST;
+                message = custom_error.err.to_string();
+            }
+            RequestProcessorError::QuotaExceeded(_) => {
+                code = StatusCode::TOO_MANY_REQUESTS;
+                message = custom_error.err.to_string();
+            }
+            RequestProcessorError::Conflict(_) => {
+                code = StatusCode::CONFLICT;
+                message = custom_error.err.to_string();
+            }
+            RequestProcessorError::Timeout(_) => {
+                code = StatusCode::GATEWAY_TIMEOUT;
+                message = custom_error.err.to_string();
+            }
+            RequestProcessorError::Script {
+                conversation_id: ref id,
+                error: ref script_error,
+            } => {
+                code = StatusCode::INTERNAL_SERVER_ERROR;
+                message = custom_error.err.to_string();
+                conversation_id = Some(*id);
+                error = Some(script_error.clone());
+            }
+            RequestProcessorError::WebhookVerification {
+                conversation_id: ref id,
+                ..
+            } => {
+                code = StatusCode::UNAUTHORIZED;
+                message = custom_error.err.to_string();
+                conversation_id = Some(*id);
+            }
             _ => {
                 code = StatusCode::INTERNAL_SERVER_ERROR;
                 message = custom_error.err.to_string();
             }
         }
+    } else if let Some(e) = err.find::<PayloadTooLarge>() {
+        code = StatusCode::PAYLOAD_TOO_LARGE;
+        message = format!("Request body exceeds the limit of {} bytes", e.limit);
+    } else if let Some(e) = err.find::<RateLimited>() {
+        code = StatusCode::TOO_MANY_REQUESTS;
+        message = format!("Rate limit per {} exceeded", e.scope);
+        // whole seconds, rounded up, so the client does not retry too early
+        retry_after = Some(e.retry_after.as_secs() + (e.retry_after.subsec_nanos() > 0) as u64);
+    } else if let Some(e) = err.find::<CorsRejected>() {
+        code = StatusCode::FORBIDDEN;
+        message = format!("CORS request rejected: {}", e.reason);
+    } else if let Some(e) = err.find::<RouteMethodNotAllowed>() {
+        code = StatusCode::METHOD_NOT_ALLOWED;
+        message = "METHOD_NOT_ALLOWED".to_string();
+        allow = Some(e.allowed.join(", "));
     } else if let Some(_) = err.find::<warp::reject::MethodNotAllowed>() {
         // We can handle a specific error, here METHOD_NOT_ALLOWED,
         // and render it however we want
         code = StatusCode::METHOD_NOT_ALLOWED;
         message = "METHOD_NOT_ALLOWED".to_string();
+    } else if let Some(e) = err.find::<warp::reject::InvalidQuery>() {
+        code = StatusCode::BAD_REQUEST;
+        message = e.to_string();
     } else if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
         // This error happens if the body could not be deserialized correctly
         // We can use the cause to analyze the error and customize the error message
@@ -76,7 +200,42 @@ pub(crate) async fn handle_rejections(
     let json = warp::reply::json(&ErrorMessage {
         code: code.as_u16(),
         message: message,
+        conversation_id,
+        error,
     });
 
-    Ok(warp::reply::with_status(json, code))
+    let mut response = warp::reply::with_status(json, code).into_response();
+    if let Some(id) = conversation_id {
+        response.headers_mut().insert(
+            "FH-Conversation-Id",
+            id.to_string()
+                .parse()
+                .expect("Uuid is a valid header value"),
+        );
+    }
+    if let Some(secs) = retry_after {
+        response.headers_mut().insert(
+            "Retry-After",
+            secs.to_string()
+                .parse()
+                .expect("Number is a valid header value"),
+        );
+    }
+    if let Some(methods) = allow {
+        response.headers_mut().insert(
+            "Allow",
+            methods.parse().expect("Methods are valid header values"),
+        );
+    }
+
+    Ok(response)
+}
+
+/// Renders a rejection right away, like [`handle_rejections`] does, e.g. to
+/// add headers to the error response.
+pub(crate) async fn render_rejection(err: Rejection) -> warp::reply::Response {
+    match handle_rejections(err).await {
+        Ok(reply) => reply.into_response(),
+        Err(never) => match never {},
+    }
 }
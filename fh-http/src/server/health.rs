@@ -0,0 +1,240 @@
+//! HTTP Endpoints for the `/healthz` and `/readyz` paths, which are polled by
+//! orchestrators.
+use crate::server::AppContext;
+use fh_core::metrics::Queue;
+use fh_db::ReqCmd;
+use fh_v8::{ProcessorCmd, PROCESSING_TIMEOUT};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+/// Time to wait for the answer of a manager to a ping.
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Time, for which the processing manager may not progress, before it counts
+/// as stuck. Every execution is limited by [`PROCESSING_TIMEOUT`].
+const MAX_STALL: Duration = Duration::from_secs(PROCESSING_TIMEOUT.as_secs() + 5);
+
+/// Share of the channel capacity, from which on a queue counts as saturated.
+const SATURATION_RATIO: f64 = 0.9;
+
+/// Wraps all warp Filters for the health endpoints.
+pub(crate) mod filters {
+    use crate::server::{util, AppContext};
+    use warp::Filter;
+
+    /// Convenient wrapper function which contains all filters.
+    pub(crate) fn health_filters(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        get_health().or(get_readiness(ctx))
+    }
+
+    /// Liveness check, which succeeds as long as the server answers.
+    ///
+    /// - method: GET
+    /// - path: /healthz
+    pub(crate) fn get_health(
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("healthz")
+            .and(warp::get())
+            .and_then(super::handlers::get_health)
+    }
+
+    /// Readiness check of the database, the request processing manager and
+    /// the command queues.
+    ///
+    /// - method: GET
+    /// - path: /readyz
+    pub(crate) fn get_readiness(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("readyz")
+            .and(util::with_ctx(ctx.clone()))
+            .and(warp::get())
+            .and_then(super::handlers::get_readiness)
+    }
+}
+
+pub(crate) mod handlers {
+    use super::Status;
+    use crate::server::AppContext;
+    use std::collections::BTreeMap;
+    use warp::http::StatusCode;
+
+    /// Answers, that the server is alive.
+    pub(crate) async fn get_health() -> Result<impl warp::Reply, warp::Rejection> {
+        let mut res = BTreeMap::new();
+        res.insert("status", "ok");
+
+        Ok(warp::reply::json(&res))
+    }
+
+    /// Runs all readiness checks. Responds with 503, if any of them failed.
+    pub(crate) async fn get_readiness(
+        ctx: AppContext,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let readiness = super::readiness(&ctx).await;
+        let code = match readiness.status {
+            Status::Ok => StatusCode::OK,
+            Status::Failed => StatusCode::SERVICE_UNAVAILABLE,
+        };
+
+        Ok(warp::reply::with_status(
+            warp::reply::json(&readiness),
+            code,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Ok,
+    Failed,
+}
+
+/// Result of a single readiness check. Only the fields, which apply to the
+/// kind of check, are set.
+#[derive(Debug, Serialize)]
+struct Check {
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    depth: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    capacity: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Check {
+    fn new(status: Status) -> Self {
+        Self {
+            status,
+            latency_ms: None,
+            depth: None,
+            capacity: None,
+            error: None,
+        }
+    }
+}
+
+/// Response body of `/readyz`. The overall status is only ok, if all checks
+/// are ok.
+#[derive(Debug, Serialize)]
+struct Readiness {
+    status: Status,
+    checks: BTreeMap<&'static str, Check>,
+}
+
+async fn readiness(ctx: &AppContext) -> Readiness {
+    let mut checks = BTreeMap::new();
+
+    // the queues are checked first, as the pings are queued themselves
+    checks.insert(
+        "db_queue",
+        queue_check(Queue::Db, ctx.config.db_channel_capacity),
+    );
+    checks.insert(
+        "processor_queue",
+        queue_check(Queue::Processor, ctx.config.processor_channel_capacity),
+    );
+
+    let (db, processor) = tokio::join!(
+        ping(async {
+            fh_core::send_cmd(&ctx.tx_db, |cmd_tx| ReqCmd::Ping { cmd_tx }).await??;
+            Ok::<_, anyhow::Error>(())
+        }),
+        processor_check(ctx),
+    );
+    checks.insert("db", db);
+    checks.insert("processor", processor);
+
+    let status = if checks.values().all(|check| check.status == Status::Ok) {
+        Status::Ok
+    } else {
+        Status::Failed
+    };
+
+    Readiness { status, checks }
+}
+
+/// Waits for the answer to a ping, at most [`PING_TIMEOUT`].
+async fn ping<F>(answer: F) -> Check
+where
+    F: Future<Output = Result<(), anyhow::Error>>,
+{
+    let start = Instant::now();
+    let res = tokio::time::timeout(PING_TIMEOUT, answer).await;
+
+    match res {
+        Ok(Ok(())) => Check {
+            latency_ms: Some(start.elapsed().as_millis()),
+            ..Check::new(Status::Ok)
+        },
+        Ok(Err(e)) => Check {
+            error: Some(e.to_string()),
+            ..Check::new(Status::Failed)
+        },
+        Err(_) => Check {
+            error: Some(format!("No answer within {} ms", PING_TIMEOUT.as_millis())),
+            ..Check::new(Status::Failed)
+        },
+    }
+}
+
+/// Checks the processing manager. It runs commands one after another, so a
+/// ping would queue behind slow RequestProcessors. A busy manager is checked by
+/// its progress instead.
+async fn processor_check(ctx: &AppContext) -> Check {
+    if let Some(stalled_for) = ctx.processing.stalled_for() {
+        return progress_check(stalled_for);
+    }
+
+    let check = ping(fh_core::send_cmd(&ctx.tx_proc, |cmd_tx| {
+        ProcessorCmd::Ping { cmd_tx }
+    }))
+    .await;
+    // the manager may have started a command, which was queued before the ping
+    match (check.status, ctx.processing.stalled_for()) {
+        (Status::Failed, Some(stalled_for)) => progress_check(stalled_for),
+        _ => check,
+    }
+}
+
+/// Fails, if a busy manager didn't progress within [`MAX_STALL`].
+fn progress_check(stalled_for: Duration) -> Check {
+    if stalled_for <= MAX_STALL {
+        return Check::new(Status::Ok);
+    }
+
+    Check {
+        error: Some(format!("No progress within {} ms", MAX_STALL.as_millis())),
+        ..Check::new(Status::Failed)
+    }
+}
+
+/// Compares the number of waiting commands with the capacity of the channel.
+fn queue_check(queue: Queue, capacity: usize) -> Check {
+    let depth = queue.depth();
+    let status = if depth as f64 >= capacity as f64 * SATURATION_RATIO {
+        Status::Failed
+    } else {
+        Status::Ok
+    };
+
+    Check {
+        depth: Some(depth),
+        capacity: Some(capacity),
+        error: match status {
+            Status::Ok => None,
+            Status::Failed => Some("Queue is saturated".to_string()),
+        },
+        ..Check::new(status)
+    }
+}
@@ -0,0 +1,94 @@
+//! HTTP Endpoint for the `/metrics` path and the recording of HTTP request
+//! metrics.
+use fh_core::metrics::{HTTP_REQUESTS, HTTP_REQUEST_DURATION};
+use std::time::Instant;
+use uuid::Uuid;
+use warp::{path::FullPath, reply::Response, Reply};
+
+/// Wraps all warp Filters for the metrics endpoint.
+pub(crate) mod filters {
+    use warp::Filter;
+
+    /// Convenient wrapper function which contains all filters.
+    pub(crate) fn metrics_filters(
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        get_metrics()
+    }
+
+    /// Fetch all metrics in the Prometheus text format.
+    ///
+    /// - method: GET
+    /// - path: /metrics
+    pub(crate) fn get_metrics(
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("metrics")
+            .and(warp::get())
+            .and_then(super::handlers::get_metrics)
+    }
+}
+
+pub(crate) mod handlers {
+    use crate::server::error::FhHttpError;
+    use warp::{http::header::CONTENT_TYPE, Rejection, Reply};
+
+    /// Renders all metrics.
+    pub(crate) async fn get_metrics() -> Result<impl Reply, Rejection> {
+        let metrics =
+            fh_core::metrics::gather().map_err(|e| warp::reject::custom(FhHttpError::new(e)))?;
+
+        Ok(warp::reply::with_header(
+            metrics,
+            CONTENT_TYPE,
+            "text/plain; version=0.0.4",
+        ))
+    }
+}
+
+/// Response extension, which carries the id of the RequestProcessor or
+/// Pipeline, that answered the request. Set by the handlers, once they found
+/// it, e.g. from a custom route.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Answered(pub(crate) Uuid);
+
+/// Label of requests to the run endpoints, which were not answered by an
+/// existing RequestProcessor or Pipeline. The ids of the path are not used,
+/// as every unknown id would create new time series.
+const UNKNOWN: &str = "unknown";
+
+/// Marks a response as answered by the given RequestProcessor or Pipeline.
+pub(crate) fn answered_by(mut response: Response, id: Uuid) -> Response {
+    response.extensions_mut().insert(Answered(id));
+    response
+}
+
+/// Checks, if the path is one of the run endpoints, e.g.
+/// `/processor/{processor_id}/run`.
+fn is_run_path(path: &str) -> bool {
+    let mut segments = path.trim_start_matches('/').split('/');
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some("processor"), Some(_), Some(action)) => action.starts_with("run"),
+        (Some("pipeline"), Some(_), Some("run")) => true,
+        _ => false,
+    }
+}
+
+/// Records count and latency of answered requests to RequestProcessors and
+/// Pipelines. Wraps the rendered responses, so rejected requests are counted
+/// with their final status as well.
+pub(crate) fn record_request<R: Reply>(started: Instant, path: FullPath, reply: R) -> Response {
+    let response = reply.into_response();
+    let id = match response.extensions().get::<Answered>() {
+        Some(Answered(id)) => id.to_string(),
+        None if is_run_path(path.as_str()) => UNKNOWN.to_string(),
+        None => return response,
+    };
+
+    HTTP_REQUESTS
+        .with_label_values(&[&id, response.status().as_str()])
+        .inc();
+    HTTP_REQUEST_DURATION
+        .with_label_values(&[&id])
+        .observe(started.elapsed().as_secs_f64());
+
+    response
+}
@@ -3,48 +3,109 @@
 mod util;
 
 pub(crate) mod admin;
+pub(crate) mod config;
 pub(crate) mod conversation;
+pub(crate) mod cors;
 pub(crate) mod error;
+pub(crate) mod health;
+pub(crate) mod metrics;
+pub(crate) mod pipeline;
 pub(crate) mod public;
+pub(crate) mod rate_limit;
+pub(crate) mod route;
+pub(crate) mod webhook;
 
 use crate::server::admin::filters::admin_filters;
 use crate::server::conversation::filters::conversation_filters;
+use crate::server::health::filters::health_filters;
+use crate::server::metrics::filters::metrics_filters;
+use crate::server::pipeline::filters::pipeline_filters;
 use crate::server::public::filters::public_filters;
-use fh_core::ReqSender;
+use crate::server::rate_limit::RateLimiters;
+use crate::server::route::{filters::route_filters, Router};
+use fh_core::{rate_limit::RateLimiter, shutdown::Shutdown, ReqSender};
 use fh_db::ReqCmd;
-use fh_v8::ProcessorCmd;
+use fh_v8::{ProcessingContext, ProcessorCmd};
+use std::{sync::Arc, time::Instant};
 use warp::Filter;
 
-/// Contain application specific configuration variables. This will include
-/// further config options later.
-#[derive(Debug)]
-pub(crate) struct Config {
-    /// Local port, the HTTP server will bind to.
-    pub(crate) port: u16,
-}
+pub(crate) use config::Config;
 
 /// Async function to be run by an executor like tokio. Loads all endpoint
-/// configurations and runs the server.
-pub(crate) async fn web_server(ctx: AppContext, cfg: &Config) {
+/// configurations and runs the server. On shutdown, no new connections are
+/// accepted and the function returns, once all in-flight requests are
+/// answered.
+pub(crate) async fn web_server(ctx: AppContext, mut shutdown: Shutdown) {
     let routes = public_filters(&ctx)
+        .or(pipeline_filters(&ctx))
         .or(admin_filters(&ctx))
         .or(conversation_filters(&ctx))
+        .or(metrics_filters())
+        .or(health_filters(&ctx))
+        .or(route_filters(&ctx))
         .with(warp::log("fh-core"))
         .recover(error::handle_rejections);
+    let routes = warp::any()
+        .map(Instant::now)
+        .and(warp::path::full())
+        .and(routes)
+        .map(metrics::record_request);
 
-    warp::serve(routes).run(([127, 0, 0, 1], cfg.port)).await
+    let server = warp::serve(routes);
+    let addr = ctx.config.socket_addr();
+    let signal = async move { shutdown.requested().await };
+    match &ctx.config.tls {
+        Some(tls) => {
+            let (_, server) = server
+                .tls()
+                .cert_path(&tls.cert_path)
+                .key_path(&tls.key_path)
+                .bind_with_graceful_shutdown(addr, signal);
+            server.await
+        }
+        None => {
+            let (_, server) = server.bind_with_graceful_shutdown(addr, signal);
+            server.await
+        }
+    }
 }
 
 /// Cheap clonable wrapper struct which contains cheap clonable references so
 /// that it can be used to pass it around in warp filters.
 #[derive(Debug, Clone)]
 pub struct AppContext {
-    tx_db: ReqSender<ReqCmd>,
-    tx_proc: ReqSender<ProcessorCmd>,
+    pub(crate) tx_db: ReqSender<ReqCmd>,
+    pub(crate) tx_proc: ReqSender<ProcessorCmd>,
+    pub(crate) config: Arc<Config>,
+    pub(crate) rate_limiters: Arc<RateLimiters>,
+    pub(crate) router: Arc<Router>,
+    /// State, which is shared with the processing manager and the delivery
+    /// worker.
+    pub(crate) processing: ProcessingContext,
 }
 
 impl AppContext {
-    pub fn new(tx_db: ReqSender<ReqCmd>, tx_proc: ReqSender<ProcessorCmd>) -> Self {
-        Self { tx_db, tx_proc }
+    pub(crate) fn new(
+        tx_db: ReqSender<ReqCmd>,
+        tx_proc: ReqSender<ProcessorCmd>,
+        config: Config,
+    ) -> Self {
+        let rate_limiters = RateLimiters::new(&config.rate_limits);
+        let processing = ProcessingContext::new(
+            config
+                .rate_limits
+                .outbound
+                .map(|limit| Arc::new(RateLimiter::new(limit))),
+            rate_limiters.processor_limiter(),
+        );
+
+        Self {
+            tx_db,
+            tx_proc,
+            rate_limiters: Arc::new(rate_limiters),
+            router: Arc::new(Router::default()),
+            processing,
+            config: Arc::new(config),
+        }
     }
 }
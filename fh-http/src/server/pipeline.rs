@@ -0,0 +1,128 @@
+//! HTTP Endpoints for the `/pipeline` path.
+
+/// Wraps all warp Filters for the Pipeline endpoints.
+pub(crate) mod filters {
+    use crate::server::{util, AppContext};
+    use uuid::Uuid;
+    use warp::Filter;
+
+    /// Run a Pipeline by Id. The steps run with or without prelude and sequel
+    /// as configured.
+    ///
+    /// - method: any
+    /// - path: /pipeline/{pipeline_id}/run
+    pub(crate) fn pipeline_filters(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("pipeline" / Uuid / "run")
+            .and(util::with_ctx(ctx.clone()))
+            .and(util::extract_request(ctx))
+            .and_then(super::handlers::run_pipeline)
+    }
+}
+
+pub(crate) mod handlers {
+    use crate::server::{
+        error::{render_rejection, FhHttpError},
+        metrics::answered_by,
+        AppContext,
+    };
+    use fh_core::{
+        request::Request, response::Response, telemetry::extract_context, FhLockingError,
+    };
+    use fh_db::ReqCmd;
+    use fh_v8::ProcessorCmd;
+    use tokio::sync::oneshot;
+    use tracing::Instrument;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use uuid::Uuid;
+    use warp::{Rejection, Reply};
+
+    /// Run a Pipeline. Invocations are rate limited and counted like those of
+    /// a single RequestProcessor, using the Pipeline's Uuid. Requests for
+    /// unknown ids are not counted per id.
+    pub(crate) async fn run_pipeline(
+        id: Uuid,
+        ctx: AppContext,
+        request: Request,
+    ) -> Result<warp::reply::Response, Rejection> {
+        let span = tracing::info_span!(
+            "run_pipeline",
+            pipeline_id = %id,
+            method = %request.method,
+        );
+        span.set_parent(&extract_context(&request.headers));
+
+        let response = match run(id, ctx, request).instrument(span).await {
+            Ok(response) => response,
+            Err(rejection) => render_rejection(rejection).await,
+        };
+
+        Ok(response)
+    }
+
+    /// Actually runs a Pipeline, see [`run_pipeline`].
+    async fn run(
+        id: Uuid,
+        ctx: AppContext,
+        request: Request,
+    ) -> Result<warp::reply::Response, Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let _ = db_cmd!(ctx, ReqCmd::GetPipeline { id, cmd_tx }, cmd_rx);
+
+        let response = match invoke(id, ctx, request).await {
+            Ok(response) => response,
+            Err(rejection) => render_rejection(rejection).await,
+        };
+
+        Ok(answered_by(response, id))
+    }
+
+    /// Invokes a found Pipeline, see [`run`].
+    async fn invoke(
+        id: Uuid,
+        ctx: AppContext,
+        request: Request,
+    ) -> Result<warp::reply::Response, Rejection> {
+        ctx.rate_limiters.check(id, &request)?;
+
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let res = proc_cmd!(
+            ctx,
+            ProcessorCmd::RunPipeline {
+                id,
+                request,
+                cmd_tx,
+                tx_db: ctx.tx_db,
+            },
+            cmd_rx
+        );
+
+        let conversation_id = response_header(&res, "FH-Conversation-Id")?;
+        let pipeline_id = response_header(&res, "FH-Pipeline-Id")?;
+        Ok(warp::reply::with_header(
+            warp::reply::with_header(
+                warp::reply::json(&res),
+                "FH-Conversation-Id",
+                conversation_id,
+            ),
+            "FH-Pipeline-Id",
+            pipeline_id,
+        )
+        .into_response())
+    }
+
+    /// Returns the value of a header, which the final response must contain.
+    fn response_header(res: &Response, name: &str) -> Result<String, Rejection> {
+        res.headers
+            .get(name)
+            .and_then(|values| values.first())
+            .cloned()
+            .ok_or_else(|| {
+                warp::reject::custom(FhHttpError::new(anyhow::Error::msg(format!(
+                    "Missing response header '{}'.",
+                    name
+                ))))
+            })
+    }
+}
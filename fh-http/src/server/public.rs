@@ -25,7 +25,7 @@ pub(crate) mod filters {
         warp::path!("processor" / Uuid / "run")
             .and(util::with_ctx(ctx.clone()))
             .and(util::with_prelude(false))
-            .and(util::extract_request())
+            .and(util::extract_request(ctx))
             .and_then(super::handlers::run_request_processor)
     }
 
@@ -39,7 +39,7 @@ pub(crate) mod filters {
         warp::path!("processor" / Uuid / "run_with_prelude")
             .and(util::with_ctx(ctx.clone()))
             .and(util::with_prelude(true))
-            .and(util::extract_request())
+            .and(util::extract_request(ctx))
             .and_then(super::handlers::run_request_processor)
     }
 
@@ -53,26 +53,133 @@ pub(crate) mod filters {
     ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
         warp::path!("hello" / String)
             .and(util::with_ctx(ctx.clone()))
-            .and(util::extract_request())
+            .and(util::extract_request(ctx))
             .and_then(super::handlers::process_request)
     }
 }
 
 pub(crate) mod handlers {
-    use crate::server::{error::FhHttpError, AppContext};
-    use fh_core::{request::Request, FhLockingError};
+    use crate::server::{
+        cors::{self, Cors},
+        error::{render_rejection, FhHttpError},
+        metrics::answered_by,
+        webhook::verify_webhook,
+        AppContext,
+    };
+    use fh_core::{request::Request, telemetry::extract_context, FhLockingError};
+    use fh_db::{
+        processor_job::{ProcessorJob, ProcessorJobStatus},
+        request_processor::RequestProcessor,
+        ReqCmd,
+    };
     use fh_v8::ProcessorCmd;
+    use serde::Serialize;
     use tokio::sync::oneshot;
+    use tracing::Instrument;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
     use uuid::Uuid;
-    use warp::{Rejection, Reply};
+    use warp::{http::StatusCode, Rejection, Reply};
 
-    /// Run a RequestProcessor.
+    /// JSON response body of an asynchronous invocation.
+    #[derive(Debug, Serialize)]
+    pub(crate) struct AsyncInvocation {
+        job_id: Uuid,
+        conversation_id: Uuid,
+        status: ProcessorJobStatus,
+    }
+
+    /// Checks, if the client asked for an asynchronous invocation via the
+    /// `Prefer: respond-async` header (RFC 7240).
+    fn prefers_async(request: &Request) -> bool {
+        request
+            .headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("prefer"))
+            .flat_map(|(_, values)| values.iter())
+            .flat_map(|value| value.split(','))
+            .any(|pref| pref.trim().eq_ignore_ascii_case("respond-async"))
+    }
+
+    /// Run a RequestProcessor. If the client prefers an asynchronous response,
+    /// the invocation is queued and answered immediately with `202 Accepted`.
+    /// Requests with an invalid webhook signature are rejected upfront.
+    ///
+    /// The invocation is traced as child of the client's trace, if it sent a
+    /// `traceparent` header.
     pub(crate) async fn run_request_processor(
+        id: Uuid,
+        ctx: AppContext,
+        prelude: bool,
+        mut request: Request,
+    ) -> Result<warp::reply::Response, Rejection> {
+        request.processor_id = Some(id);
+
+        let span = tracing::info_span!(
+            "run_request_processor",
+            processor_id = %id,
+            method = %request.method,
+            prelude,
+        );
+        span.set_parent(&extract_context(&request.headers));
+
+        let response = match run(id, ctx, prelude, request).instrument(span).await {
+            Ok(response) => response,
+            Err(rejection) => render_rejection(rejection).await,
+        };
+
+        Ok(response)
+    }
+
+    /// Actually runs a RequestProcessor, see [`run_request_processor`].
+    /// Preflight requests are answered according to the RequestProcessor's
+    /// CORS policy without running it and without taking a token of the rate
+    /// limits.
+    ///
+    /// Once the RequestProcessor is found, errors are rendered right here, so
+    /// they carry the CORS headers and the response is counted for the
+    /// RequestProcessor, even if it was found by a custom route. Requests for
+    /// unknown ids are not, so clients can't create arbitrary metric labels.
+    async fn run(
         id: Uuid,
         ctx: AppContext,
         prelude: bool,
         request: Request,
-    ) -> Result<impl Reply, Rejection> {
+    ) -> Result<warp::reply::Response, Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let processor = db_cmd!(ctx, ReqCmd::GetRequestProcessor { id, cmd_tx }, cmd_rx);
+        let cors_headers = match cors::apply(processor.cors.as_ref(), &request) {
+            Ok(Cors::Preflight(response)) => return Ok(answered_by(response, id)),
+            Ok(Cors::Headers(headers)) => headers,
+            Err(rejection) => return Ok(answered_by(render_rejection(rejection).await, id)),
+        };
+
+        let res = match ctx.rate_limiters.check(id, &request) {
+            Ok(()) => invoke(id, ctx, prelude, &processor, request).await,
+            Err(rejection) => Err(rejection),
+        };
+        let mut response = match res {
+            Ok(response) => response,
+            Err(rejection) => render_rejection(rejection).await,
+        };
+        response.headers_mut().extend(cors_headers);
+
+        Ok(answered_by(response, id))
+    }
+
+    /// Invokes a RequestProcessor, either right away or as ProcessorJob.
+    async fn invoke(
+        id: Uuid,
+        ctx: AppContext,
+        prelude: bool,
+        processor: &RequestProcessor,
+        request: Request,
+    ) -> Result<warp::reply::Response, Rejection> {
+        verify_webhook(&ctx, processor, &request).await?;
+
+        if prefers_async(&request) {
+            return queue_request_processor(id, ctx, prelude, request).await;
+        }
+
         let (cmd_tx, cmd_rx) = oneshot::channel();
         let res = proc_cmd!(
             ctx,
@@ -82,6 +189,7 @@ pub(crate) mod handlers {
                 cmd_tx,
                 tx_db: ctx.tx_db,
                 prelude,
+                conversation_id: None,
             },
             cmd_rx
         );
@@ -95,7 +203,54 @@ pub(crate) mod handlers {
                     "Missing response header 'FH-Conversation-Id'.",
                 ))))?[0]
                 .clone(),
-        ))
+        )
+        .into_response())
+    }
+
+    /// Queue a RequestProcessor invocation as ProcessorJob. The conversation
+    /// is created upfront, so the client can poll it right away.
+    async fn queue_request_processor(
+        id: Uuid,
+        ctx: AppContext,
+        prelude: bool,
+        request: Request,
+    ) -> Result<warp::reply::Response, Rejection> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let _ = db_cmd!(ctx, ReqCmd::GetRequestProcessor { id, cmd_tx }, cmd_rx);
+
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let conversation = db_cmd!(
+            ctx,
+            ReqCmd::CreateRequestConversation {
+                request_processor_id: id,
+                parent_id: None,
+                cmd_tx,
+            },
+            cmd_rx
+        );
+
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let job = db_cmd!(
+            ctx,
+            ReqCmd::CreateProcessorJob {
+                job: ProcessorJob::new(id, conversation.id, prelude, request),
+                cmd_tx,
+            },
+            cmd_rx
+        );
+
+        let body = AsyncInvocation {
+            job_id: job.id,
+            conversation_id: job.conversation_id,
+            status: job.status,
+        };
+
+        Ok(warp::reply::with_header(
+            warp::reply::with_status(warp::reply::json(&body), StatusCode::ACCEPTED),
+            "FH-Conversation-Id",
+            job.conversation_id.to_string(),
+        )
+        .into_response())
     }
 
     /// Run the static RequestProcessor from `fh_v8/src/flow_heater.js`.
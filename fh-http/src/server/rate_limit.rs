@@ -0,0 +1,83 @@
+//! Rate limiting of RequestProcessor invocations per processor and per
+//! client.
+use crate::server::{config::RateLimitConfig, error::RateLimited};
+use fh_core::{metrics::RATE_LIMITED, rate_limit::RateLimiter, request::Request};
+use std::sync::Arc;
+use uuid::Uuid;
+use warp::Rejection;
+
+/// Key for clients, whose address is unknown.
+const UNKNOWN_CLIENT: &str = "unknown";
+
+/// All configured limits for incoming requests.
+#[derive(Debug)]
+pub(crate) struct RateLimiters {
+    /// Shared with the processing manager, which limits chained invocations.
+    processor: Option<Arc<RateLimiter<Uuid>>>,
+    client: Option<RateLimiter<String>>,
+    client_key_header: Option<String>,
+}
+
+impl RateLimiters {
+    pub(crate) fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            processor: config
+                .processor
+                .map(|limit| Arc::new(RateLimiter::new(limit))),
+            client: config.client.map(RateLimiter::new),
+            client_key_header: config.client_key_header.clone(),
+        }
+    }
+
+    /// Limit per RequestProcessor, if configured.
+    pub(crate) fn processor_limiter(&self) -> Option<Arc<RateLimiter<Uuid>>> {
+        self.processor.clone()
+    }
+
+    /// Takes a token for the client first and then for the RequestProcessor,
+    /// so rejected clients do not use up the limit of the processor.
+    pub(crate) fn check(&self, id: Uuid, request: &Request) -> Result<(), Rejection> {
+        if let Some(limiter) = &self.client {
+            check(limiter, self.client_key(request), "client")?;
+        }
+        if let Some(limiter) = &self.processor {
+            check(limiter, id, "processor")?;
+        }
+
+        Ok(())
+    }
+
+    /// Identifies the client by the configured header or, if it is missing,
+    /// by its address. Like `X-Forwarded-For`, the header is only honoured,
+    /// if the request was sent by a trusted proxy, otherwise clients could
+    /// evade the limit by sending a new key with every request.
+    fn client_key(&self, request: &Request) -> String {
+        let header = self
+            .client_key_header
+            .as_ref()
+            .filter(|_| request.trusted_proxy)
+            .and_then(|name| {
+                request
+                    .headers
+                    .iter()
+                    .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                    .and_then(|(_, values)| values.first())
+            });
+
+        match (header, &request.remote_addr) {
+            (Some(key), _) => format!("key:{}", key),
+            (None, Some(addr)) => format!("addr:{}", addr),
+            (None, None) => UNKNOWN_CLIENT.to_string(),
+        }
+    }
+}
+
+fn check<K>(limiter: &RateLimiter<K>, key: K, scope: &'static str) -> Result<(), Rejection>
+where
+    K: std::hash::Hash + Eq,
+{
+    limiter.check(key).map_err(|retry_after| {
+        RATE_LIMITED.with_label_values(&[scope]).inc();
+        warp::reject::custom(RateLimited::new(scope, retry_after))
+    })
+}
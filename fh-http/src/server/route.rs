@@ -0,0 +1,150 @@
+//! Custom routes, which make RequestProcessors reachable at a host and path
+//! pattern like `/hooks/ttn/{device}` instead of
+//! `/processor/{processor_id}/run`.
+use crate::server::{error::FhHttpError, AppContext};
+use fh_core::FhLockingError;
+use fh_db::{processor_route::ProcessorRoute, ReqCmd};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, PoisonError, RwLock,
+};
+use tokio::sync::oneshot;
+use warp::Rejection;
+
+/// Cache of all ProcessorRoutes, ordered by precedence. The routes are loaded
+/// from the database on the first request and again after every change.
+#[derive(Debug, Default)]
+pub(crate) struct Router {
+    routes: RwLock<Option<Arc<Vec<ProcessorRoute>>>>,
+    /// Incremented on every change, so a load, which raced with a change, does
+    /// not store outdated routes.
+    generation: AtomicUsize,
+}
+
+impl Router {
+    /// Drops the cached routes. Must be called after routes were changed.
+    pub(crate) fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *self.routes.write().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// Returns the cached routes or loads them from the database.
+    async fn routes(&self, ctx: &AppContext) -> Result<Arc<Vec<ProcessorRoute>>, Rejection> {
+        let cached = self
+            .routes
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if let Some(routes) = cached {
+            return Ok(routes);
+        }
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let mut routes = db_cmd!(
+            ctx,
+            ReqCmd::ListProcessorRoutes {
+                request_processor_id: None,
+                cmd_tx,
+            },
+            cmd_rx
+        );
+        routes.sort_by_key(ProcessorRoute::precedence);
+        let routes = Arc::new(routes);
+
+        let mut cached = self.routes.write().unwrap_or_else(PoisonError::into_inner);
+        if self.generation.load(Ordering::SeqCst) == generation {
+            *cached = Some(routes.clone());
+        }
+
+        Ok(routes)
+    }
+}
+
+/// Wraps the warp Filter for custom routes.
+pub(crate) mod filters {
+    use crate::server::{util, AppContext};
+    use fh_db::processor_route::RESERVED_PREFIXES;
+    use warp::Filter;
+
+    /// Run the RequestProcessor of the first matching route. Has to be the
+    /// last filter, as it accepts any method and path.
+    ///
+    /// - method: any
+    /// - path: any, except for the built-in endpoints
+    pub(crate) fn route_filters(
+        ctx: &AppContext,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        not_reserved()
+            .and(util::with_ctx(ctx.clone()))
+            .and(util::extract_request(ctx))
+            .and_then(super::handlers::run_route)
+    }
+
+    /// Warp filter which rejects paths of the built-in endpoints, so their
+    /// bodies are not read a second time, after their own filter rejected
+    /// them.
+    fn not_reserved() -> impl Filter<Extract = (), Error = warp::Rejection> + Copy {
+        warp::path::full()
+            .and_then(|path: warp::path::FullPath| async move {
+                let first = path.as_str().trim_start_matches('/').split('/').next();
+                match first {
+                    Some(first) if RESERVED_PREFIXES.contains(&first) => {
+                        Err(warp::reject::not_found())
+                    }
+                    _ => Ok(()),
+                }
+            })
+            .untuple_one()
+    }
+}
+
+pub(crate) mod handlers {
+    use crate::server::{cors, error::RouteMethodNotAllowed, public, AppContext};
+    use fh_core::request::Request;
+    use warp::Rejection;
+
+    /// Runs the RequestProcessor of the first route, which matches host, path
+    /// and method of the request. If routes only match host and path, the
+    /// request is rejected with `405 Method Not Allowed`. Preflight requests
+    /// are matched by the method they ask for.
+    pub(crate) async fn run_route(
+        ctx: AppContext,
+        mut request: Request,
+    ) -> Result<warp::reply::Response, Rejection> {
+        let routes = ctx.router.routes(&ctx).await?;
+        let method = cors::preflight_method(&request)
+            .unwrap_or(request.method.as_str())
+            .to_string();
+
+        let mut allowed = Vec::new();
+        for route in routes.iter() {
+            let params = match route.matches(request.host.as_deref(), &request.path) {
+                Some(params) => params,
+                None => continue,
+            };
+
+            if !route.allows_method(&method) {
+                allowed.extend(route.methods.iter().cloned());
+                continue;
+            }
+
+            request.path_params = params;
+            return public::handlers::run_request_processor(
+                route.request_processor_id,
+                ctx.clone(),
+                route.prelude,
+                request,
+            )
+            .await;
+        }
+
+        if allowed.is_empty() {
+            Err(warp::reject::not_found())
+        } else {
+            allowed.sort();
+            allowed.dedup();
+            Err(warp::reject::custom(RouteMethodNotAllowed::new(allowed)))
+        }
+    }
+}
@@ -1,7 +1,18 @@
-use crate::server::error::FhHttpError;
+use crate::server::{
+    error::{FhHttpError, PayloadTooLarge},
+    Config,
+};
 use fh_core::request::Request;
-use std::convert::TryFrom;
-use warp::{http, Filter, Rejection};
+use std::{
+    convert::TryFrom,
+    net::{IpAddr, SocketAddr},
+};
+use tokio::stream::StreamExt;
+use warp::{
+    http,
+    hyper::body::{Buf, Bytes},
+    Filter, Rejection,
+};
 
 use super::AppContext;
 
@@ -19,27 +30,97 @@ pub(crate) fn with_prelude(
     warp::any().map(move || prelude)
 }
 
-/// Warp filter which extracts the full http request data.
-pub(crate) fn extract_request() -> impl Filter<Extract = (Request,), Error = warp::Rejection> + Copy
-{
+/// Warp filter which extracts the full http request data, including the
+/// client's address, host and scheme. Bodies larger than the configured limit
+/// are rejected.
+pub(crate) fn extract_request(
+    ctx: &AppContext,
+) -> impl Filter<Extract = (Request,), Error = warp::Rejection> + Clone {
+    let config = ctx.config.clone();
+
     warp::method()
         .and(warp::path::full())
+        .and(raw_query())
         .and(warp::header::headers_cloned())
-        .and(warp::body::bytes())
-        .and_then(try_extract_request)
+        .and(body_limit(config.body_limit))
+        .and(limited_body(config.body_limit))
+        .and(warp::addr::remote())
+        .and_then(move |method, path, query, headers, body: Bytes, remote| {
+            let config = config.clone();
+            async move {
+                let mut request = try_extract_request(method, path, query, headers, body)?;
+                resolve_client(&mut request, remote, &config);
+                Ok(request)
+            }
+        })
+}
+
+/// Warp filter which rejects requests, whose announced content length exceeds
+/// `limit` bytes, before the body is read.
+fn body_limit(limit: u64) -> impl Filter<Extract = (), Error = warp::Rejection> + Copy {
+    warp::header::optional::<u64>("content-length")
+        .and_then(move |length: Option<u64>| async move {
+            match length {
+                Some(length) if length > limit => {
+                    Err(warp::reject::custom(PayloadTooLarge::new(limit)))
+                }
+                _ => Ok(()),
+            }
+        })
+        .untuple_one()
+}
+
+/// Warp filter which reads the body, but stops as soon as it exceeds `limit`
+/// bytes. Chunked bodies have no content length to check upfront, so they are
+/// never buffered completely.
+fn limited_body(limit: u64) -> impl Filter<Extract = (Bytes,), Error = warp::Rejection> + Clone {
+    warp::body::stream().and_then(move |stream| async move {
+        tokio::pin!(stream);
+
+        let mut body = Vec::new();
+        while let Some(buf) = stream.next().await {
+            let mut buf =
+                buf.map_err(|e| warp::reject::custom(FhHttpError::new(anyhow::Error::new(e))))?;
+            while buf.has_remaining() {
+                let chunk = buf.bytes();
+                if (body.len() + chunk.len()) as u64 > limit {
+                    return Err(warp::reject::custom(PayloadTooLarge::new(limit)));
+                }
+
+                body.extend_from_slice(chunk);
+                let n = chunk.len();
+                buf.advance(n);
+            }
+        }
+
+        Ok(Bytes::from(body))
+    })
+}
+
+/// Warp filter which extracts the raw query string, which is empty if the
+/// request has none.
+fn raw_query() -> impl Filter<Extract = (String,), Error = std::convert::Infallible> + Copy {
+    warp::query::raw().or(warp::any().map(String::new)).unify()
 }
 
 /// Helpe function which tries to extract a [`fh_core::request::Request`] from
 /// warps request data.
-async fn try_extract_request(
+fn try_extract_request(
     method: http::Method,
     path: warp::path::FullPath,
+    query: String,
     headers: http::HeaderMap,
-    body: warp::hyper::body::Bytes,
+    body: Bytes,
 ) -> Result<Request, Rejection> {
+    let uri = if query.is_empty() {
+        path.as_str().to_string()
+    } else {
+        format!("{}?{}", path.as_str(), query)
+    };
+
     let mut req = http::Request::builder()
         .method(method)
-        .uri(path.as_str())
+        .uri(uri)
         .body(body.iter().cloned().collect::<Vec<u8>>())
         .expect("request builder");
     {
@@ -49,6 +130,60 @@ async fn try_extract_request(
     Request::try_from(req).map_err(|e| warp::reject::custom(FhHttpError::new(e)))
 }
 
+/// Fills the client information of a request. The `X-Forwarded-*` headers are
+/// only honoured, if the request was sent by a trusted proxy, otherwise the
+/// client could spoof them.
+fn resolve_client(request: &mut Request, remote: Option<SocketAddr>, config: &Config) {
+    let trusted_proxies = &config.trusted_proxies;
+    let peer = remote.map(|addr| addr.ip());
+    let trusted = peer.map_or(false, |ip| trusted_proxies.contains(&ip));
+    let forwarded = |name| {
+        request
+            .header(name)
+            .filter(|_| trusted)
+            .map(|value| value.split(',').next().unwrap_or("").trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let host = forwarded("x-forwarded-host").or_else(|| request.header("host").map(str::to_string));
+    let scheme = forwarded("x-forwarded-proto").unwrap_or_else(|| match config.tls {
+        Some(_) => "https".to_string(),
+        None => "http".to_string(),
+    });
+    let remote_addr = if trusted {
+        forwarded_client(request, trusted_proxies).or(peer)
+    } else {
+        peer
+    };
+
+    request.host = host;
+    request.scheme = Some(scheme.to_ascii_lowercase());
+    request.remote_addr = remote_addr.map(|ip| ip.to_string());
+    request.trusted_proxy = trusted;
+}
+
+/// Returns the client address from `X-Forwarded-For`. The list is walked from
+/// the right, as every proxy appends the address of its peer. The first
+/// address, which is not a trusted proxy, is the client.
+fn forwarded_client(request: &Request, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let addrs = request
+        .headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("x-forwarded-for"))
+        .flat_map(|(_, values)| values.iter())
+        .flat_map(|value| value.split(','))
+        .map(|addr| addr.trim().parse::<IpAddr>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+
+    addrs
+        .iter()
+        .rev()
+        .find(|ip| !trusted_proxies.contains(ip))
+        .or_else(|| addrs.first())
+        .copied()
+}
+
 #[macro_export]
 macro_rules! proc_cmd {
     ($ctx: expr, $cmd: expr, $cmd_rx: ident) => {{
@@ -58,7 +193,7 @@ macro_rules! proc_cmd {
             .map_err(|e| warp::reject::custom(FhLockingError::new(e.to_string())))?
             .clone();
 
-        tx2.send($cmd)
+        fh_core::enqueue(&mut tx2, $cmd)
             .await
             .map_err(|e| warp::reject::custom(FhHttpError::new(e)))?;
 
@@ -78,7 +213,7 @@ macro_rules! db_cmd {
             .map_err(|e| warp::reject::custom(FhLockingError::new(e.to_string())))?
             .clone();
 
-        tx2.send($cmd)
+        fh_core::enqueue(&mut tx2, $cmd)
             .await
             .map_err(|e| warp::reject::custom(FhHttpError::new(e)))?;
 
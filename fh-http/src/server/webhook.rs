@@ -0,0 +1,205 @@
+//! Verification of webhook signatures, which is configured declaratively per
+//! RequestProcessor. Invalid requests are rejected before a V8 isolate is
+//! spent on them.
+use crate::server::{error::FhHttpError, AppContext};
+use chrono::{DateTime, Utc};
+use fh_core::{
+    crypto::{hmac_verify, HashAlgorithm},
+    error::ScriptError,
+    request::Request,
+    FhLockingError,
+};
+use fh_db::{
+    request_conversation::{AuditItem, ConversationStatus},
+    request_processor::{RequestProcessor, WebhookProvider, WebhookVerification},
+    ReqCmd, RequestProcessorError,
+};
+use tokio::sync::oneshot;
+use warp::{http::StatusCode, Rejection};
+
+/// Maximum age in seconds of signed timestamps (Stripe, Slack), to prevent
+/// replaying old requests.
+const TIMESTAMP_TOLERANCE_SECS: i64 = 300;
+
+/// Default signature header of the provider `generic`.
+const GENERIC_HEADER: &str = "x-signature";
+
+/// Returns the first value of the given header or fails with a reason.
+fn header_value<'a>(request: &'a Request, name: &str) -> Result<&'a str, String> {
+    request
+        .header(name)
+        .ok_or_else(|| format!("Missing header '{}'", name))
+}
+
+/// Decodes a hex encoded signature.
+fn decode_signature(value: &str) -> Result<Vec<u8>, String> {
+    hex::decode(value.trim()).map_err(|_| "Signature is not hex encoded".to_string())
+}
+
+/// Makes sure, that a signed timestamp is recent enough.
+fn check_timestamp(timestamp: &str, now: DateTime<Utc>) -> Result<(), String> {
+    let timestamp: i64 = timestamp
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid timestamp '{}'", timestamp))?;
+
+    if (now.timestamp() - timestamp).abs() > TIMESTAMP_TOLERANCE_SECS {
+        return Err("Timestamp is outside of the tolerance".to_string());
+    }
+
+    Ok(())
+}
+
+/// Checks, if one of the HMAC-SHA256 `signatures` matches `data`.
+fn check_signatures(secret: &[u8], data: &[u8], signatures: &[Vec<u8>]) -> Result<(), String> {
+    if signatures
+        .iter()
+        .any(|s| hmac_verify(HashAlgorithm::Sha256, secret, data, s))
+    {
+        Ok(())
+    } else {
+        Err("Signature does not match".to_string())
+    }
+}
+
+/// Verifies the signature of a request according to the provider's scheme.
+/// Returns the reason, if the request is not acceptable.
+pub(crate) fn verify_signature(
+    verification: &WebhookVerification,
+    secret: &[u8],
+    request: &Request,
+    now: DateTime<Utc>,
+) -> Result<(), String> {
+    let body = request.body.as_bytes();
+
+    match verification.provider {
+        WebhookProvider::Github => {
+            let header = header_value(request, "x-hub-signature-256")?;
+            let signature = header
+                .strip_prefix("sha256=")
+                .ok_or_else(|| "Signature must start with 'sha256='".to_string())?;
+
+            check_signatures(secret, body, &[decode_signature(signature)?])
+        }
+        WebhookProvider::Stripe => {
+            let header = header_value(request, "stripe-signature")?;
+            let mut timestamp = None;
+            let mut signatures = Vec::new();
+            for part in header.split(',') {
+                let mut kv = part.trim().splitn(2, '=');
+                match (kv.next(), kv.next()) {
+                    (Some("t"), Some(t)) => timestamp = Some(t),
+                    (Some("v1"), Some(s)) => signatures.push(decode_signature(s)?),
+                    _ => {}
+                }
+            }
+
+            let timestamp = timestamp.ok_or_else(|| "Missing timestamp".to_string())?;
+            check_timestamp(timestamp, now)?;
+
+            let mut data = format!("{}.", timestamp).into_bytes();
+            data.extend_from_slice(body);
+            check_signatures(secret, &data, &signatures)
+        }
+        WebhookProvider::Slack => {
+            let timestamp = header_value(request, "x-slack-request-timestamp")?;
+            check_timestamp(timestamp, now)?;
+
+            let header = header_value(request, "x-slack-signature")?;
+            let signature = header
+                .strip_prefix("v0=")
+                .ok_or_else(|| "Signature must start with 'v0='".to_string())?;
+
+            let mut data = format!("v0:{}:", timestamp).into_bytes();
+            data.extend_from_slice(body);
+            check_signatures(secret, &data, &[decode_signature(signature)?])
+        }
+        WebhookProvider::Generic => {
+            let name = verification.header.as_deref().unwrap_or(GENERIC_HEADER);
+            let header = header_value(request, name)?;
+            let signature = header.strip_prefix("sha256=").unwrap_or(header);
+
+            check_signatures(secret, body, &[decode_signature(signature)?])
+        }
+    }
+}
+
+/// Verifies the webhook signature of an incoming request, if the
+/// RequestProcessor demands it. A rejected request is recorded as a failed
+/// conversation, containing the request and the reason as error.
+pub(crate) async fn verify_webhook(
+    ctx: &AppContext,
+    processor: &RequestProcessor,
+    request: &Request,
+) -> Result<(), Rejection> {
+    let id = processor.id;
+    let verification = match &processor.webhook_verification {
+        Some(verification) => verification,
+        None => return Ok(()),
+    };
+
+    let (cmd_tx, cmd_rx) = oneshot::channel();
+    let secrets = db_cmd!(
+        ctx,
+        ReqCmd::GetProcessorSecretValues {
+            request_processor_id: id,
+            cmd_tx,
+        },
+        cmd_rx
+    );
+    let result = match secrets.get(&verification.secret) {
+        Some(secret) => verify_signature(
+            verification,
+            secret.expose().as_bytes(),
+            request,
+            Utc::now(),
+        ),
+        None => Err(format!(
+            "Secret '{}' is not defined for this RequestProcessor",
+            verification.secret
+        )),
+    };
+    let reason = match result {
+        Ok(()) => return Ok(()),
+        Err(reason) => reason,
+    };
+
+    let (cmd_tx, cmd_rx) = oneshot::channel();
+    let conversation = db_cmd!(
+        ctx,
+        ReqCmd::CreateRequestConversation {
+            request_processor_id: id,
+            parent_id: None,
+            cmd_tx,
+        },
+        cmd_rx
+    );
+
+    let error = RequestProcessorError::WebhookVerification {
+        conversation_id: conversation.id,
+        reason,
+    };
+    let items = vec![
+        AuditItem::new_request(conversation.id, 0, request.clone()),
+        AuditItem::new_error(conversation.id, ScriptError::new(error.to_string())),
+    ];
+    for item in items {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        db_cmd!(ctx, ReqCmd::CreateAuditLogEntry { item, cmd_tx }, cmd_rx);
+    }
+
+    let (cmd_tx, cmd_rx) = oneshot::channel();
+    db_cmd!(
+        ctx,
+        ReqCmd::FinishRequestConversation {
+            id: conversation.id,
+            status: ConversationStatus::Failed,
+            status_code: Some(StatusCode::UNAUTHORIZED.as_u16()),
+            error: Some(error.to_string()),
+            cmd_tx,
+        },
+        cmd_rx
+    );
+
+    Err(warp::reject::custom(FhHttpError::new(error)))
+}
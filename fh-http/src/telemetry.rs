@@ -0,0 +1,80 @@
+//! Setup of logging and tracing. Spans always get OpenTelemetry ids, so the
+//! trace context is propagated to outbound requests, but they are only
+//! exported, if an OTLP endpoint is configured.
+use crate::server::Config;
+use anyhow::Result;
+use opentelemetry::{
+    global,
+    sdk::{propagation::TraceContextPropagator, trace as sdktrace, Resource},
+    trace::TracerProvider,
+    KeyValue,
+};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// Name of this service in exported traces.
+const SERVICE_NAME: &str = "fh-http";
+
+/// Filter directives, if `RUST_LOG` is not set. The spans of these crates
+/// carry the trace context, so they have to be enabled to propagate it.
+const DEFAULT_FILTER: &str = "error,fh_http=info,fh_v8=info,fh_db=info";
+
+/// Keeps the tracing pipeline alive. Pending spans are exported, when it is
+/// dropped.
+pub(crate) enum Telemetry {
+    Otlp {
+        _uninstall: opentelemetry_otlp::Uninstall,
+    },
+    Local {
+        _provider: sdktrace::TracerProvider,
+    },
+}
+
+/// Installs the global subscriber, which writes log output according to
+/// `RUST_LOG` and records spans with OpenTelemetry.
+pub(crate) fn init(config: &Config) -> Result<Telemetry> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let trace_config = sdktrace::config().with_resource(Resource::new(vec![KeyValue::new(
+        "service.name",
+        SERVICE_NAME,
+    )]));
+    let (tracer, telemetry) = match &config.otlp_endpoint {
+        Some(endpoint) => {
+            let (tracer, uninstall) = opentelemetry_otlp::new_pipeline()
+                .with_endpoint(endpoint)
+                .with_trace_config(trace_config)
+                .install()?;
+            (
+                tracer,
+                Telemetry::Otlp {
+                    _uninstall: uninstall,
+                },
+            )
+        }
+        None => {
+            let provider = sdktrace::TracerProvider::builder()
+                .with_config(trace_config)
+                .build();
+            let tracer = provider.get_tracer(SERVICE_NAME, None);
+            (
+                tracer,
+                Telemetry::Local {
+                    _provider: provider,
+                },
+            )
+        }
+    };
+
+    let filter = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        Err(_) => EnvFilter::try_new(DEFAULT_FILTER)?,
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()?;
+
+    Ok(telemetry)
+}
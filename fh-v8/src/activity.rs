@@ -0,0 +1,54 @@
+//! Progress of the request processing manager. The manager runs its commands
+//! one after another, so it answers pings only after the running command.
+//! Its progress is shared instead, so readiness checks don't have to queue
+//! behind slow RequestProcessors.
+use std::{
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Time of the last progress of the manager, `None` while it is idle.
+#[derive(Debug, Default)]
+pub(crate) struct Activity {
+    progress: Mutex<Option<Instant>>,
+}
+
+impl Activity {
+    /// Marks the manager as busy, until the returned guard is dropped.
+    pub(crate) fn busy(&self) -> Busy<'_> {
+        self.set(Some(Instant::now()));
+        Busy(self)
+    }
+
+    /// Records progress of a busy manager, e.g. the start of the next step of
+    /// a Pipeline.
+    pub(crate) fn progressed(&self) {
+        let mut progress = self.lock();
+        if progress.is_some() {
+            *progress = Some(Instant::now());
+        }
+    }
+
+    /// Time since the last progress of a busy manager.
+    pub(crate) fn stalled_for(&self) -> Option<Duration> {
+        self.lock().map(|progress| progress.elapsed())
+    }
+
+    fn set(&self, progress: Option<Instant>) {
+        *self.lock() = progress;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<Instant>> {
+        // the state is a plain timestamp, so it can't be left inconsistent
+        self.progress.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Guard of a busy manager, see [`Activity::busy`].
+pub(crate) struct Busy<'a>(&'a Activity);
+
+impl Drop for Busy<'_> {
+    fn drop(&mut self) {
+        self.0.set(None);
+    }
+}
@@ -0,0 +1,398 @@
+//! Chaining of RequestProcessors: nested invocations via `fh.invoke` and
+//! declarative [`fh_db::pipeline::Pipeline`]s. All invoked RequestProcessors
+//! record to child conversations, so a chain forms a tree of conversations.
+use crate::{
+    create_request_conversation, run_request_processor,
+    runtime::{store_audit_item, RuntimeState},
+    ProcessingContext, MAX_INVOKE_DEPTH,
+};
+use deno_core::{error::AnyError, BufVec, OpState};
+use fh_core::{metrics::RATE_LIMITED, request::Request, response::Response, ReqSender};
+use fh_db::{
+    pipeline::{PipelineStep, StepErrorPolicy},
+    request_processor::RequestProcessor,
+    ReqCmd, RequestProcessorError,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{mpsc, Mutex, PoisonError},
+};
+use tokio::sync::oneshot;
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Arguments of the `invoke` op. The defaults of the request are filled in
+/// by `fh_prelude.js`.
+#[derive(Debug, Deserialize)]
+struct InvokeArgs {
+    processor_id: Uuid,
+    request: Request,
+    #[serde(default = "default_prelude")]
+    prelude: bool,
+}
+
+fn default_prelude() -> bool {
+    true
+}
+
+/// Represents the `invoke` function, which can be called from the JsRuntime
+/// using `Deno.core.jsonOpAsync("invoke", {processor_id, request, prelude})`.
+/// Runs another stored RequestProcessor with the given request and returns
+/// its response, like `dispatch_request` does for external services.
+///
+/// The invoked RequestProcessor records to a new conversation, whose parent
+/// is the current one. Invocations may be nested up to [`MAX_INVOKE_DEPTH`]
+/// levels, which also stops cycles. The invoked RequestProcessor is checked
+/// like one run by a client, see [`check_chained`].
+pub(crate) async fn op_invoke(
+    state: Rc<RefCell<OpState>>,
+    args: Value,
+    _bufs: BufVec,
+) -> Result<Value, AnyError> {
+    let args: InvokeArgs = serde_json::from_value(args)?;
+    let mut request = args.request;
+    request.processor_id = Some(args.processor_id);
+    request.parse();
+
+    // the state is not borrowed across awaits, so other ops can run meanwhile
+    let (ctx, tx_db, conversation_id, depth, inc, item) = {
+        let mut op_state = state.borrow_mut();
+        let ctx = op_state.borrow::<ProcessingContext>().clone();
+        let rt_state = op_state.borrow_mut::<RuntimeState>();
+        if rt_state.depth >= MAX_INVOKE_DEPTH {
+            return Err(RequestProcessorError::Custom(format!(
+                "Invocations may be nested at most {} levels deep",
+                MAX_INVOKE_DEPTH
+            ))
+            .into());
+        }
+
+        let (inc, item) = rt_state.register_request(request.clone())?;
+        (
+            ctx,
+            rt_state.tx_db.clone(),
+            rt_state.conversation_id,
+            rt_state.depth,
+            inc,
+            item,
+        )
+    };
+    store_audit_item(tx_db.clone(), item).await?;
+
+    let child =
+        create_request_conversation(tx_db.clone(), args.processor_id, Some(conversation_id))
+            .await?;
+    let response = run_nested(
+        ctx,
+        tx_db.clone(),
+        args.processor_id,
+        request,
+        args.prelude,
+        child.id,
+        depth + 1,
+    )
+    .await
+    .map_err(|e| {
+        RequestProcessorError::Custom(format!(
+            "Invocation of RequestProcessor {} failed in conversation {}: {}",
+            args.processor_id, child.id, e
+        ))
+    })?;
+
+    let item = state
+        .borrow_mut()
+        .borrow_mut::<RuntimeState>()
+        .register_response(inc, response.clone())?;
+    store_audit_item(tx_db, item).await?;
+
+    Ok(serde_json::json!(response))
+}
+
+/// Checks a RequestProcessor, which is run by another RequestProcessor or by
+/// a Pipeline, instead of the HTTP server: it must not rely on the checks of
+/// incoming requests and its invocations are rate limited like those of
+/// clients.
+pub(crate) fn check_chained(
+    ctx: &ProcessingContext,
+    processor: &RequestProcessor,
+) -> Result<(), RequestProcessorError> {
+    processor.check_chainable()?;
+
+    if let Some(limiter) = &ctx.processor_limiter {
+        if let Err(retry_after) = limiter.check(processor.id) {
+            RATE_LIMITED.with_label_values(&["processor"]).inc();
+            return Err(RequestProcessorError::QuotaExceeded(format!(
+                "Rate limit of RequestProcessor {} exceeded, retry in {} ms",
+                processor.id,
+                retry_after.as_millis()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// A nested invocation, which is run by a worker of [`NestedWorkers`].
+struct NestedRun {
+    ctx: ProcessingContext,
+    tx_db: ReqSender<ReqCmd>,
+    id: Uuid,
+    request: Request,
+    prelude: bool,
+    conversation_id: Uuid,
+    depth: usize,
+    span: tracing::Span,
+    res_tx: oneshot::Sender<Result<Response, RequestProcessorError>>,
+}
+
+/// Idle workers for nested invocations. The JsRuntime is not `Send` and the
+/// processing manager handles one command at a time, so nested invocations
+/// can neither be spawned on the current runtime nor be sent as
+/// [`crate::ProcessorCmd`]. Instead, every worker is a thread with its own
+/// runtime, which runs one invocation at a time and is reused afterwards.
+#[derive(Debug, Default)]
+pub(crate) struct NestedWorkers {
+    idle: Mutex<Vec<mpsc::Sender<NestedRun>>>,
+}
+
+impl NestedWorkers {
+    /// Takes an idle worker or starts a new one.
+    fn take(&self) -> std::io::Result<mpsc::Sender<NestedRun>> {
+        let idle = self
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+
+        match idle {
+            Some(worker) => Ok(worker),
+            None => start_worker(),
+        }
+    }
+
+    /// Keeps a worker for the next invocation. A single chain needs at most
+    /// [`MAX_INVOKE_DEPTH`] workers at once, further ones are stopped.
+    fn put_back(&self, worker: mpsc::Sender<NestedRun>) {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        if idle.len() < MAX_INVOKE_DEPTH {
+            idle.push(worker);
+        }
+    }
+}
+
+/// Starts a worker thread, which runs until its sender is dropped.
+fn start_worker() -> std::io::Result<mpsc::Sender<NestedRun>> {
+    let (tx, rx) = mpsc::channel::<NestedRun>();
+
+    std::thread::Builder::new()
+        .name("fh-invoke".to_string())
+        .spawn(move || {
+            let mut rt = match tokio::runtime::Builder::new()
+                .basic_scheduler()
+                .enable_all()
+                .build()
+            {
+                Ok(rt) => rt,
+                Err(e) => {
+                    tracing::error!("Unable to start runtime for nested invocations: {:?}", e);
+                    return;
+                }
+            };
+
+            for run in rx {
+                let res = rt.block_on(
+                    run_request_processor(
+                        run.ctx,
+                        run.tx_db,
+                        run.id,
+                        run.request,
+                        run.prelude,
+                        run.conversation_id,
+                        run.depth,
+                    )
+                    .instrument(run.span),
+                );
+
+                // the invoking code may have timed out in the meantime
+                let _ = run.res_tx.send(res);
+            }
+        })?;
+
+    Ok(tx)
+}
+
+/// Runs a RequestProcessor on a worker of [`NestedWorkers`]. If the invoking
+/// code stops waiting, e.g. due to a timeout, the worker is not reused and
+/// stops after the invocation.
+async fn run_nested(
+    ctx: ProcessingContext,
+    tx_db: ReqSender<ReqCmd>,
+    id: Uuid,
+    request: Request,
+    prelude: bool,
+    conversation_id: Uuid,
+    depth: usize,
+) -> Result<Response, RequestProcessorError> {
+    let workers = ctx.nested_workers.clone();
+    let worker = workers
+        .take()
+        .map_err(|e| RequestProcessorError::Processing(e.into()))?;
+
+    let (res_tx, res_rx) = oneshot::channel();
+    let run = NestedRun {
+        span: tracing::info_span!("nested_invocation", processor_id = %id, depth),
+        ctx,
+        tx_db,
+        id,
+        request,
+        prelude,
+        conversation_id,
+        depth,
+        res_tx,
+    };
+    worker.send(run).map_err(|_| {
+        RequestProcessorError::Processing(anyhow::Error::msg(
+            "The worker for nested invocations stopped",
+        ))
+    })?;
+
+    let res = res_rx
+        .await
+        .map_err(|e| RequestProcessorError::Processing(e.into()))?;
+    workers.put_back(worker);
+
+    res
+}
+
+/// Runs the steps of a Pipeline one after another. The response of a step is
+/// the request of the next one. A failed step is attempted again up to its
+/// number of retries, then the pipeline is aborted or the step is skipped.
+///
+/// Every run of a step records to a child conversation of the run before, so
+/// the conversation in the `FH-Conversation-Id` header of the final response
+/// is the root of the whole chain. The steps run one level deeper than the
+/// Pipeline.
+pub(crate) async fn run_pipeline(
+    ctx: ProcessingContext,
+    tx_db: ReqSender<ReqCmd>,
+    id: Uuid,
+    request: Request,
+    depth: usize,
+) -> Result<Response, RequestProcessorError> {
+    let (cmd_tx, cmd_rx) = oneshot::channel();
+    let pipeline = execute_command!(tx_db, ReqCmd::GetPipeline { id, cmd_tx }, cmd_rx);
+
+    let mut input = request;
+    let mut root = None;
+    let mut previous = None;
+    let mut output = None;
+    let mut last_error = None;
+    for step in &pipeline.steps {
+        let mut attempts = 0;
+        let result = loop {
+            let (conversation_id, result) = run_step(
+                ctx.clone(),
+                tx_db.clone(),
+                step,
+                input.clone(),
+                previous,
+                depth + 1,
+            )
+            .await;
+            if let Some(conversation_id) = conversation_id {
+                root.get_or_insert(conversation_id);
+                previous = Some(conversation_id);
+            }
+
+            attempts += 1;
+            if result.is_ok() || attempts > step.retries {
+                break result;
+            }
+        };
+
+        match result {
+            Ok(response) => {
+                input = next_request(&input, &response);
+                output = Some(response);
+            }
+            Err(e) if step.on_error == StepErrorPolicy::Continue => last_error = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut response = match (output, last_error) {
+        (Some(response), _) => response,
+        (None, Some(e)) => return Err(e),
+        (None, None) => {
+            return Err(RequestProcessorError::Custom(format!(
+                "Pipeline {} has no steps",
+                pipeline.id
+            )))
+        }
+    };
+    if let Some(root) = root {
+        response
+            .headers
+            .insert("FH-Conversation-Id".to_string(), vec![root.to_string()]);
+    }
+    response
+        .headers
+        .insert("FH-Pipeline-Id".to_string(), vec![pipeline.id.to_string()]);
+
+    Ok(response)
+}
+
+/// Runs a single attempt of a step in a new conversation. Returns the Uuid of
+/// the conversation, if it could be created.
+async fn run_step(
+    ctx: ProcessingContext,
+    tx_db: ReqSender<ReqCmd>,
+    step: &PipelineStep,
+    mut request: Request,
+    parent_id: Option<Uuid>,
+    depth: usize,
+) -> (Option<Uuid>, Result<Response, RequestProcessorError>) {
+    let id = step.request_processor_id;
+    let conversation = match create_request_conversation(tx_db.clone(), id, parent_id).await {
+        Ok(conversation) => conversation,
+        Err(e) => return (None, Err(e)),
+    };
+
+    request.processor_id = Some(id);
+    let res = run_request_processor(
+        ctx,
+        tx_db,
+        id,
+        request,
+        step.prelude,
+        conversation.id,
+        depth,
+    )
+    .await;
+
+    (Some(conversation.id), res)
+}
+
+/// Derives the request of the next step from the response of the previous
+/// one: a `POST` with the response body. Only the content type is passed on
+/// as header, the remaining data of the incoming request, like path and
+/// query, is kept.
+fn next_request(previous: &Request, response: &Response) -> Request {
+    let content_type = response
+        .headers
+        .iter()
+        .chain(previous.headers.iter())
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .map(|(name, values)| (name.clone(), values.clone()));
+
+    let mut request = previous.clone();
+    request.method = "POST".to_string();
+    request.body = response.body.clone().unwrap_or_default();
+    request.headers = content_type.into_iter().collect();
+    request.parse();
+
+    request
+}
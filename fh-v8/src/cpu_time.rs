@@ -0,0 +1,55 @@
+//! Measurement of the CPU time, which is spent executing a RequestProcessor.
+//! The execution is a future, which is polled together with other tasks and
+//! may move between the threads of the runtime. Thus the CPU time of the
+//! polling thread is only measured while the execution itself is polled.
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// CPU time, which the current thread consumed so far.
+fn thread_cpu_time() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid, writable timespec
+    let res = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+    if res != 0 {
+        return Duration::default();
+    }
+
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Future, which sums up the CPU time spent in the polls of its inner future.
+/// Time spent waiting, e.g. for outbound requests, is not counted.
+pub(crate) struct CpuTimed<F> {
+    inner: F,
+    cpu_time: Duration,
+}
+
+/// Wraps a future to measure its CPU time, see [`CpuTimed`]. Resolves to the
+/// output of the future together with the consumed CPU time.
+pub(crate) fn cpu_timed<F: Future + Unpin>(inner: F) -> CpuTimed<F> {
+    CpuTimed {
+        inner,
+        cpu_time: Duration::default(),
+    }
+}
+
+impl<F: Future + Unpin> Future for CpuTimed<F> {
+    type Output = (F::Output, Duration);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let started = thread_cpu_time();
+        let res = Pin::new(&mut self.inner).poll(cx);
+        let spent = thread_cpu_time().checked_sub(started).unwrap_or_default();
+        self.cpu_time += spent;
+
+        let cpu_time = self.cpu_time;
+        res.map(|output| (output, cpu_time))
+    }
+}
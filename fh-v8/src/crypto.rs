@@ -0,0 +1,75 @@
+//! Ops backing `crypto.subtle` as defined in `fh_web.js`. Keys and data are
+//! passed as zero copy buffers, results are returned as JSON byte arrays.
+use deno_core::error::AnyError;
+use deno_core::OpState;
+use deno_core::ZeroCopyBuf;
+use fh_core::crypto::{self, HashAlgorithm};
+use fh_db::RequestProcessorError;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Arguments of the `crypto_digest` and `crypto_hmac_*` ops.
+#[derive(Debug, Deserialize)]
+struct CryptoArgs {
+    hash: String,
+}
+
+/// Returns the buffer at `idx` or fails with a message naming the argument.
+fn get_buf<'a>(bufs: &'a [ZeroCopyBuf], idx: usize, name: &str) -> Result<&'a [u8], AnyError> {
+    bufs.get(idx)
+        .map(|b| &b[..])
+        .ok_or(RequestProcessorError::Custom(format!("Missing buffer for '{}'", name)).into())
+}
+
+/// Represents `crypto.subtle.digest()`. Can be called from the JsRuntime
+/// using `Deno.core.jsonOpSync("crypto_digest", {hash}, data)`.
+pub(crate) fn op_crypto_digest(
+    _state: &mut OpState,
+    args: Value,
+    bufs: &mut [ZeroCopyBuf],
+) -> Result<Value, AnyError> {
+    let args: CryptoArgs = serde_json::from_value(args)?;
+    let hash: HashAlgorithm = args.hash.parse()?;
+
+    Ok(serde_json::json!(crypto::digest(
+        hash,
+        get_buf(bufs, 0, "data")?
+    )))
+}
+
+/// Represents `crypto.subtle.sign()` for HMAC keys. Can be called from the
+/// JsRuntime using `Deno.core.jsonOpSync("crypto_hmac_sign", {hash}, key, data)`.
+pub(crate) fn op_crypto_hmac_sign(
+    _state: &mut OpState,
+    args: Value,
+    bufs: &mut [ZeroCopyBuf],
+) -> Result<Value, AnyError> {
+    let args: CryptoArgs = serde_json::from_value(args)?;
+    let hash: HashAlgorithm = args.hash.parse()?;
+
+    Ok(serde_json::json!(crypto::hmac_sign(
+        hash,
+        get_buf(bufs, 0, "key")?,
+        get_buf(bufs, 1, "data")?
+    )))
+}
+
+/// Represents `crypto.subtle.verify()` for HMAC keys. Can be called from the
+/// JsRuntime using
+/// `Deno.core.jsonOpSync("crypto_hmac_verify", {hash}, key, signature, data)`.
+/// The signature is compared in constant time.
+pub(crate) fn op_crypto_hmac_verify(
+    _state: &mut OpState,
+    args: Value,
+    bufs: &mut [ZeroCopyBuf],
+) -> Result<Value, AnyError> {
+    let args: CryptoArgs = serde_json::from_value(args)?;
+    let hash: HashAlgorithm = args.hash.parse()?;
+
+    Ok(serde_json::json!(crypto::hmac_verify(
+        hash,
+        get_buf(bufs, 0, "key")?,
+        get_buf(bufs, 2, "data")?,
+        get_buf(bufs, 1, "signature")?
+    )))
+}
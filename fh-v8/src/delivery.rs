@@ -0,0 +1,412 @@
+//! Outbound HTTP delivery, which is shared by the `dispatch_request` op and
+//! the background worker retrying queued
+//! [`fh_db::outbound_delivery::OutboundDelivery`] entries.
+use crate::{record_usage, runtime::redact_secrets, ProcessingContext};
+use anyhow::{bail, Result};
+use fh_core::{
+    metrics::{
+        OUTBOUND_REQUESTS, OUTBOUND_REQUEST_DURATION, OUTBOUND_REQUEST_ERRORS, RATE_LIMITED,
+    },
+    rate_limit::RateLimiter,
+    request::Request,
+    response::Response,
+    shutdown::Shutdown,
+    telemetry::context_headers,
+    ReqSender,
+};
+use fh_db::{
+    outbound_delivery::{DeliveryStatus, OutboundDelivery},
+    processor_secret::SecretValue,
+    processor_usage::Usage,
+    request_conversation::{AuditItem, LogLevel},
+    ReqCmd, RequestProcessorError,
+};
+use reqwest::{header, Method, Url};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Maximum number of due deliveries, which are fetched at once.
+const BATCH_SIZE: i32 = 10;
+
+/// Time to wait before polling the queue again, if nothing was due.
+const IDLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Headers, which are specific to a single connection and therefore never
+/// forwarded. `host` and `content-length` are set by the client itself.
+const SKIPPED_HEADERS: &[&str] = &[
+    "connection",
+    "content-length",
+    "host",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Takes a token for the host of `url`, if outbound requests are limited.
+/// Fails, if the limit of outbound requests to this host is exceeded.
+pub(crate) fn check_outbound_rate_limit(
+    limiter: Option<&RateLimiter<String>>,
+    url: &str,
+) -> Result<()> {
+    let limiter = match limiter {
+        Some(limiter) => limiter,
+        None => return Ok(()),
+    };
+
+    let host = Url::parse(url)?.host_str().unwrap_or("").to_string();
+    if let Err(retry_after) = limiter.check(host.clone()) {
+        RATE_LIMITED.with_label_values(&["outbound"]).inc();
+        bail!(
+            "Rate limit of outbound requests to '{}' exceeded, retry in {} ms",
+            host,
+            retry_after.as_millis()
+        );
+    }
+
+    Ok(())
+}
+
+/// Sends the given request to `url` and converts the received response. The
+/// request's headers are sent, except for hop-by-hop headers. They must only
+/// contain the headers given explicitly, see
+/// [`fh_core::request::RequestSpec::headers`]. If no
+/// `accept` header is given, `application/json` is accepted. The trace context
+/// is sent as `traceparent` header.
+pub(crate) async fn send_request(url: &str, request: &Request) -> Result<Response> {
+    let mut headers = header::HeaderMap::new();
+    headers.insert(
+        header::ACCEPT,
+        header::HeaderValue::from_static("application/json"),
+    );
+    for (name, values) in &request.headers {
+        let name = header::HeaderName::from_str(name)?;
+        if SKIPPED_HEADERS.contains(&name.as_str()) {
+            continue;
+        }
+
+        headers.remove(&name);
+        for value in values {
+            headers.append(&name, header::HeaderValue::from_str(value)?);
+        }
+    }
+
+    let url = Url::parse(url)?;
+    let host = url.host_str().unwrap_or("").to_string();
+
+    // the called service continues the trace of this request
+    let span = tracing::info_span!(
+        "outbound_request",
+        method = %request.method,
+        host = %host,
+        status = tracing::field::Empty,
+    );
+    for (name, value) in context_headers(&span) {
+        headers.insert(
+            header::HeaderName::from_str(&name)?,
+            header::HeaderValue::from_str(&value)?,
+        );
+    }
+
+    let c = reqwest::Client::builder().build()?;
+    let started = Instant::now();
+    let res = c
+        .request(Method::from_str(&request.method)?, url)
+        .body(request.body.clone())
+        .headers(headers)
+        .send()
+        .instrument(span.clone())
+        .await;
+    OUTBOUND_REQUEST_DURATION
+        .with_label_values(&[&host])
+        .observe(started.elapsed().as_secs_f64());
+
+    let response = match res {
+        Ok(response) => response,
+        Err(e) => {
+            OUTBOUND_REQUEST_ERRORS.with_label_values(&[&host]).inc();
+            return Err(e.into());
+        }
+    };
+    span.record("status", &response.status().as_u16());
+    OUTBOUND_REQUESTS
+        .with_label_values(&[&host, response.status().as_str()])
+        .inc();
+
+    Response::try_from_response(response).await
+}
+
+/// Accounts an outbound request of a RequestProcessor, including failed ones.
+/// Counts the sent body and, if a response was received, its body.
+pub(crate) async fn record_outbound_usage(
+    tx_db: ReqSender<ReqCmd>,
+    request_processor_id: Uuid,
+    request: &Request,
+    result: &Result<Response>,
+) {
+    let received = match result {
+        Ok(response) => response.raw_body.as_ref().map_or(0, Vec::len),
+        Err(_) => 0,
+    };
+    let usage = Usage {
+        outbound_requests: 1,
+        outbound_bytes: (request.body.len() + received) as i64,
+        ..Usage::default()
+    };
+
+    record_usage(tx_db, request_processor_id, usage).await;
+}
+
+/// Checks, if a received response is worth another attempt. This is the case
+/// for all server errors.
+pub(crate) fn is_retryable(response: &Response) -> bool {
+    response.code >= 500
+}
+
+/// Async function which can be run e.g. by tokio which loops until shutdown
+/// and retries all due OutboundDeliveries.
+pub async fn delivery_worker(
+    tx_db: ReqSender<ReqCmd>,
+    ctx: ProcessingContext,
+    mut shutdown: Shutdown,
+) {
+    while !shutdown.is_requested() {
+        match deliver_due(&tx_db, &ctx).await {
+            Ok(n) if n > 0 => {}
+            Ok(_) => {
+                shutdown.sleep(IDLE_INTERVAL).await;
+            }
+            Err(e) => {
+                tracing::error!("Unable to process OutboundDelivery queue: {:?}", e);
+                shutdown.sleep(IDLE_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// Attempts all currently due deliveries. Returns the number of attempts. A
+/// delivery, whose attempt fails with an error, is recorded as failed attempt,
+/// so it backs off like any other failure and doesn't block the others.
+async fn deliver_due(
+    tx_db: &ReqSender<ReqCmd>,
+    ctx: &ProcessingContext,
+) -> Result<usize, RequestProcessorError> {
+    let (cmd_tx, cmd_rx) = oneshot::channel();
+    let deliveries = execute_command!(
+        tx_db,
+        ReqCmd::GetDueOutboundDeliveries {
+            limit: BATCH_SIZE,
+            cmd_tx,
+        },
+        cmd_rx
+    );
+
+    let n = deliveries.len();
+    for delivery in deliveries {
+        let id = delivery.id;
+        let error = match attempt_delivery(tx_db, ctx, delivery).await {
+            Ok(()) => continue,
+            Err(e) => e,
+        };
+
+        tracing::error!("Unable to attempt OutboundDelivery {}: {:?}", id, error);
+        if let Err(e) = record_failed_attempt(tx_db, id, error.to_string()).await {
+            tracing::error!(
+                "Unable to record attempt of OutboundDelivery {}: {:?}",
+                id,
+                e
+            );
+        }
+    }
+
+    Ok(n)
+}
+
+/// Records a failed attempt of an OutboundDelivery, which could not be sent.
+async fn record_failed_attempt(
+    tx_db: &ReqSender<ReqCmd>,
+    id: Uuid,
+    error: String,
+) -> Result<(), RequestProcessorError> {
+    let (cmd_tx, cmd_rx) = oneshot::channel();
+    execute_command!(
+        tx_db,
+        ReqCmd::RecordDeliveryAttempt {
+            id,
+            error: Some(error),
+            cmd_tx,
+        },
+        cmd_rx
+    );
+
+    Ok(())
+}
+
+/// Sends an OutboundDelivery once and records the attempt on the conversation,
+/// which issued the request originally. Every attempt takes a token of the
+/// outbound rate limit, a limited attempt fails like an unreachable host.
+async fn attempt_delivery(
+    tx_db: &ReqSender<ReqCmd>,
+    ctx: &ProcessingContext,
+    delivery: OutboundDelivery,
+) -> Result<(), RequestProcessorError> {
+    let (cmd_tx, cmd_rx) = oneshot::channel();
+    let secrets = execute_command!(
+        tx_db,
+        ReqCmd::GetProcessorSecretValues {
+            request_processor_id: delivery.request_processor_id,
+            cmd_tx,
+        },
+        cmd_rx
+    );
+
+    // attempts are recorded with the increment of the original request
+    let (cmd_tx, cmd_rx) = oneshot::channel();
+    let request_item = execute_command!(
+        tx_db,
+        ReqCmd::CreateAuditLogEntry {
+            item: redact_secrets(
+                &secrets,
+                AuditItem::new_request(
+                    delivery.conversation_id,
+                    delivery.inc,
+                    delivery.request.clone(),
+                ),
+            )?,
+            cmd_tx,
+        },
+        cmd_rx
+    );
+
+    let result = match check_outbound_rate_limit(ctx.outbound_limiter.as_deref(), &delivery.url) {
+        Ok(()) => {
+            let result = send_request(&delivery.url, &delivery.request).await;
+            record_outbound_usage(
+                tx_db.clone(),
+                delivery.request_processor_id,
+                &delivery.request,
+                &result,
+            )
+            .await;
+            result
+        }
+        Err(e) => Err(e),
+    };
+
+    let error = match result {
+        Ok(response) => {
+            let error = match is_retryable(&response) {
+                true => Some(format!("Received status code {}", response.code)),
+                false => None,
+            };
+
+            let (cmd_tx, cmd_rx) = oneshot::channel();
+            execute_command!(
+                tx_db,
+                ReqCmd::CreateAuditLogEntry {
+                    item: redact_secrets(
+                        &secrets,
+                        AuditItem::new_response(
+                            delivery.conversation_id,
+                            request_item.get_id(),
+                            response,
+                        ),
+                    )?,
+                    cmd_tx,
+                },
+                cmd_rx
+            );
+
+            error
+        }
+        Err(e) => Some(e.to_string()),
+    };
+
+    let (cmd_tx, cmd_rx) = oneshot::channel();
+    let delivery = execute_command!(
+        tx_db,
+        ReqCmd::RecordDeliveryAttempt {
+            id: delivery.id,
+            error,
+            cmd_tx,
+        },
+        cmd_rx
+    );
+
+    // the attempt is recorded already, so it must not be recorded as failed
+    if let Err(e) = log_delivery_attempt(tx_db, &secrets, &delivery).await {
+        tracing::error!(
+            "Unable to log attempt of OutboundDelivery {}: {:?}",
+            delivery.id,
+            e
+        );
+    }
+
+    Ok(())
+}
+
+/// Logs the state of an OutboundDelivery after an attempt to the conversation,
+/// which issued the request originally.
+async fn log_delivery_attempt(
+    tx_db: &ReqSender<ReqCmd>,
+    secrets: &HashMap<String, SecretValue>,
+    delivery: &OutboundDelivery,
+) -> Result<(), RequestProcessorError> {
+    let (cmd_tx, cmd_rx) = oneshot::channel();
+    execute_command!(
+        tx_db,
+        ReqCmd::CreateAuditLogEntry {
+            item: redact_secrets(
+                secrets,
+                AuditItem::new_log(
+                    delivery.conversation_id,
+                    delivery_log_level(delivery),
+                    delivery_log_message(delivery),
+                    None,
+                ),
+            )?,
+            cmd_tx,
+        },
+        cmd_rx
+    );
+
+    Ok(())
+}
+
+/// Log level for the state of an OutboundDelivery after an attempt.
+pub(crate) fn delivery_log_level(delivery: &OutboundDelivery) -> LogLevel {
+    match delivery.status {
+        DeliveryStatus::Delivered => LogLevel::Info,
+        DeliveryStatus::Pending => LogLevel::Warn,
+        DeliveryStatus::Dead => LogLevel::Error,
+    }
+}
+
+/// Describes the state of an OutboundDelivery after an attempt.
+pub(crate) fn delivery_log_message(delivery: &OutboundDelivery) -> String {
+    let prefix = format!(
+        "Delivery {} to {}: attempt {} of {}",
+        delivery.id, delivery.url, delivery.attempts, delivery.max_attempts
+    );
+    let error = delivery.last_error.as_deref().unwrap_or("");
+    match delivery.status {
+        DeliveryStatus::Delivered => format!("{} succeeded", prefix),
+        DeliveryStatus::Pending => format!(
+            "{} failed ({}), next attempt at {}",
+            prefix,
+            error,
+            delivery.next_attempt_at.to_rfc3339()
+        ),
+        DeliveryStatus::Dead => format!("{} failed ({}), giving up", prefix, error),
+    }
+}
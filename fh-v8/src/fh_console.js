@@ -0,0 +1,206 @@
+// Bootstrap script, which is executed in every JsRuntime before the code of
+// the request processor, regardless of the prelude. It provides the `console`
+// global, whose output ends up as `log` audit items of the conversation.
+((globalThis) => {
+    // nested objects deeper than this are abbreviated, e.g. `[Object]`
+    const MAX_DEPTH = 2;
+
+    Deno.core.ops();
+
+    function formatKey(key) {
+        return /^[A-Za-z_$][\w$]*$/.test(key) ? key : JSON.stringify(key);
+    }
+
+    function formatEntries(open, entries, close) {
+        if (entries.length === 0) {
+            return `${open}${close}`;
+        }
+
+        return `${open} ${entries.join(", ")} ${close}`;
+    }
+
+    function inspectValue(value, depth, seen) {
+        switch (typeof value) {
+            case "string":
+                return JSON.stringify(value);
+            case "undefined":
+                return "undefined";
+            case "bigint":
+                return `${value}n`;
+            case "symbol":
+                return value.toString();
+            case "function":
+                return value.name ? `[Function: ${value.name}]` : "[Function (anonymous)]";
+            case "number":
+            case "boolean":
+                return String(value);
+        }
+
+        if (value === null) {
+            return "null";
+        }
+        if (seen.includes(value)) {
+            return "[Circular]";
+        }
+        if (value instanceof Error) {
+            return value.stack || `${value.name}: ${value.message}`;
+        }
+        if (value instanceof Date) {
+            return isNaN(value) ? "Invalid Date" : value.toISOString();
+        }
+        if (value instanceof RegExp) {
+            return value.toString();
+        }
+
+        const nested = seen.concat([value]);
+        const inspectNested = (v) => inspectValue(v, depth + 1, nested);
+
+        if (Array.isArray(value)) {
+            if (depth > MAX_DEPTH) {
+                return "[Array]";
+            }
+            return formatEntries("[", value.map(inspectNested), "]");
+        }
+        if (value instanceof Map) {
+            if (depth > MAX_DEPTH) {
+                return "[Map]";
+            }
+            const entries = [...value].map(([k, v]) => `${inspectNested(k)} => ${inspectNested(v)}`);
+            return formatEntries(`Map(${value.size}) {`, entries, "}");
+        }
+        if (value instanceof Set) {
+            if (depth > MAX_DEPTH) {
+                return "[Set]";
+            }
+            return formatEntries(`Set(${value.size}) {`, [...value].map(inspectNested), "}");
+        }
+
+        const name = value.constructor && value.constructor.name;
+        if (depth > MAX_DEPTH) {
+            return `[${name || "Object"}]`;
+        }
+        const prefix = name && name !== "Object" ? `${name} ` : "";
+        const entries = Object.keys(value).map((key) => `${formatKey(key)}: ${inspectNested(value[key])}`);
+
+        return prefix + formatEntries("{", entries, "}");
+    }
+
+    // strings are printed as they are, all other values like browsers do
+    function inspect(value) {
+        return typeof value === "string" ? value : inspectValue(value, 0, []);
+    }
+
+    // supports the format specifiers %s, %d, %i, %f, %o, %O, %c and %%
+    function format(args) {
+        if (args.length === 0) {
+            return "";
+        }
+
+        const rest = args.slice(1);
+        let first = args[0];
+
+        if (typeof first === "string") {
+            first = first.replace(/%[sdifoOc%]/g, (spec) => {
+                if (spec === "%%") {
+                    return "%";
+                }
+                if (rest.length === 0) {
+                    return spec;
+                }
+
+                const arg = rest.shift();
+                switch (spec) {
+                    case "%s":
+                        return inspect(arg);
+                    case "%d":
+                        return typeof arg === "bigint" ? `${arg}n` : String(Number(arg));
+                    case "%i":
+                        return String(parseInt(arg));
+                    case "%f":
+                        return String(parseFloat(arg));
+                    case "%c":
+                        return "";
+                    default:
+                        return inspectValue(arg, 0, []);
+                }
+            });
+        } else {
+            first = inspect(first);
+        }
+
+        return [first].concat(rest.map(inspect)).join(" ");
+    }
+
+    function write(level, message) {
+        Deno.core.jsonOpSync("console", { level: level, message: message });
+        Deno.core.print(`${message}\n`, level === "warn" || level === "error");
+    }
+
+    function logWith(level) {
+        return (...args) => write(level, format(args));
+    }
+
+    const counts = new Map();
+    const timers = new Map();
+
+    const console = {
+        log: logWith("info"),
+        info: logWith("info"),
+        debug: logWith("debug"),
+        warn: logWith("warn"),
+        error: logWith("error"),
+
+        trace(...args) {
+            // drop the `Error` line and the frame of `trace` itself
+            const stack = new Error().stack.split("\n").slice(2).join("\n");
+            write("debug", `Trace: ${format(args)}\n${stack}`);
+        },
+
+        dir(value) {
+            write("info", inspect(value));
+        },
+
+        assert(condition, ...args) {
+            if (!condition) {
+                const message = args.length > 0 ? `Assertion failed: ${format(args)}` : "Assertion failed";
+                write("error", message);
+            }
+        },
+
+        count(label = "default") {
+            const count = (counts.get(label) || 0) + 1;
+            counts.set(label, count);
+            write("info", `${label}: ${count}`);
+        },
+
+        countReset(label = "default") {
+            counts.delete(label);
+        },
+
+        time(label = "default") {
+            timers.set(label, Date.now());
+        },
+
+        timeLog(label = "default", ...args) {
+            if (!timers.has(label)) {
+                write("warn", `Timer '${label}' does not exist`);
+                return;
+            }
+
+            const elapsed = Date.now() - timers.get(label);
+            write("info", [`${label}: ${elapsed}ms`].concat(args.map(inspect)).join(" "));
+        },
+
+        timeEnd(label = "default") {
+            console.timeLog(label);
+            timers.delete(label);
+        }
+    };
+
+    Object.defineProperty(globalThis, "console", {
+        value: console,
+        writable: true,
+        enumerable: false,
+        configurable: true
+    });
+})(this);
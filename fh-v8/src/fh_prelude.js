@@ -1,30 +1,100 @@
+class FhKv {
+    async get(key) {
+        return await Deno.core.jsonOpAsync("kv_get", { key: key });
+    };
+
+    async set(key, value, options = {}) {
+        // wrap everything so we can unpack it in rust
+        const spec = {
+            "key": key,
+            "value": value === undefined ? null : value,
+            "ttl": options.ttl
+        };
+
+        return await Deno.core.jsonOpAsync("kv_set", spec);
+    };
+
+    async delete(key) {
+        return await Deno.core.jsonOpAsync("kv_delete", { key: key });
+    };
+
+    async list(prefix) {
+        return await Deno.core.jsonOpAsync("kv_list", { prefix: prefix });
+    };
+}
+
+async function fhLog(level, data, fields) {
+    // wrap everything so we can unpack it in rust in a structured way
+    const spec = {
+        level: level,
+        data: data,
+        fields: fields === undefined ? null : fields
+    };
+
+    await Deno.core.jsonOpAsync("fh_log", spec);
+    Deno.core.print(`${data}\n`);
+}
+
+// `fh.log(data, fields)` logs with level info, the methods `debug`, `info`,
+// `warn` and `error` log with the respective level.
+function createFhLog() {
+    const log = async (data, fields) => await fhLog("info", data, fields);
+    log.debug = async (data, fields) => await fhLog("debug", data, fields);
+    log.info = async (data, fields) => await fhLog("info", data, fields);
+    log.warn = async (data, fields) => await fhLog("warn", data, fields);
+    log.error = async (data, fields) => await fhLog("error", data, fields);
+
+    return log;
+}
+
 class Fh {
     constructor() {
         Deno.core.ops();
+        this.kv = new FhKv();
+        this.log = createFhLog();
+        this.config = Deno.core.jsonOpSync("get_config", []);
     };
 
-    async log(data) {
-        // if (typeof data !== 'string') {
-        // TODO: error handling
-        // }
-
-        // wrap everything so we can unpack it in rust in a structured way
+    async dispatch_request(url, request, options = {}) {
+        // wrap everything so we can unpack it in rust
         const spec = {
-            data: data
+            "url": url,
+            "request": request,
+            "headers": options.headers,
+            "retry": options.retry
         };
 
-        await Deno.core.jsonOpAsync("fh_log", spec);
-        Deno.core.print(`${data}\n`);
+        return await Deno.core.jsonOpAsync("dispatch_request", spec);
     };
 
-    async dispatch_request(url, request) {
+    // runs another stored processor and returns its response
+    async invoke(processor_id, request = {}, options = {}) {
+        const headers = { ...(request.headers || {}) };
+        let body = request.body === undefined ? "" : request.body;
+        if (typeof body !== "string") {
+            body = JSON.stringify(body);
+            const hasContentType = Object.keys(headers)
+                .some((name) => name.toLowerCase() === "content-type");
+            if (!hasContentType) {
+                headers["content-type"] = ["application/json"];
+            }
+        }
+
         // wrap everything so we can unpack it in rust
         const spec = {
-            "url": url,
-            "request": request
+            "processor_id": processor_id,
+            "request": {
+                "method": request.method || "POST",
+                "path": request.path || "/",
+                "query": request.query === undefined ? null : request.query,
+                "version": request.version || "HTTP/1.1",
+                "headers": headers,
+                "body": body
+            },
+            "prelude": options.prelude === undefined ? true : options.prelude
         };
 
-        return await Deno.core.jsonOpAsync("dispatch_request", spec);
+        return await Deno.core.jsonOpAsync("invoke", spec);
     };
 
     async respond_with(response) {
@@ -34,6 +104,10 @@ class Fh {
     get_request() {
         return Deno.core.jsonOpSync("get_request", []);
     };
+
+    secret(name) {
+        return Deno.core.jsonOpSync("get_secret", { name: name });
+    };
 }
 
 async function prelude() {
@@ -0,0 +1,683 @@
+// Bootstrap script, which is executed in every JsRuntime after `fh_console.js`.
+// It provides a subset of the web platform APIs, so that common libraries run
+// unchanged: `fetch`, `Headers`, `Response`, `URL`, `URLSearchParams`,
+// `TextEncoder`, `TextDecoder`, `atob`, `btoa`, timers and `crypto` including
+// digests and HMAC of `crypto.subtle`.
+((globalThis) => {
+    function define(name, value) {
+        Object.defineProperty(globalThis, name, {
+            value: value,
+            writable: true,
+            enumerable: false,
+            configurable: true
+        });
+    }
+
+    // ---------------------------------------------------------------------
+    // URLSearchParams & URL
+    // ---------------------------------------------------------------------
+
+    function encodeForm(s) {
+        return encodeURIComponent(s).replace(/%20/g, "+");
+    }
+
+    function decodeForm(s) {
+        return decodeURIComponent(s.replace(/\+/g, " "));
+    }
+
+    class URLSearchParams {
+        constructor(init = "") {
+            this._list = [];
+            this._url = null;
+
+            if (typeof init === "string") {
+                this._parse(init);
+            } else if (init instanceof URLSearchParams) {
+                this._list = init._list.map(([k, v]) => [k, v]);
+            } else if (init !== null && typeof init === "object") {
+                const pairs = typeof init[Symbol.iterator] === "function" ? init : Object.entries(init);
+                for (const [name, value] of pairs) {
+                    this._list.push([String(name), String(value)]);
+                }
+            }
+        };
+
+        _parse(query) {
+            this._list = [];
+            for (const part of query.replace(/^\?/, "").split("&")) {
+                if (part === "") {
+                    continue;
+                }
+                const idx = part.indexOf("=");
+                const name = idx === -1 ? part : part.slice(0, idx);
+                const value = idx === -1 ? "" : part.slice(idx + 1);
+                this._list.push([decodeForm(name), decodeForm(value)]);
+            }
+        };
+
+        // propagates changes to the URL, which created these params
+        _update() {
+            if (this._url !== null) {
+                const query = this.toString();
+                this._url._setParts({ search: query === "" ? "" : `?${query}` }, false);
+            }
+        };
+
+        append(name, value) {
+            this._list.push([String(name), String(value)]);
+            this._update();
+        };
+
+        delete(name) {
+            this._list = this._list.filter(([k]) => k !== name);
+            this._update();
+        };
+
+        get(name) {
+            const entry = this._list.find(([k]) => k === name);
+            return entry === undefined ? null : entry[1];
+        };
+
+        getAll(name) {
+            return this._list.filter(([k]) => k === name).map(([, v]) => v);
+        };
+
+        has(name) {
+            return this._list.some(([k]) => k === name);
+        };
+
+        set(name, value) {
+            const idx = this._list.findIndex(([k]) => k === name);
+            if (idx === -1) {
+                this._list.push([String(name), String(value)]);
+            } else {
+                this._list[idx][1] = String(value);
+                this._list = this._list.filter(([k], i) => k !== name || i <= idx);
+            }
+            this._update();
+        };
+
+        sort() {
+            this._list.sort(([a], [b]) => (a < b ? -1 : a > b ? 1 : 0));
+            this._update();
+        };
+
+        forEach(callback, thisArg) {
+            for (const [name, value] of this._list) {
+                callback.call(thisArg, value, name, this);
+            }
+        };
+
+        *entries() {
+            for (const [name, value] of this._list) {
+                yield [name, value];
+            }
+        };
+
+        *keys() {
+            for (const [name] of this._list) {
+                yield name;
+            }
+        };
+
+        *values() {
+            for (const [, value] of this._list) {
+                yield value;
+            }
+        };
+
+        [Symbol.iterator]() {
+            return this.entries();
+        };
+
+        toString() {
+            return this._list.map(([k, v]) => `${encodeForm(k)}=${encodeForm(v)}`).join("&");
+        };
+    }
+
+    // parsing is done by the `url_parse` op, the parts are cached in `_parts`
+    class URL {
+        constructor(url, base) {
+            const args = { href: String(url) };
+            if (base !== undefined) {
+                args.base = String(base);
+            }
+
+            try {
+                this._parts = Deno.core.jsonOpSync("url_parse", args);
+            } catch (e) {
+                throw new TypeError(`Invalid URL: ${args.href}`);
+            }
+            this._searchParams = new URLSearchParams(this._parts.search);
+            this._searchParams._url = this;
+        };
+
+        // rebuilds and parses the href after a setter was called
+        _setParts(parts, updateSearchParams = true) {
+            const p = Object.assign({}, this._parts, parts);
+            const userinfo = p.username !== ""
+                ? `${p.username}${p.password !== "" ? `:${p.password}` : ""}@`
+                : "";
+            const authority = p.host !== "" ? `//${userinfo}${p.host}` : "";
+            const search = p.search === "" || p.search.startsWith("?") ? p.search : `?${p.search}`;
+            const hash = p.hash === "" || p.hash.startsWith("#") ? p.hash : `#${p.hash}`;
+
+            this._parts = Deno.core.jsonOpSync("url_parse", {
+                href: `${p.protocol}${authority}${p.pathname}${search}${hash}`
+            });
+            if (updateSearchParams) {
+                this._searchParams._parse(this._parts.search);
+            }
+        };
+
+        get href() { return this._parts.href; };
+        set href(value) {
+            this._parts = new URL(value)._parts;
+            this._searchParams._parse(this._parts.search);
+        };
+
+        get origin() { return this._parts.origin; };
+
+        get protocol() { return this._parts.protocol; };
+        set protocol(value) { this._setParts({ protocol: value.endsWith(":") ? value : `${value}:` }); };
+
+        get username() { return this._parts.username; };
+        set username(value) { this._setParts({ username: value }); };
+
+        get password() { return this._parts.password; };
+        set password(value) { this._setParts({ password: value }); };
+
+        get host() { return this._parts.host; };
+        set host(value) { this._setParts({ host: value }); };
+
+        get hostname() { return this._parts.hostname; };
+        set hostname(value) {
+            const port = this._parts.port;
+            this._setParts({ host: port === "" ? value : `${value}:${port}` });
+        };
+
+        get port() { return this._parts.port; };
+        set port(value) {
+            const port = String(value);
+            this._setParts({ host: port === "" ? this._parts.hostname : `${this._parts.hostname}:${port}` });
+        };
+
+        get pathname() { return this._parts.pathname; };
+        set pathname(value) { this._setParts({ pathname: value.startsWith("/") ? value : `/${value}` }); };
+
+        get search() { return this._parts.search; };
+        set search(value) { this._setParts({ search: value }); };
+
+        get hash() { return this._parts.hash; };
+        set hash(value) { this._setParts({ hash: value }); };
+
+        get searchParams() { return this._searchParams; };
+
+        toString() {
+            return this.href;
+        };
+
+        toJSON() {
+            return this.href;
+        };
+    }
+
+    // ---------------------------------------------------------------------
+    // Encoding
+    // ---------------------------------------------------------------------
+
+    function toUint8Array(input) {
+        if (input instanceof Uint8Array) {
+            return input;
+        }
+        if (input instanceof ArrayBuffer) {
+            return new Uint8Array(input);
+        }
+        if (ArrayBuffer.isView(input)) {
+            return new Uint8Array(input.buffer, input.byteOffset, input.byteLength);
+        }
+
+        throw new TypeError("Expected an ArrayBuffer or ArrayBufferView");
+    }
+
+    class TextEncoder {
+        get encoding() { return "utf-8"; };
+
+        encode(input = "") {
+            return Deno.core.encode(String(input));
+        };
+    }
+
+    class TextDecoder {
+        constructor(label = "utf-8", options = {}) {
+            if (!["utf-8", "utf8", "unicode-1-1-utf-8"].includes(label.toLowerCase())) {
+                throw new RangeError(`The encoding '${label}' is not supported`);
+            }
+            this.fatal = Boolean(options.fatal);
+            this.ignoreBOM = Boolean(options.ignoreBOM);
+        };
+
+        get encoding() { return "utf-8"; };
+
+        decode(input) {
+            if (input === undefined) {
+                return "";
+            }
+
+            let bytes = toUint8Array(input);
+            if (!this.ignoreBOM && bytes[0] === 0xef && bytes[1] === 0xbb && bytes[2] === 0xbf) {
+                bytes = bytes.subarray(3);
+            }
+
+            return Deno.core.decode(bytes);
+        };
+    }
+
+    const BASE64 = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    function btoa(data) {
+        const s = String(data);
+        let out = "";
+
+        for (let i = 0; i < s.length; i += 3) {
+            const bytes = [s.charCodeAt(i), s.charCodeAt(i + 1), s.charCodeAt(i + 2)];
+            if (bytes.some((b) => b > 0xff)) {
+                throw new Error("btoa: the string contains characters outside of the Latin1 range");
+            }
+
+            const n = (bytes[0] << 16) | ((bytes[1] || 0) << 8) | (bytes[2] || 0);
+            out += BASE64[(n >> 18) & 63] + BASE64[(n >> 12) & 63];
+            out += i + 1 < s.length ? BASE64[(n >> 6) & 63] : "=";
+            out += i + 2 < s.length ? BASE64[n & 63] : "=";
+        }
+
+        return out;
+    }
+
+    function atob(data) {
+        const s = String(data).replace(/[\t\n\f\r ]/g, "").replace(/={1,2}$/, "");
+        if (s.length % 4 === 1 || /[^A-Za-z0-9+/]/.test(s)) {
+            throw new Error("atob: the string is not correctly encoded");
+        }
+
+        let out = "";
+        let buffer = 0;
+        let bits = 0;
+        for (const c of s) {
+            buffer = (buffer << 6) | BASE64.indexOf(c);
+            bits += 6;
+            if (bits >= 8) {
+                bits -= 8;
+                out += String.fromCharCode((buffer >> bits) & 0xff);
+            }
+        }
+
+        return out;
+    }
+
+    // ---------------------------------------------------------------------
+    // Timers
+    // ---------------------------------------------------------------------
+
+    let nextTimerId = 1;
+    const activeTimers = new Set();
+
+    function startTimer(id, callback, delay) {
+        Deno.core.jsonOpAsync("timer_start", { id: id, delay: Math.max(0, Number(delay) || 0) })
+            .then((fired) => {
+                if (fired && activeTimers.has(id)) {
+                    callback();
+                }
+            });
+    }
+
+    function setTimeout(callback, delay = 0, ...args) {
+        const id = nextTimerId++;
+        activeTimers.add(id);
+        startTimer(id, () => {
+            activeTimers.delete(id);
+            callback(...args);
+        }, delay);
+        return id;
+    }
+
+    function setInterval(callback, delay = 0, ...args) {
+        const id = nextTimerId++;
+        const tick = () => {
+            startTimer(id, tick, delay);
+            callback(...args);
+        };
+
+        activeTimers.add(id);
+        startTimer(id, tick, delay);
+        return id;
+    }
+
+    function clearTimer(id) {
+        if (activeTimers.delete(id)) {
+            Deno.core.jsonOpSync("timer_cancel", { id: id });
+        }
+    }
+
+    // ---------------------------------------------------------------------
+    // Crypto
+    // ---------------------------------------------------------------------
+
+    const HASH_ALGORITHMS = ["SHA-1", "SHA-256", "SHA-384", "SHA-512"];
+
+    // the raw key material is kept out of reach of the processor's code
+    const keyMaterial = new WeakMap();
+
+    function cryptoError(name, message) {
+        const error = new Error(message);
+        error.name = name;
+        return error;
+    }
+
+    function normalizeAlgorithm(algorithm) {
+        const alg = typeof algorithm === "string" ? { name: algorithm } : Object.assign({}, algorithm);
+        alg.name = String(alg.name).toUpperCase();
+        if (alg.hash !== undefined) {
+            alg.hash = normalizeAlgorithm(alg.hash);
+        }
+        return alg;
+    }
+
+    function hashName(algorithm) {
+        if (!HASH_ALGORITHMS.includes(algorithm.name)) {
+            throw cryptoError("NotSupportedError", `Unsupported hash algorithm '${algorithm.name}'`);
+        }
+        return algorithm.name;
+    }
+
+    function bytesToArrayBuffer(bytes) {
+        return new Uint8Array(bytes).buffer;
+    }
+
+    class CryptoKey {
+        constructor(algorithm, extractable, usages, raw) {
+            this.type = "secret";
+            this.algorithm = algorithm;
+            this.extractable = extractable;
+            this.usages = usages;
+            keyMaterial.set(this, raw);
+        };
+    }
+
+    function checkHmacKey(algorithm, key, usage) {
+        if (normalizeAlgorithm(algorithm).name !== "HMAC") {
+            throw cryptoError("NotSupportedError", "Only HMAC is supported");
+        }
+        if (!keyMaterial.has(key) || !key.usages.includes(usage)) {
+            throw cryptoError("InvalidAccessError", `The key does not support '${usage}'`);
+        }
+    }
+
+    // a subset of the WebCrypto API: digests and HMAC
+    const subtle = {
+        async digest(algorithm, data) {
+            const hash = hashName(normalizeAlgorithm(algorithm));
+            return bytesToArrayBuffer(Deno.core.jsonOpSync("crypto_digest", { hash: hash }, toUint8Array(data)));
+        },
+
+        async importKey(format, keyData, algorithm, extractable, usages) {
+            const alg = normalizeAlgorithm(algorithm);
+            if (alg.name !== "HMAC") {
+                throw cryptoError("NotSupportedError", "Only HMAC keys are supported");
+            }
+            if (format !== "raw") {
+                throw cryptoError("NotSupportedError", `Unsupported key format '${format}'`);
+            }
+            if (alg.hash === undefined) {
+                throw new TypeError("HMAC keys require a hash algorithm");
+            }
+
+            // copy the key, so later changes of `keyData` don't affect it
+            const raw = new Uint8Array(toUint8Array(keyData));
+            const keyAlgorithm = { name: "HMAC", hash: { name: hashName(alg.hash) }, length: raw.length * 8 };
+
+            return new CryptoKey(keyAlgorithm, Boolean(extractable), Array.from(usages), raw);
+        },
+
+        async exportKey(format, key) {
+            if (format !== "raw") {
+                throw cryptoError("NotSupportedError", `Unsupported key format '${format}'`);
+            }
+            if (!keyMaterial.has(key) || !key.extractable) {
+                throw cryptoError("InvalidAccessError", "The key is not extractable");
+            }
+
+            return bytesToArrayBuffer(keyMaterial.get(key));
+        },
+
+        async sign(algorithm, key, data) {
+            checkHmacKey(algorithm, key, "sign");
+            const signature = Deno.core.jsonOpSync(
+                "crypto_hmac_sign",
+                { hash: key.algorithm.hash.name },
+                keyMaterial.get(key),
+                toUint8Array(data)
+            );
+
+            return bytesToArrayBuffer(signature);
+        },
+
+        async verify(algorithm, key, signature, data) {
+            checkHmacKey(algorithm, key, "verify");
+            return Deno.core.jsonOpSync(
+                "crypto_hmac_verify",
+                { hash: key.algorithm.hash.name },
+                keyMaterial.get(key),
+                toUint8Array(signature),
+                toUint8Array(data)
+            );
+        }
+    };
+
+    const crypto = {
+        randomUUID() {
+            return Deno.core.jsonOpSync("random_uuid", {});
+        },
+
+        getRandomValues(array) {
+            if (!ArrayBuffer.isView(array) || array instanceof Float32Array || array instanceof Float64Array) {
+                throw new TypeError("getRandomValues expects an integer TypedArray");
+            }
+
+            Deno.core.jsonOpSync("get_random_values", {}, toUint8Array(array));
+            return array;
+        },
+
+        subtle: subtle
+    };
+
+    // ---------------------------------------------------------------------
+    // Fetch
+    // ---------------------------------------------------------------------
+
+    class Headers {
+        constructor(init = {}) {
+            this._map = new Map();
+
+            if (init instanceof Headers) {
+                init.forEach((value, name) => this.append(name, value));
+            } else if (typeof init[Symbol.iterator] === "function") {
+                for (const [name, value] of init) {
+                    this.append(name, value);
+                }
+            } else {
+                // values may be arrays, like the headers of `fh` requests
+                for (const [name, value] of Object.entries(init)) {
+                    for (const v of Array.isArray(value) ? value : [value]) {
+                        this.append(name, v);
+                    }
+                }
+            }
+        };
+
+        append(name, value) {
+            const key = String(name).toLowerCase();
+            this._map.set(key, (this._map.get(key) || []).concat([String(value)]));
+        };
+
+        delete(name) {
+            this._map.delete(String(name).toLowerCase());
+        };
+
+        get(name) {
+            const values = this._map.get(String(name).toLowerCase());
+            return values === undefined ? null : values.join(", ");
+        };
+
+        has(name) {
+            return this._map.has(String(name).toLowerCase());
+        };
+
+        set(name, value) {
+            this._map.set(String(name).toLowerCase(), [String(value)]);
+        };
+
+        forEach(callback, thisArg) {
+            for (const [name, value] of this.entries()) {
+                callback.call(thisArg, value, name, this);
+            }
+        };
+
+        *entries() {
+            for (const name of [...this._map.keys()].sort()) {
+                yield [name, this.get(name)];
+            }
+        };
+
+        *keys() {
+            for (const [name] of this.entries()) {
+                yield name;
+            }
+        };
+
+        *values() {
+            for (const [, value] of this.entries()) {
+                yield value;
+            }
+        };
+
+        [Symbol.iterator]() {
+            return this.entries();
+        };
+
+        // converts to the representation of `fh` requests: `{name: [values]}`
+        _toObject() {
+            const obj = {};
+            for (const [name, values] of this._map) {
+                obj[name] = values.slice();
+            }
+            return obj;
+        };
+    }
+
+    // the body is kept as bytes and only decoded by `text()` and `json()`
+    class Response {
+        constructor(body = null, init = {}) {
+            if (body === null || body === undefined) {
+                this._body = new Uint8Array(0);
+            } else if (body instanceof ArrayBuffer || ArrayBuffer.isView(body)) {
+                this._body = new Uint8Array(toUint8Array(body));
+            } else {
+                this._body = Deno.core.encode(String(body));
+            }
+            this.status = init.status === undefined ? 200 : init.status;
+            this.statusText = init.statusText || "";
+            this.headers = new Headers(init.headers || {});
+            this.url = init.url || "";
+            this.bodyUsed = false;
+        };
+
+        get ok() {
+            return this.status >= 200 && this.status < 300;
+        };
+
+        _consume() {
+            if (this.bodyUsed) {
+                return Promise.reject(new TypeError("Body has already been consumed"));
+            }
+            this.bodyUsed = true;
+            return Promise.resolve(this._body);
+        };
+
+        async text() {
+            return new TextDecoder().decode(await this._consume());
+        };
+
+        async json() {
+            return JSON.parse(await this.text());
+        };
+
+        async arrayBuffer() {
+            const bytes = await this._consume();
+            return bytes.buffer.slice(bytes.byteOffset, bytes.byteOffset + bytes.byteLength);
+        };
+    }
+
+    // converts the body of `fetch` to a string and sets the matching content type
+    function prepareBody(body, headers) {
+        if (body === undefined || body === null) {
+            return "";
+        }
+        if (body instanceof URLSearchParams) {
+            if (!headers.has("content-type")) {
+                headers.set("content-type", "application/x-www-form-urlencoded;charset=UTF-8");
+            }
+            return body.toString();
+        }
+        if (body instanceof ArrayBuffer || ArrayBuffer.isView(body)) {
+            return Deno.core.decode(toUint8Array(body));
+        }
+
+        if (!headers.has("content-type")) {
+            headers.set("content-type", "text/plain;charset=UTF-8");
+        }
+        return String(body);
+    }
+
+    // sends the request via `dispatch_request`, so it is recorded on the
+    // conversation like requests of `fh.dispatch_request`
+    async function fetch(input, init = {}) {
+        const url = new URL(input instanceof URL ? input.href : String(input));
+        const headers = new Headers(init.headers || {});
+        const body = prepareBody(init.body, headers);
+
+        const request = {
+            method: (init.method || "GET").toUpperCase(),
+            headers: headers._toObject(),
+            body: body,
+            path: url.pathname,
+            query: url.search === "" ? null : url.search.slice(1),
+            version: "HTTP/1.1"
+        };
+
+        const spec = { url: url.href, request: request, headers: request.headers, raw: true };
+        const r = await Deno.core.jsonOpAsync("dispatch_request", spec);
+        // `body` is decoded lossily, the received bytes are passed as `raw_body`
+        const body = r.raw_body === undefined
+            ? r.body
+            : Uint8Array.from(atob(r.raw_body), (c) => c.charCodeAt(0));
+
+        return new Response(body, { status: r.code, headers: r.headers, url: url.href });
+    }
+
+    define("URL", URL);
+    define("URLSearchParams", URLSearchParams);
+    define("TextEncoder", TextEncoder);
+    define("TextDecoder", TextDecoder);
+    define("atob", atob);
+    define("btoa", btoa);
+    define("setTimeout", setTimeout);
+    define("setInterval", setInterval);
+    define("clearTimeout", clearTimer);
+    define("clearInterval", clearTimer);
+    define("crypto", crypto);
+    define("CryptoKey", CryptoKey);
+    define("Headers", Headers);
+    define("Response", Response);
+    define("fetch", fetch);
+})(this);
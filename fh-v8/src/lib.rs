@@ -1,32 +1,129 @@
 #[macro_use]
 mod util;
+mod activity;
+mod chain;
+mod cpu_time;
+mod crypto;
+mod delivery;
 mod runtime;
+mod watchdog;
+mod web;
 
-use crate::runtime::{prepare_runtime, prepare_user_code};
+use crate::cpu_time::cpu_timed;
+pub use crate::delivery::delivery_worker;
+use crate::runtime::{prepare_runtime, prepare_user_code, to_script_error};
+use crate::watchdog::Watchdog;
 use anyhow::{Error, Result};
-use fh_core::{request::Request, response::Response, ReqSender, Responder};
+use fh_core::{
+    metrics::{Queue, Queued, V8_EXECUTION_DURATION},
+    rate_limit::RateLimiter,
+    request::Request,
+    respond,
+    response::Response,
+    shutdown::Shutdown,
+    telemetry::Traced,
+    ReqReceiver, ReqSender, Responder,
+};
 use fh_db::{
-    request_conversation::RequestConversation,
+    processor_usage::Usage,
+    request_conversation::{ConversationStatus, LogLevel, RequestConversation},
     request_processor::{RequestProcessor, RequestProcessorLanguage, RequestProcessorRuntime},
     ReqCmd, RequestProcessorError,
 };
 use runtime::RuntimeState;
-use std::collections::HashMap;
-use tokio::sync::{mpsc, oneshot};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use strum_macros::AsRefStr;
+use tokio::sync::oneshot;
+use tracing::Instrument;
 use uuid::Uuid;
 
-/// Async function which can be run e.g. by tokio which loops forever and
-/// receives [`ProcessorCmd`] commands via the given Receiver.
+/// Maximum time a RequestProcessor may run, before its execution is
+/// terminated and the conversation is marked as timed out.
+pub const PROCESSING_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum nesting of `fh.invoke` calls. Also stops invocation cycles.
+pub const MAX_INVOKE_DEPTH: usize = 5;
+
+/// Cheap clonable state, which is shared by all executions of the processing
+/// manager and the background workers.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessingContext {
+    /// Limit of outbound requests per target host, if configured.
+    pub outbound_limiter: Option<Arc<RateLimiter<String>>>,
+    /// Limit of invocations per RequestProcessor, if configured. Shared with
+    /// the HTTP server, so chained invocations take from the same buckets.
+    pub processor_limiter: Option<Arc<RateLimiter<Uuid>>>,
+    /// Workers, which run the RequestProcessors invoked via `fh.invoke`.
+    nested_workers: Arc<chain::NestedWorkers>,
+    /// Progress of the processing manager, see [`Self::stalled_for`].
+    activity: Arc<activity::Activity>,
+}
+
+impl ProcessingContext {
+    pub fn new(
+        outbound_limiter: Option<Arc<RateLimiter<String>>>,
+        processor_limiter: Option<Arc<RateLimiter<Uuid>>>,
+    ) -> Self {
+        Self {
+            outbound_limiter,
+            processor_limiter,
+            nested_workers: Arc::default(),
+            activity: Arc::default(),
+        }
+    }
+
+    /// Time since the processing manager made progress, e.g. started the
+    /// execution of a RequestProcessor, or `None`, if it is idle. As every
+    /// execution is limited by [`PROCESSING_TIMEOUT`], a manager, which
+    /// stalled for longer, is stuck.
+    pub fn stalled_for(&self) -> Option<Duration> {
+        self.activity.stalled_for()
+    }
+}
+
+/// Async function which can be run e.g. by tokio which loops until shutdown
+/// and receives [`ProcessorCmd`] commands via the given Receiver. On shutdown,
+/// no further commands are accepted, but already queued ones are processed.
 pub async fn request_processing_manager(
-    rx: &mut mpsc::Receiver<ProcessorCmd>,
+    rx: &mut ReqReceiver<ProcessorCmd>,
+    ctx: ProcessingContext,
+    mut shutdown: Shutdown,
 ) -> anyhow::Result<()> {
+    loop {
+        let cmd = tokio::select! {
+            cmd = rx.recv() => cmd,
+            _ = shutdown.requested() => break,
+        };
+        match cmd {
+            Some(cmd) => process_traced_command(cmd, &ctx).await?,
+            None => break,
+        }
+    }
+
+    rx.close();
     while let Some(cmd) = rx.recv().await {
-        process_command(cmd).await?;
+        process_traced_command(cmd, &ctx).await?;
     }
 
     Ok(())
 }
 
+/// Processes a received command within a child span of its sender's span.
+async fn process_traced_command(cmd: Traced<ProcessorCmd>, ctx: &ProcessingContext) -> Result<()> {
+    Queue::Processor.dequeued();
+    let span =
+        tracing::info_span!(parent: &cmd.span, "processor_command", command = cmd.inner.as_ref());
+    let _busy = ctx.activity.busy();
+
+    process_command(cmd.into_inner(), ctx)
+        .instrument(span)
+        .await
+}
+
 /// Central Command Enum, which contains all Commands to be sent to the `fh_v8`
 /// crate. A ProcessorCmd is received over a [`tokio::sync::mpsc`] channel and handled
 /// in the [`crate::request_processing_manager`] function.
@@ -34,7 +131,7 @@ pub async fn request_processing_manager(
 /// Each variant of the ProcessorCmd responds data back using a [`Responder`] type
 /// which, by convention is given by the variant field `cmd_tx`. The Responder
 /// is the transmitter of a [`tokio::sync::oneshot`] channel.
-#[derive(Debug)]
+#[derive(Debug, AsRefStr)]
 pub enum ProcessorCmd {
     Http {
         request: Request,
@@ -47,12 +144,29 @@ pub enum ProcessorCmd {
         cmd_tx: Responder<Result<Response, RequestProcessorError>>,
         tx_db: ReqSender<ReqCmd>,
         prelude: bool,
+        /// Existing conversation to record to, e.g. for queued asynchronous
+        /// invocations. If `None`, a new conversation is created.
+        conversation_id: Option<Uuid>,
     },
+    /// Runs the steps of a [`fh_db::pipeline::Pipeline`] one after another.
+    RunPipeline {
+        id: Uuid,
+        request: Request,
+        cmd_tx: Responder<Result<Response, RequestProcessorError>>,
+        tx_db: ReqSender<ReqCmd>,
+    },
+    /// Answered, once all previously queued commands are processed. Used for
+    /// readiness checks.
+    Ping { cmd_tx: Responder<()> },
+}
+
+impl Queued for ProcessorCmd {
+    const QUEUE: Queue = Queue::Processor;
 }
 
 /// Actual `ProcessorCmd` command processor which matches the given variant and
 /// calls the underlying functions.
-async fn process_command(cmd: ProcessorCmd) -> Result<()> {
+async fn process_command(cmd: ProcessorCmd, ctx: &ProcessingContext) -> Result<()> {
     match cmd {
         ProcessorCmd::Http {
             request: req,
@@ -67,49 +181,48 @@ async fn process_command(cmd: ProcessorCmd) -> Result<()> {
                     language: RequestProcessorLanguage::Javascript,
                     runtime: RequestProcessorRuntime::V8,
                     code: prepare_user_code(include_str!("flow_heater.js"), true),
+                    config: Default::default(),
+                    min_log_level: LogLevel::Debug,
+                    webhook_verification: None,
+                    quota: None,
+                    cors: None,
                 },
             )
             .await;
 
             let req_proc = match req_proc_res {
                 Err(err) => {
-                    cmd_tx.send(Err(err)).map_err(|e| {
-                        Error::msg(format!(
-                            "Unable to send Response to server handler: {:?}",
-                            e
-                        ))
-                    })?;
+                    respond(cmd_tx, Err(err));
 
                     return Ok(());
                 }
                 Ok(procc) => procc,
             };
 
-            let conversation_res = create_request_conversation(tx_db.clone(), req_proc.id).await;
+            let conversation_res =
+                create_request_conversation(tx_db.clone(), req_proc.id, None).await;
             let conversation_id = match conversation_res {
                 Err(err) => {
-                    cmd_tx.send(Err(err)).map_err(|e| {
-                        Error::msg(format!(
-                            "Unable to send Response to server handler: {:?}",
-                            e
-                        ))
-                    })?;
+                    respond(cmd_tx, Err(err));
 
                     return Ok(());
                 }
                 Ok(conv) => conv.id,
             };
 
-            let res = process_request(tx_db.clone(), req, conversation_id, req_proc.code)
-                .await
-                .map_err(RequestProcessorError::Processing);
+            let res = process_request(
+                ctx.clone(),
+                tx_db.clone(),
+                req,
+                conversation_id,
+                req_proc,
+                0,
+            )
+            .await
+            .map_err(to_processing_error);
+            let res = finish_request_conversation(tx_db.clone(), conversation_id, res).await;
 
-            cmd_tx.send(res).map_err(|e| {
-                Error::msg(format!(
-                    "Unable to send Response to server handler: {:?}",
-                    e
-                ))
-            })?;
+            respond(cmd_tx, res);
         }
         ProcessorCmd::RunRequestProcessor {
             id,
@@ -117,59 +230,140 @@ async fn process_command(cmd: ProcessorCmd) -> Result<()> {
             cmd_tx,
             tx_db,
             prelude,
+            conversation_id,
         } => {
-            let conversation_res = create_request_conversation(tx_db.clone(), id).await;
-            let conversation_id = match conversation_res {
-                Err(err) => {
-                    cmd_tx.send(Err(err)).map_err(|e| {
-                        Error::msg(format!(
-                            "Unable to send Response to server handler: {:?}",
-                            e
-                        ))
-                    })?;
-
-                    return Ok(());
+            let conversation_res = match conversation_id {
+                Some(conversation_id) => Ok(conversation_id),
+                None => create_request_conversation(tx_db.clone(), id, None)
+                    .await
+                    .map(|conv| conv.id),
+            };
+            let r = match conversation_res {
+                Ok(conversation_id) => {
+                    run_request_processor(
+                        ctx.clone(),
+                        tx_db,
+                        id,
+                        request,
+                        prelude,
+                        conversation_id,
+                        0,
+                    )
+                    .await
                 }
-                Ok(conv) => conv.id,
+                Err(err) => Err(err),
             };
 
-            let req_proc_res = get_request_processor(tx_db.clone(), id).await;
+            respond(cmd_tx, r);
+        }
+        ProcessorCmd::RunPipeline {
+            id,
+            request,
+            cmd_tx,
+            tx_db,
+        } => {
+            // pipelines are only run by clients, so they are not nested
+            let r = chain::run_pipeline(ctx.clone(), tx_db, id, request, 0).await;
 
-            let request_processor = match req_proc_res {
-                Err(err) => {
-                    cmd_tx.send(Err(err)).map_err(|e| {
-                        Error::msg(format!(
-                            "Unable to send Response to server handler: {:?}",
-                            e
-                        ))
-                    })?;
+            respond(cmd_tx, r);
+        }
+        ProcessorCmd::Ping { cmd_tx } => {
+            respond(cmd_tx, ());
+        }
+    }
 
-                    return Ok(());
-                }
-                Ok(req_proc) => req_proc,
-            };
+    Ok(())
+}
+
+/// Runs a stored RequestProcessor within the given, already created
+/// conversation and records the outcome on it. `depth` is the number of
+/// `fh.invoke` calls and Pipelines, which led to this invocation.
+pub(crate) async fn run_request_processor(
+    ctx: ProcessingContext,
+    tx_db: ReqSender<ReqCmd>,
+    id: Uuid,
+    request: Request,
+    prelude: bool,
+    conversation_id: Uuid,
+    depth: usize,
+) -> Result<Response, RequestProcessorError> {
+    let res = async {
+        let mut request_processor = get_request_processor(tx_db.clone(), id).await?;
+        // the HTTP server only checks the invocations by clients
+        if depth > 0 {
+            chain::check_chained(&ctx, &request_processor)?;
+        }
+        check_usage_quota(tx_db.clone(), &request_processor).await?;
+        request_processor.code = prepare_user_code(&request_processor.code, prelude);
 
-            if prelude {}
+        process_request(
+            ctx,
+            tx_db.clone(),
+            request,
+            conversation_id,
+            request_processor,
+            depth,
+        )
+        .await
+        .map_err(to_processing_error)
+    }
+    .await;
 
-            let r = process_request(
-                tx_db.clone(),
-                request,
-                conversation_id,
-                prepare_user_code(&request_processor.code, prelude),
-            )
-            .await
-            .map_err(RequestProcessorError::Processing);
-
-            cmd_tx.send(r).map_err(|e| {
-                Error::msg(format!(
-                    "Unable to send Response to server handler: {:?}",
-                    e
-                ))
-            })?;
+    finish_request_conversation(tx_db, conversation_id, res).await
+}
+
+/// Converts the error of [`process_request`] for the server handler. Timeouts
+/// and script errors are kept, all other errors are wrapped as processing
+/// errors.
+fn to_processing_error(e: Error) -> RequestProcessorError {
+    match e.downcast::<RequestProcessorError>() {
+        Ok(e @ RequestProcessorError::Timeout(_)) => e,
+        Ok(e @ RequestProcessorError::Script { .. }) => e,
+        Ok(e) => RequestProcessorError::Processing(e.into()),
+        Err(e) => RequestProcessorError::Processing(e),
+    }
+}
+
+/// Records the outcome of a processing on its RequestConversation. Failing to
+/// record the outcome doesn't affect the result, which is passed through.
+async fn finish_request_conversation(
+    tx_db: ReqSender<ReqCmd>,
+    conversation_id: Uuid,
+    res: Result<Response, RequestProcessorError>,
+) -> Result<Response, RequestProcessorError> {
+    let (status, status_code, error) = match &res {
+        Ok(response) => (ConversationStatus::Succeeded, Some(response.code), None),
+        Err(e @ RequestProcessorError::Timeout(_)) => {
+            (ConversationStatus::TimedOut, None, Some(e.to_string()))
         }
+        Err(e) => (ConversationStatus::Failed, None, Some(e.to_string())),
+    };
+
+    let (cmd_tx, cmd_rx) = oneshot::channel();
+    let finished: Result<(), RequestProcessorError> = async {
+        Ok(execute_command!(
+            tx_db,
+            ReqCmd::FinishRequestConversation {
+                id: conversation_id,
+                status,
+                status_code,
+                error,
+                cmd_tx,
+            },
+            cmd_rx
+        ))
+    }
+    .await;
+
+    if let Err(e) = finished {
+        tracing::error!(
+            "Unable to finish RequestConversation {}: {:?}",
+            conversation_id,
+            e
+        );
     }
 
-    Ok(())
+    res
 }
 
 /// Handles RequestProcessor creation with all the boilerplate. Passes
@@ -178,50 +372,33 @@ async fn create_request_processor(
     tx_db: ReqSender<ReqCmd>,
     proc: RequestProcessor,
 ) -> Result<RequestProcessor, RequestProcessorError> {
-    let mut tx_db2 = tx_db
-        .lock()
-        .map_err(|e| RequestProcessorError::Locking(e.to_string()))?
-        .clone();
-
-    let (cmd_tx2, cmd_rx2) = oneshot::channel();
-
-    tx_db2
-        .send(ReqCmd::CreateRequestProcessor {
-            cmd_tx: cmd_tx2,
-            proc,
-        })
-        .await
-        .map_err(anyhow::Error::new)?;
+    let (cmd_tx, cmd_rx) = oneshot::channel();
 
-    cmd_rx2
-        .await
-        .map_err(|_| Error::msg(format!("Unable to send () to server handler")))?
+    Ok(execute_command!(
+        tx_db,
+        ReqCmd::CreateRequestProcessor { cmd_tx, proc },
+        cmd_rx
+    ))
 }
 
 /// Handles RequestConversation creation with all the boilerplate. Passes
 /// [`ReqCmd`] commands to the `fh_db` crate asyncronously.
-async fn create_request_conversation(
+pub(crate) async fn create_request_conversation(
     tx_db: ReqSender<ReqCmd>,
     request_processor_id: Uuid,
+    parent_id: Option<Uuid>,
 ) -> Result<RequestConversation, RequestProcessorError> {
-    let mut tx_db2 = tx_db
-        .lock()
-        .map_err(|e| RequestProcessorError::Locking(e.to_string()))?
-        .clone();
-
-    let (cmd_tx2, cmd_rx2) = oneshot::channel();
-
-    tx_db2
-        .send(ReqCmd::CreateRequestConversation {
-            request_processor_id: request_processor_id,
-            cmd_tx: cmd_tx2,
-        })
-        .await
-        .map_err(anyhow::Error::new)?;
+    let (cmd_tx, cmd_rx) = oneshot::channel();
 
-    cmd_rx2
-        .await
-        .map_err(|_| Error::msg(format!("Unable to send () to server handler")))?
+    Ok(execute_command!(
+        tx_db,
+        ReqCmd::CreateRequestConversation {
+            request_processor_id,
+            parent_id,
+            cmd_tx,
+        },
+        cmd_rx
+    ))
 }
 
 /// Fetches a RequestProcessor from the `fh_db` crate using a [`ReqCmd`] command.
@@ -229,38 +406,152 @@ async fn get_request_processor(
     tx_db: ReqSender<ReqCmd>,
     id: Uuid,
 ) -> Result<RequestProcessor, RequestProcessorError> {
-    let mut tx_db2 = tx_db
-        .lock()
-        .map_err(|e| RequestProcessorError::Locking(e.to_string()))?
-        .clone();
+    let (cmd_tx, cmd_rx) = oneshot::channel();
 
-    let (cmd_tx2, cmd_rx2) = oneshot::channel();
+    Ok(execute_command!(
+        tx_db,
+        ReqCmd::GetRequestProcessor { id, cmd_tx },
+        cmd_rx
+    ))
+}
 
-    tx_db2
-        .send(ReqCmd::GetRequestProcessor {
-            id,
-            cmd_tx: cmd_tx2,
-        })
-        .await
-        .map_err(anyhow::Error::new)?;
+/// Rejects the invocation with [`RequestProcessorError::QuotaExceeded`], if
+/// the RequestProcessor has a quota and one of its limits is reached.
+async fn check_usage_quota(
+    tx_db: ReqSender<ReqCmd>,
+    request_processor: &RequestProcessor,
+) -> Result<(), RequestProcessorError> {
+    let quota = match &request_processor.quota {
+        Some(quota) => quota.clone(),
+        None => return Ok(()),
+    };
+    let (cmd_tx, cmd_rx) = oneshot::channel();
 
-    cmd_rx2
-        .await
-        .map_err(|_| Error::msg(format!("Unable to send () to server handler")))?
+    Ok(execute_command!(
+        tx_db,
+        ReqCmd::CheckUsageQuota {
+            request_processor_id: request_processor.id,
+            quota,
+            cmd_tx,
+        },
+        cmd_rx
+    ))
 }
 
-/// Actual V8 processing function. Creates the JsRuntime, prepares prelude and
-/// sequel code snippets and optionally wraps the RequestProcessor's code with
-/// these. Returns a final response including a `FH-Conversation-Id` header.
+/// Adds `usage` to the daily usage of a RequestProcessor. Failures are only
+/// logged, as the accounted work is already done.
+pub(crate) async fn record_usage(
+    tx_db: ReqSender<ReqCmd>,
+    request_processor_id: Uuid,
+    usage: Usage,
+) {
+    let recorded: Result<(), RequestProcessorError> = async {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        Ok(execute_command!(
+            tx_db,
+            ReqCmd::RecordUsage {
+                request_processor_id,
+                usage,
+                cmd_tx,
+            },
+            cmd_rx
+        ))
+    }
+    .await;
+
+    if let Err(e) = recorded {
+        tracing::error!(
+            "Unable to record usage of RequestProcessor {}: {:?}",
+            request_processor_id,
+            e
+        );
+    }
+}
+
+/// Creates the error for a processing, which exceeded [`PROCESSING_TIMEOUT`].
+fn timeout_error() -> Error {
+    RequestProcessorError::Timeout(PROCESSING_TIMEOUT.as_millis() as u64).into()
+}
+
+/// Actual V8 processing function. Creates the JsRuntime and executes the
+/// RequestProcessor's code, which has to be already wrapped with prelude and
+/// sequel, if desired. `depth` is the number of `fh.invoke` calls, which led
+/// to this execution. Returns a final response including a
+/// `FH-Conversation-Id` header.
 pub async fn process_request(
+    ctx: ProcessingContext,
     tx_db: ReqSender<ReqCmd>,
     req: Request,
     conversation_id: Uuid,
-    code: String,
+    request_processor: RequestProcessor,
+    depth: usize,
 ) -> Result<Response> {
-    let mut js_runtime = prepare_runtime(tx_db.clone(), req.clone(), conversation_id).await?;
-    js_runtime.execute("custom_code.js", &prepare_user_code(&code, false))?;
-    js_runtime.run_event_loop().await?;
+    ctx.activity.progressed();
+    let mut js_runtime = prepare_runtime(
+        ctx,
+        tx_db.clone(),
+        req.clone(),
+        conversation_id,
+        &request_processor,
+        depth,
+    )
+    .instrument(tracing::info_span!("prepare_runtime"))
+    .await?;
+
+    // the timeout cancels awaiting ops, the watchdog terminates busy code
+    let watchdog = Watchdog::start(
+        js_runtime.v8_isolate().thread_safe_handle(),
+        PROCESSING_TIMEOUT,
+    );
+    let started = Instant::now();
+    let execution = tokio::time::timeout(PROCESSING_TIMEOUT, async {
+        js_runtime.execute("custom_code.js", &request_processor.code)?;
+        js_runtime.run_event_loop().await
+    })
+    .instrument(tracing::info_span!(
+        "v8_execution",
+        processor_id = %request_processor.id,
+        %conversation_id,
+    ));
+    let (res, cpu_time) = cpu_timed(Box::pin(execution)).await;
+    let terminated = watchdog.stop();
+    V8_EXECUTION_DURATION
+        .with_label_values(&[&request_processor.id.to_string()])
+        .observe(started.elapsed().as_secs_f64());
+    let usage = Usage {
+        invocations: 1,
+        cpu_ms: cpu_time.as_millis() as i64,
+        ..Usage::default()
+    };
+    record_usage(tx_db.clone(), request_processor.id, usage).await;
+
+    // store the `console` output, even if the processing failed
+    {
+        let state = js_runtime.op_state();
+        let mut op_state = state.borrow_mut();
+        op_state
+            .borrow_mut::<RuntimeState>()
+            .flush_console()
+            .await?;
+    }
+
+    match res {
+        Err(_) => return Err(timeout_error()),
+        Ok(Err(_)) if terminated => return Err(timeout_error()),
+        Ok(Err(e)) => {
+            let state = js_runtime.op_state();
+            let mut op_state = state.borrow_mut();
+            let rt_state = op_state.borrow_mut::<RuntimeState>();
+            let error = rt_state.add_error(to_script_error(&e)).await?;
+
+            return Err(RequestProcessorError::Script {
+                conversation_id,
+                error,
+            }
+            .into());
+        }
+        Ok(Ok(())) => {}
+    }
 
     // extract the requests
     let state = js_runtime.op_state();
@@ -277,6 +568,7 @@ pub async fn process_request(
             headers: HashMap::new(),
             body: Some(rt_state.get_final_response_body()?),
             version: "HTTP/1.1".to_string(), // TODO: fill that with something correct
+            raw_body: None,
         }
     };
 
@@ -1,18 +1,37 @@
+use crate::chain::op_invoke;
+use crate::crypto::{op_crypto_digest, op_crypto_hmac_sign, op_crypto_hmac_verify};
+use crate::delivery::{
+    check_outbound_rate_limit, delivery_log_level, delivery_log_message, is_retryable,
+    record_outbound_usage, send_request,
+};
+use crate::web::{
+    op_get_random_values, op_random_uuid, op_timer_cancel, op_timer_start, op_url_parse, Timers,
+};
+use crate::ProcessingContext;
 use anyhow::Result;
 use deno_core::JsRuntime;
 use deno_core::OpState;
 use deno_core::ZeroCopyBuf;
-use deno_core::{error::AnyError, BufVec};
+use deno_core::{
+    error::{AnyError, JsError},
+    BufVec,
+};
 use fh_core::{
+    error::ScriptError,
     request::{Request, RequestResponseList, RequestSpec},
     response::Response,
     ReqSender,
 };
-use fh_db::{ReqCmd, RequestProcessorError};
-use reqwest::{header, Method, Url};
+use fh_db::{
+    outbound_delivery::OutboundDelivery,
+    processor_secret::SecretValue,
+    request_conversation::{AuditItem, LogLevel},
+    request_processor::{RequestProcessor, RequestProcessorConfig},
+    ReqCmd, RequestProcessorError,
+};
+use serde::Deserialize;
 use serde_json::Value;
-use std::str::FromStr;
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 use tokio::sync::oneshot;
 use uuid::Uuid;
 
@@ -36,28 +55,61 @@ pub(crate) struct RuntimeState {
     /// Id of the current conversation.
     pub(crate) conversation_id: Uuid,
 
+    /// Id of the RequestProcessor, which is currently executed.
+    pub(crate) request_processor_id: Uuid,
+
+    /// Number of `fh.invoke` calls and Pipelines, which led to this
+    /// execution. 0 for invocations by clients.
+    pub(crate) depth: usize,
+
+    /// Configuration object of the RequestProcessor.
+    pub(crate) config: RequestProcessorConfig,
+
+    /// Log entries below this level are not stored.
+    pub(crate) min_log_level: LogLevel,
+
     /// Optional final response.
     pub(crate) final_response: Option<Response>,
+
+    /// Log entries written via `console`, which are not stored yet.
+    pub(crate) console_entries: Vec<AuditItem>,
+
+    /// Decrypted secrets of the RequestProcessor. Their values are redacted
+    /// from all AuditItems.
+    pub(crate) secrets: HashMap<String, SecretValue>,
 }
 
 impl RuntimeState {
     /// Creates a new RuntimeState.
     ///
-    /// Implicitly creates an AuditItem for the incoming request.
+    /// Implicitly loads the RequestProcessor's secrets and creates an AuditItem
+    /// for the incoming request.
     async fn new(
         request: Request,
         tx_db: ReqSender<ReqCmd>,
         conversation_id: Uuid,
+        request_processor: &RequestProcessor,
+        depth: usize,
     ) -> anyhow::Result<Self> {
+        let request_processor_id = request_processor.id;
+        let (cmd_tx1, cmd_rx1) = oneshot::channel();
+        let secrets = execute_command!(
+            tx_db,
+            ReqCmd::GetProcessorSecretValues {
+                request_processor_id,
+                cmd_tx: cmd_tx1,
+            },
+            cmd_rx1
+        );
+
         let (cmd_tx2, cmd_rx2) = oneshot::channel();
         let req_audit_item = execute_command!(
             tx_db,
             ReqCmd::CreateAuditLogEntry {
-                item: fh_db::request_conversation::AuditItem::new_request(
-                    conversation_id,
-                    0,
-                    request.clone(),
-                ),
+                item: redact_secrets(
+                    &secrets,
+                    AuditItem::new_request(conversation_id, 0, request.clone()),
+                )?,
                 cmd_tx: cmd_tx2,
             },
             cmd_rx2
@@ -66,79 +118,128 @@ impl RuntimeState {
         Ok(Self {
             counter: RequestCounter(0),
             conversation_id,
+            request_processor_id,
+            depth,
+            config: request_processor.config.clone(),
+            min_log_level: request_processor.min_log_level,
             final_response: None,
+            console_entries: Vec::new(),
             request,
             request_list: RequestResponseList::new(),
             tx_db,
             request_audit_id: req_audit_item.get_id(),
+            secrets,
         })
     }
 
-    /// Adds an issued Request.
-    ///
-    /// Implicitly creates an AuditItem for the issued request.
-    async fn add_request(&mut self, request: Request) -> anyhow::Result<usize> {
+    /// Adds an issued Request and returns its AuditItem, which is stored by
+    /// the calling op via [`store_audit_item`] after releasing its borrow.
+    pub(crate) fn register_request(
+        &mut self,
+        request: Request,
+    ) -> anyhow::Result<(usize, AuditItem)> {
         let inc = self.counter.increment();
         self.request_list.add_request(inc, request.clone());
 
-        let (cmd_tx2, cmd_rx2) = oneshot::channel();
-        execute_command!(
-            self.tx_db,
-            ReqCmd::CreateAuditLogEntry {
-                item: fh_db::request_conversation::AuditItem::new_request(
-                    self.conversation_id,
-                    inc as i32,
-                    request.clone(),
-                ),
-                cmd_tx: cmd_tx2,
-            },
-            cmd_rx2
-        );
-        Ok(inc)
+        let item = redact_secrets(
+            &self.secrets,
+            AuditItem::new_request(self.conversation_id, inc as i32, request),
+        )?;
+        Ok((inc, item))
     }
 
-    /// Adds a received Response.
-    ///
-    /// Implicitly creates an AuditItem for the issued response.
-    async fn add_response(&mut self, idx: usize, response: Response) -> anyhow::Result<()> {
+    /// Adds a received Response and returns its AuditItem, which is stored
+    /// like the one of [`Self::register_request`].
+    pub(crate) fn register_response(
+        &mut self,
+        idx: usize,
+        response: Response,
+    ) -> anyhow::Result<AuditItem> {
         self.request_list.add_response(idx, response.clone());
 
-        let (cmd_tx2, cmd_rx2) = oneshot::channel();
-        execute_command!(
-            self.tx_db,
-            ReqCmd::CreateAuditLogEntry {
-                item: fh_db::request_conversation::AuditItem::new_response(
-                    self.conversation_id,
-                    self.request_audit_id,
-                    response,
-                ),
-                cmd_tx: cmd_tx2,
-            },
-            cmd_rx2
-        );
-
-        Ok(())
+        redact_secrets(
+            &self.secrets,
+            AuditItem::new_response(self.conversation_id, self.request_audit_id, response),
+        )
     }
 
     /// Adds the final response.
-    async fn add_final_response(&mut self, response: Response) -> anyhow::Result<()> {
-        self.final_response = Some(response.clone());
+    fn add_final_response(&mut self, response: Response) {
+        self.final_response = Some(response);
+    }
+
+    /// Creates a AuditItem::Log for a log entry, which is stored like the
+    /// one of [`Self::register_request`]. Entries below the RequestProcessor's
+    /// minimum log level are dropped.
+    fn log_item(
+        &self,
+        level: LogLevel,
+        log: String,
+        fields: Option<Value>,
+    ) -> anyhow::Result<Option<AuditItem>> {
+        if level < self.min_log_level {
+            return Ok(None);
+        }
+
+        let item = redact_secrets(
+            &self.secrets,
+            AuditItem::new_log(self.conversation_id, level, log, fields),
+        )?;
+        Ok(Some(item))
+    }
+
+    /// Buffers a log entry written via `console`. The `console` functions are
+    /// synchronous, so the entries are stored by [`Self::flush_console`]
+    /// after the execution.
+    fn add_console_entry(&mut self, level: LogLevel, log: String) {
+        if level < self.min_log_level {
+            return;
+        }
+
+        self.console_entries
+            .push(AuditItem::new_log(self.conversation_id, level, log, None));
+    }
+
+    /// Stores all buffered `console` entries as AuditItem::Log.
+    pub(crate) async fn flush_console(&mut self) -> anyhow::Result<()> {
+        for item in std::mem::take(&mut self.console_entries) {
+            let (cmd_tx2, cmd_rx2) = oneshot::channel();
+            execute_command!(
+                self.tx_db,
+                ReqCmd::CreateAuditLogEntry {
+                    item: redact_secrets(&self.secrets, item)?,
+                    cmd_tx: cmd_tx2,
+                },
+                cmd_rx2
+            );
+        }
+
         Ok(())
     }
 
-    /// Adds a log entry by creating a AuditItem::Log.
-    async fn add_log_entry(&mut self, log: String) -> anyhow::Result<()> {
+    /// Adds an error by creating a AuditItem::Error. Returns the error with
+    /// all secrets redacted.
+    pub(crate) async fn add_error(&mut self, error: ScriptError) -> anyhow::Result<ScriptError> {
+        let item = redact_secrets(
+            &self.secrets,
+            AuditItem::new_error(self.conversation_id, error),
+        )?;
+        let error = match &item {
+            AuditItem::Error { payload, .. } => payload.clone(),
+            _ => unreachable!(),
+        };
+
         let (cmd_tx2, cmd_rx2) = oneshot::channel();
         execute_command!(
             self.tx_db,
             ReqCmd::CreateAuditLogEntry {
-                item: fh_db::request_conversation::AuditItem::new_log(self.conversation_id, log,),
+                item,
                 cmd_tx: cmd_tx2,
             },
             cmd_rx2
         );
 
-        Ok(())
+        Ok(error)
     }
 
     /// Computes the final response body.
@@ -169,6 +270,32 @@ impl RuntimeState {
     }
 }
 
+/// Replaces all secret values of the RequestProcessor in the given AuditItem,
+/// before it is sent to the database.
+pub(crate) fn redact_secrets(
+    secrets: &HashMap<String, SecretValue>,
+    item: AuditItem,
+) -> Result<AuditItem, RequestProcessorError> {
+    let values: Vec<&str> = secrets.values().map(|s| s.expose()).collect();
+    item.redact(&values)
+}
+
+/// Converts an error of the JsRuntime to a [`ScriptError`]. Uncaught
+/// JavaScript exceptions contain the stack trace and source position.
+pub(crate) fn to_script_error(e: &AnyError) -> ScriptError {
+    match e.downcast_ref::<JsError>() {
+        Some(js_error) => ScriptError {
+            message: js_error.message.clone(),
+            stack: js_error.stack.clone(),
+            script_resource_name: js_error.script_resource_name.clone(),
+            line_number: js_error.line_number,
+            column_number: js_error.start_column.map(|c| c + 1),
+            source_line: js_error.source_line.clone(),
+        },
+        None => ScriptError::new(e.to_string()),
+    }
+}
+
 /// Simple wrapper type for a Counter
 pub(crate) struct RequestCounter(usize);
 
@@ -193,6 +320,46 @@ fn op_get_request(
     Ok(serde_json::json!(r.request))
 }
 
+/// Represents the `get_config` function, which can be called from the
+/// JsRuntime using `Deno.core.jsonOpSync("get_config")`.
+/// Returns the RequestProcessor's configuration object.
+fn op_get_config(
+    state: &mut OpState,
+    _args: Value,
+    _bufs: &mut [ZeroCopyBuf],
+) -> Result<Value, AnyError> {
+    let r = state.borrow::<RuntimeState>();
+    Ok(serde_json::json!(r.config))
+}
+
+/// Arguments of the `get_secret` op.
+#[derive(Debug, Deserialize)]
+struct SecretArgs {
+    name: String,
+}
+
+/// Represents the `get_secret` function, which can be called from the
+/// JsRuntime using `Deno.core.jsonOpSync("get_secret", {name})`.
+/// Returns the plaintext value of the RequestProcessor's secret with the given
+/// name. Fails, if no such secret is defined.
+fn op_get_secret(
+    state: &mut OpState,
+    args: Value,
+    _bufs: &mut [ZeroCopyBuf],
+) -> Result<Value, AnyError> {
+    let args: SecretArgs = serde_json::from_value(args)?;
+    let r = state.borrow::<RuntimeState>();
+    let secret = r
+        .secrets
+        .get(&args.name)
+        .ok_or(RequestProcessorError::Custom(format!(
+            "Secret '{}' is not defined for this RequestProcessor",
+            args.name
+        )))?;
+
+    Ok(serde_json::json!(secret.expose()))
+}
+
 /// Adds a final response, which shall be returned to the client. Represents the `respond_with` function, which can be called from the JsRuntime
 /// using `Deno.core.jsonOpAsync("respond_with", response)`.
 /// The response object is a JSON representation of [`fh_core::response::Response`].
@@ -202,18 +369,31 @@ async fn op_respond_with(
     _bufs: BufVec,
 ) -> Result<Value, AnyError> {
     let response: Response = serde_json::from_value(args)?;
-    let mut op_state = state.borrow_mut();
-    let rt_state = op_state.borrow_mut::<RuntimeState>();
-
-    rt_state.add_final_response(response).await?;
+    state
+        .borrow_mut()
+        .borrow_mut::<RuntimeState>()
+        .add_final_response(response);
 
     Ok(serde_json::json!(()))
 }
 
+/// Arguments of the `fh_log` op.
+#[derive(Debug, Deserialize)]
+struct LogArgs {
+    #[serde(default)]
+    level: LogLevel,
+    data: Option<Value>,
+    #[serde(default)]
+    fields: Option<Value>,
+}
+
 /// Represents the `fh_log` function, which can be called from the JsRuntime
 /// using `Deno.core.jsonOpAsync("fh_log", spec)`.
-/// The `spec` object has one key:
-/// - data: String to be logged.
+/// The `spec` object has these keys:
+/// - level (optional): one of `debug`, `info` (default), `warn` or `error`.
+/// - data: value to be logged. Strings are stored as they are, all other
+///   values as JSON.
+/// - fields (optional): structured JSON metadata.
 ///
 /// The data is actually printed to stdout by the JsRuntime and additionally stored to the db
 /// as a [`fh_db::request_conversation::AuditItem::Log`].
@@ -222,25 +402,77 @@ async fn op_log(
     args: Value,
     _bufs: BufVec,
 ) -> Result<Value, AnyError> {
-    let log_entry = args
-        .get("data")
-        .ok_or(RequestProcessorError::Custom(
-            "No 'data' attribute available for AuditEntry::Log".to_string(),
-        ))?
-        .to_string();
+    let args: LogArgs = serde_json::from_value(args)?;
+    let log_entry = match args.data {
+        Some(Value::String(s)) => s,
+        Some(data) => data.to_string(),
+        None => {
+            return Err(RequestProcessorError::Custom(
+                "No 'data' attribute available for AuditEntry::Log".to_string(),
+            )
+            .into())
+        }
+    };
+    let fields = args.fields.filter(|f| !f.is_null());
+
+    let (tx_db, item) = {
+        let op_state = state.borrow();
+        let rt_state = op_state.borrow::<RuntimeState>();
+        (
+            rt_state.tx_db.clone(),
+            rt_state.log_item(args.level, log_entry, fields)?,
+        )
+    };
+    if let Some(item) = item {
+        store_audit_item(tx_db, item).await?;
+    }
 
-    let mut op_state = state.borrow_mut();
-    let rt_state = op_state.borrow_mut::<RuntimeState>();
-    rt_state.add_log_entry(log_entry).await?;
+    Ok(serde_json::json!(()))
+}
+
+/// Arguments of the `console` op.
+#[derive(Debug, Deserialize)]
+struct ConsoleArgs {
+    level: LogLevel,
+    message: String,
+}
+
+/// Represents the `console` functions, which are defined in `fh_console.js`
+/// and call `Deno.core.jsonOpSync("console", {level, message})` with the
+/// already formatted message.
+fn op_console(
+    state: &mut OpState,
+    args: Value,
+    _bufs: &mut [ZeroCopyBuf],
+) -> Result<Value, AnyError> {
+    let args: ConsoleArgs = serde_json::from_value(args)?;
+    state
+        .borrow_mut::<RuntimeState>()
+        .add_console_entry(args.level, args.message);
 
     Ok(serde_json::json!(()))
 }
 
+/// Converts a received response for the JS side. If `raw` is set, the received
+/// bytes are added base64 encoded, as `body` may not be valid UTF-8.
+fn response_value(r: &Response, raw: bool) -> Value {
+    let mut value = serde_json::json!(r);
+    if let (true, Some(bytes)) = (raw, &r.raw_body) {
+        value["raw_body"] = Value::String(base64::encode(bytes));
+    }
+
+    value
+}
+
 /// Represents the `dispatch_request` function, which can be called from the
 /// JsRuntime using `Deno.core.jsonOpAsync("dispatch_request", spec)`. The
-/// `spec` object has two keys:
+/// `spec` object has these keys:
 /// - request: regular request object, based on [`fh_core::request::Request`]
 /// - url: fully qualified URL, where the request should be sent to.
+/// - headers (optional): headers, which replace the ones of `request`.
+/// - retry (optional): [`fh_core::request::RetryPolicy`] for failed requests.
+/// - raw (optional): if set, the received body is added as `raw_body`, see
+///   [`response_value`].
 ///
 /// The request is stored as a
 /// [`fh_db::request_conversation::AuditItem::Request`] to the database. Then
@@ -249,41 +481,286 @@ async fn op_log(
 /// then stored in the database as
 /// [`fh_db::request_conversation::AuditItem::Response`] with the requests Uuid
 /// to match be able to match a Request / Response pair later on.
+///
+/// If a retry policy is given and the request fails or receives a server
+/// error, the request is queued as [`OutboundDelivery`] and a synthesized
+/// `202` response with the header `FH-Delivery-Id` is returned.
 async fn op_dispatch_request(
     state: Rc<RefCell<OpState>>,
     args: Value,
     _bufs: BufVec,
 ) -> Result<Value, AnyError> {
-    let request_spec: RequestSpec = serde_json::from_value(args)?;
+    let mut request_spec: RequestSpec = serde_json::from_value(args)?;
+    // only explicitly given headers are sent, see `RequestSpec::headers`
+    request_spec.request.headers = std::mem::take(&mut request_spec.headers);
+
+    // the state is not borrowed across awaits, so other ops (e.g. concurrent
+    // requests) can run meanwhile
+    let (outbound_limiter, tx_db, request_processor_id, conversation_id, inc, item) = {
+        let mut op_state = state.borrow_mut();
+        let outbound_limiter = op_state
+            .borrow::<ProcessingContext>()
+            .outbound_limiter
+            .clone();
+        let rt_state = op_state.borrow_mut::<RuntimeState>();
+        let (inc, item) = rt_state.register_request(request_spec.request.clone())?;
+        (
+            outbound_limiter,
+            rt_state.tx_db.clone(),
+            rt_state.request_processor_id,
+            rt_state.conversation_id,
+            inc,
+            item,
+        )
+    };
+    store_audit_item(tx_db.clone(), item).await?;
+
+    // a limited request fails like an unreachable host, so it is queued for
+    // a later attempt, if a retry policy is given
+    let result = match check_outbound_rate_limit(outbound_limiter.as_deref(), &request_spec.url) {
+        Ok(()) => {
+            let result = send_request(&request_spec.url, &request_spec.request).await;
+            record_outbound_usage(
+                tx_db.clone(),
+                request_processor_id,
+                &request_spec.request,
+                &result,
+            )
+            .await;
+            result
+        }
+        Err(e) => Err(e),
+    };
+
+    let add_response = |r: &Response| {
+        state
+            .borrow_mut()
+            .borrow_mut::<RuntimeState>()
+            .register_response(inc, r.clone())
+    };
+    let raw = request_spec.raw;
+    let policy = match request_spec.retry {
+        None => {
+            let r = result?;
+            store_audit_item(tx_db, add_response(&r)?).await?;
+            return Ok(response_value(&r, raw));
+        }
+        Some(policy) => policy,
+    };
 
-    let mut op_state = state.borrow_mut();
-    let rt_state = op_state.borrow_mut::<RuntimeState>();
+    let error = match result {
+        Ok(r) if !is_retryable(&r) => {
+            store_audit_item(tx_db, add_response(&r)?).await?;
+            return Ok(response_value(&r, raw));
+        }
+        Ok(r) => {
+            store_audit_item(tx_db.clone(), add_response(&r)?).await?;
+            format!("Received status code {}", r.code)
+        }
+        Err(e) => e.to_string(),
+    };
+
+    let (cmd_tx2, cmd_rx2) = oneshot::channel();
+    let delivery = execute_command!(
+        tx_db,
+        ReqCmd::CreateOutboundDelivery {
+            delivery: OutboundDelivery::new(
+                request_processor_id,
+                conversation_id,
+                inc as i32,
+                request_spec.url,
+                request_spec.request,
+                &policy,
+                error,
+            ),
+            cmd_tx: cmd_tx2,
+        },
+        cmd_rx2
+    );
+    let item = state.borrow().borrow::<RuntimeState>().log_item(
+        delivery_log_level(&delivery),
+        delivery_log_message(&delivery),
+        None,
+    )?;
+    if let Some(item) = item {
+        store_audit_item(tx_db, item).await?;
+    }
 
-    let inc = rt_state.add_request(request_spec.request.clone()).await?;
+    let mut headers = HashMap::new();
+    headers.insert("FH-Delivery-Id".to_string(), vec![delivery.id.to_string()]);
+    let r = Response {
+        code: 202,
+        headers,
+        body: Some(
+            serde_json::json!({
+                "delivery_id": delivery.id,
+                "status": delivery.status,
+            })
+            .to_string(),
+        ),
+        version: "HTTP/1.1".to_string(),
+        raw_body: None,
+    };
 
-    let c = reqwest::Client::builder().build()?;
-    let response: reqwest::Response = c
-        .request(
-            Method::from_str(&request_spec.request.method)?,
-            Url::parse(&request_spec.url)?,
-        )
-        .body(request_spec.request.body)
-        .header(header::ACCEPT, "application/json")
-        .send()
-        .await?;
+    Ok(serde_json::json!(r))
+}
 
-    let r = Response::try_from_response(response).await?;
+/// Arguments of the `kv_get` and `kv_delete` ops.
+#[derive(Debug, Deserialize)]
+struct KvKeyArgs {
+    key: String,
+}
 
-    rt_state.add_response(inc, r.clone()).await?;
+/// Arguments of the `kv_set` op.
+#[derive(Debug, Deserialize)]
+struct KvSetArgs {
+    key: String,
+    #[serde(default)]
+    value: Value,
+    ttl: Option<i64>,
+}
 
-    Ok(serde_json::json!(r))
+/// Arguments of the `kv_list` op.
+#[derive(Debug, Deserialize)]
+struct KvListArgs {
+    prefix: Option<String>,
 }
 
-/// Registers all custom operations and the [`RuntimeState`] and returns the final prepared [`JsRuntime`].
+/// Stores an AuditItem, which was returned by the [`RuntimeState`], so that
+/// ops don't need to hold a borrow of the [`OpState`] while awaiting the DB
+/// command.
+pub(crate) async fn store_audit_item(
+    tx_db: ReqSender<ReqCmd>,
+    item: AuditItem,
+) -> anyhow::Result<()> {
+    let (cmd_tx, cmd_rx) = oneshot::channel();
+    execute_command!(tx_db, ReqCmd::CreateAuditLogEntry { item, cmd_tx }, cmd_rx);
+
+    Ok(())
+}
+
+/// Clones the DB transmitter and the current RequestProcessor's Uuid out of
+/// the [`RuntimeState`], so that ops don't need to hold a borrow of the
+/// [`OpState`] while awaiting DB commands.
+fn db_context(state: &Rc<RefCell<OpState>>) -> (ReqSender<ReqCmd>, Uuid) {
+    let op_state = state.borrow();
+    let rt_state = op_state.borrow::<RuntimeState>();
+    (rt_state.tx_db.clone(), rt_state.request_processor_id)
+}
+
+/// Represents the `kv_get` function, which can be called from the JsRuntime
+/// using `Deno.core.jsonOpAsync("kv_get", {key})`.
+/// Returns the stored value or `null`, if the key does not exist or expired.
+async fn op_kv_get(
+    state: Rc<RefCell<OpState>>,
+    args: Value,
+    _bufs: BufVec,
+) -> Result<Value, AnyError> {
+    let args: KvKeyArgs = serde_json::from_value(args)?;
+    let (tx_db, request_processor_id) = db_context(&state);
+
+    let (cmd_tx2, cmd_rx2) = oneshot::channel();
+    let entry = execute_command!(
+        tx_db,
+        ReqCmd::GetKvEntry {
+            request_processor_id,
+            key: args.key,
+            cmd_tx: cmd_tx2,
+        },
+        cmd_rx2
+    );
+
+    Ok(entry.map(|e| e.value).unwrap_or(Value::Null))
+}
+
+/// Represents the `kv_set` function, which can be called from the JsRuntime
+/// using `Deno.core.jsonOpAsync("kv_set", {key, value, ttl})`.
+/// The optional `ttl` is given in seconds. Stores the value in the
+/// RequestProcessor's key-value store, see [`fh_db::processor_kv`].
+async fn op_kv_set(
+    state: Rc<RefCell<OpState>>,
+    args: Value,
+    _bufs: BufVec,
+) -> Result<Value, AnyError> {
+    let args: KvSetArgs = serde_json::from_value(args)?;
+    let (tx_db, request_processor_id) = db_context(&state);
+
+    let (cmd_tx2, cmd_rx2) = oneshot::channel();
+    execute_command!(
+        tx_db,
+        ReqCmd::SetKvEntry {
+            request_processor_id,
+            key: args.key,
+            value: args.value,
+            ttl: args.ttl,
+            cmd_tx: cmd_tx2,
+        },
+        cmd_rx2
+    );
+
+    Ok(serde_json::json!(()))
+}
+
+/// Represents the `kv_delete` function, which can be called from the JsRuntime
+/// using `Deno.core.jsonOpAsync("kv_delete", {key})`.
+/// Returns `true`, if the key existed.
+async fn op_kv_delete(
+    state: Rc<RefCell<OpState>>,
+    args: Value,
+    _bufs: BufVec,
+) -> Result<Value, AnyError> {
+    let args: KvKeyArgs = serde_json::from_value(args)?;
+    let (tx_db, request_processor_id) = db_context(&state);
+
+    let (cmd_tx2, cmd_rx2) = oneshot::channel();
+    let deleted = execute_command!(
+        tx_db,
+        ReqCmd::DeleteKvEntry {
+            request_processor_id,
+            key: args.key,
+            cmd_tx: cmd_tx2,
+        },
+        cmd_rx2
+    );
+
+    Ok(serde_json::json!(deleted))
+}
+
+/// Represents the `kv_list` function, which can be called from the JsRuntime
+/// using `Deno.core.jsonOpAsync("kv_list", {prefix})`.
+/// Returns a sorted list of all non-expired keys starting with `prefix`.
+async fn op_kv_list(
+    state: Rc<RefCell<OpState>>,
+    args: Value,
+    _bufs: BufVec,
+) -> Result<Value, AnyError> {
+    let args: KvListArgs = serde_json::from_value(args)?;
+    let (tx_db, request_processor_id) = db_context(&state);
+
+    let (cmd_tx2, cmd_rx2) = oneshot::channel();
+    let keys = execute_command!(
+        tx_db,
+        ReqCmd::ListKvKeys {
+            request_processor_id,
+            prefix: args.prefix,
+            cmd_tx: cmd_tx2,
+        },
+        cmd_rx2
+    );
+
+    Ok(serde_json::json!(keys))
+}
+
+/// Registers all custom operations, the [`RuntimeState`] and the
+/// [`ProcessingContext`], executes the `console` and web API bootstraps and
+/// returns the final prepared [`JsRuntime`].
 pub(crate) async fn prepare_runtime(
+    ctx: ProcessingContext,
     tx_db: ReqSender<ReqCmd>,
     request: Request,
     conversation_id: Uuid,
+    request_processor: &RequestProcessor,
+    depth: usize,
 ) -> anyhow::Result<JsRuntime> {
     let mut js_runtime = JsRuntime::new(Default::default());
 
@@ -291,14 +768,50 @@ pub(crate) async fn prepare_runtime(
         "dispatch_request",
         deno_core::json_op_async(op_dispatch_request),
     );
+    js_runtime.register_op("invoke", deno_core::json_op_async(op_invoke));
     js_runtime.register_op("fh_log", deno_core::json_op_async(op_log));
+    js_runtime.register_op("console", deno_core::json_op_sync(op_console));
     js_runtime.register_op("respond_with", deno_core::json_op_async(op_respond_with));
     js_runtime.register_op("get_request", deno_core::json_op_sync(op_get_request));
+    js_runtime.register_op("get_secret", deno_core::json_op_sync(op_get_secret));
+    js_runtime.register_op("get_config", deno_core::json_op_sync(op_get_config));
+    js_runtime.register_op("kv_get", deno_core::json_op_async(op_kv_get));
+    js_runtime.register_op("kv_set", deno_core::json_op_async(op_kv_set));
+    js_runtime.register_op("kv_delete", deno_core::json_op_async(op_kv_delete));
+    js_runtime.register_op("kv_list", deno_core::json_op_async(op_kv_list));
+    js_runtime.register_op("url_parse", deno_core::json_op_sync(op_url_parse));
+    js_runtime.register_op("timer_start", deno_core::json_op_async(op_timer_start));
+    js_runtime.register_op("timer_cancel", deno_core::json_op_sync(op_timer_cancel));
+    js_runtime.register_op("random_uuid", deno_core::json_op_sync(op_random_uuid));
+    js_runtime.register_op(
+        "get_random_values",
+        deno_core::json_op_sync(op_get_random_values),
+    );
+    js_runtime.register_op("crypto_digest", deno_core::json_op_sync(op_crypto_digest));
+    js_runtime.register_op(
+        "crypto_hmac_sign",
+        deno_core::json_op_sync(op_crypto_hmac_sign),
+    );
+    js_runtime.register_op(
+        "crypto_hmac_verify",
+        deno_core::json_op_sync(op_crypto_hmac_verify),
+    );
 
+    js_runtime.op_state().borrow_mut().put::<RuntimeState>(
+        RuntimeState::new(request, tx_db, conversation_id, request_processor, depth).await?,
+    );
     js_runtime
         .op_state()
         .borrow_mut()
-        .put::<RuntimeState>(RuntimeState::new(request, tx_db, conversation_id).await?);
+        .put::<Timers>(Timers::default());
+    js_runtime
+        .op_state()
+        .borrow_mut()
+        .put::<ProcessingContext>(ctx);
+
+    // provides the `console` global and web APIs, also without prelude
+    js_runtime.execute("fh_console.js", include_str!("fh_console.js"))?;
+    js_runtime.execute("fh_web.js", include_str!("fh_web.js"))?;
 
     Ok(js_runtime)
 }
@@ -6,10 +6,12 @@ macro_rules! execute_command {
             .map_err(|e| RequestProcessorError::Locking(e.to_string()))?
             .clone();
 
-        tx_db2.send($cmd).await.map_err(anyhow::Error::new)?;
+        fh_core::enqueue(&mut tx_db2, $cmd)
+            .await
+            .map_err(anyhow::Error::new)?;
 
         // HINT: never omit awaiting here... this leads to runtime hangs!
         // TODO: error handling?
-        $cmd_rx.await??
+        $cmd_rx.await.map_err(anyhow::Error::new)??
     }};
 }
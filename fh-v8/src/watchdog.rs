@@ -0,0 +1,44 @@
+//! Watchdog, which terminates the JavaScript execution of a JsRuntime after a
+//! timeout. Awaiting ops can be cancelled by dropping their futures, but
+//! synchronous code like an endless loop never yields back to tokio. Thus the
+//! isolate is terminated from a separate thread.
+use deno_core::v8::IsolateHandle;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Handle of a started watchdog thread.
+pub(crate) struct Watchdog {
+    done_tx: mpsc::Sender<()>,
+    fired: Arc<AtomicBool>,
+}
+
+impl Watchdog {
+    /// Starts a thread, which terminates the isolate's execution, if
+    /// [`Watchdog::stop`] isn't called within `timeout`.
+    pub(crate) fn start(isolate: IsolateHandle, timeout: Duration) -> Self {
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        let fired = Arc::new(AtomicBool::new(false));
+        let fired2 = fired.clone();
+
+        thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = done_rx.recv_timeout(timeout) {
+                fired2.store(true, Ordering::SeqCst);
+                isolate.terminate_execution();
+            }
+        });
+
+        Self { done_tx, fired }
+    }
+
+    /// Stops the watchdog. Returns `true`, if the execution was terminated.
+    pub(crate) fn stop(self) -> bool {
+        let _ = self.done_tx.send(());
+        self.fired.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,199 @@
+//! Ops backing the web APIs defined in `fh_web.js`, like `URL`, timers and
+//! `crypto`. `fetch` is not part of this module, it uses the audited
+//! `dispatch_request` op.
+use deno_core::OpState;
+use deno_core::ZeroCopyBuf;
+use deno_core::{error::AnyError, BufVec};
+use fh_db::RequestProcessorError;
+use rand::RngCore;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    time::Duration,
+};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// Maximum number of bytes, which can be filled by `crypto.getRandomValues`.
+const MAX_RANDOM_BYTES: usize = 65536;
+
+/// Timers of a JsRuntime. Async ops only start, when they are polled the
+/// first time, so a timer might be cancelled before it is pending.
+#[derive(Default)]
+pub(crate) struct Timers {
+    pending: HashMap<u32, oneshot::Sender<()>>,
+    cancelled: HashSet<u32>,
+}
+
+/// Arguments of the `url_parse` op.
+#[derive(Debug, Deserialize)]
+struct UrlArgs {
+    href: String,
+    base: Option<String>,
+}
+
+/// Components of a parsed URL, named like the properties of the web `URL`.
+#[derive(Debug, Serialize)]
+struct UrlParts {
+    href: String,
+    origin: String,
+    protocol: String,
+    username: String,
+    password: String,
+    host: String,
+    hostname: String,
+    port: String,
+    pathname: String,
+    search: String,
+    hash: String,
+}
+
+impl From<&Url> for UrlParts {
+    fn from(url: &Url) -> Self {
+        let hostname = url.host_str().unwrap_or("").to_string();
+        let port = url.port().map(|p| p.to_string()).unwrap_or_default();
+        let host = if port.is_empty() {
+            hostname.clone()
+        } else {
+            format!("{}:{}", hostname, port)
+        };
+
+        Self {
+            href: url.to_string(),
+            origin: url.origin().ascii_serialization(),
+            protocol: format!("{}:", url.scheme()),
+            username: url.username().to_string(),
+            password: url.password().unwrap_or("").to_string(),
+            host,
+            hostname,
+            port,
+            pathname: url.path().to_string(),
+            search: match url.query() {
+                Some(q) if !q.is_empty() => format!("?{}", q),
+                _ => "".to_string(),
+            },
+            hash: match url.fragment() {
+                Some(f) if !f.is_empty() => format!("#{}", f),
+                _ => "".to_string(),
+            },
+        }
+    }
+}
+
+/// Represents the `url_parse` function, which can be called from the
+/// JsRuntime using `Deno.core.jsonOpSync("url_parse", {href, base})`.
+/// Parses `href`, optionally relative to `base`, and returns its components.
+pub(crate) fn op_url_parse(
+    _state: &mut OpState,
+    args: Value,
+    _bufs: &mut [ZeroCopyBuf],
+) -> Result<Value, AnyError> {
+    let args: UrlArgs = serde_json::from_value(args)?;
+    let url = match args.base {
+        Some(base) => Url::parse(&base)?.join(&args.href)?,
+        None => Url::parse(&args.href)?,
+    };
+
+    Ok(serde_json::json!(UrlParts::from(&url)))
+}
+
+/// Arguments of the `timer_start` and `timer_cancel` ops.
+#[derive(Debug, Deserialize)]
+struct TimerArgs {
+    id: u32,
+    #[serde(default)]
+    delay: u64,
+}
+
+/// Represents the timers, which can be started from the JsRuntime using
+/// `Deno.core.jsonOpAsync("timer_start", {id, delay})`. Resolves to `true`
+/// after `delay` milliseconds or to `false`, if the timer was cancelled.
+pub(crate) async fn op_timer_start(
+    state: Rc<RefCell<OpState>>,
+    args: Value,
+    _bufs: BufVec,
+) -> Result<Value, AnyError> {
+    let args: TimerArgs = serde_json::from_value(args)?;
+
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+    {
+        let mut op_state = state.borrow_mut();
+        let timers = op_state.borrow_mut::<Timers>();
+        if timers.cancelled.remove(&args.id) {
+            return Ok(serde_json::json!(false));
+        }
+        timers.pending.insert(args.id, cancel_tx);
+    }
+
+    let fired = tokio::select! {
+        _ = tokio::time::delay_for(Duration::from_millis(args.delay)) => true,
+        _ = cancel_rx => false,
+    };
+    state
+        .borrow_mut()
+        .borrow_mut::<Timers>()
+        .pending
+        .remove(&args.id);
+
+    Ok(serde_json::json!(fired))
+}
+
+/// Cancels a pending timer. Can be called from the JsRuntime using
+/// `Deno.core.jsonOpSync("timer_cancel", {id})`.
+pub(crate) fn op_timer_cancel(
+    state: &mut OpState,
+    args: Value,
+    _bufs: &mut [ZeroCopyBuf],
+) -> Result<Value, AnyError> {
+    let args: TimerArgs = serde_json::from_value(args)?;
+    let timers = state.borrow_mut::<Timers>();
+    match timers.pending.remove(&args.id) {
+        // the timer might have fired in the meantime
+        Some(cancel_tx) => {
+            let _ = cancel_tx.send(());
+        }
+        None => {
+            timers.cancelled.insert(args.id);
+        }
+    }
+
+    Ok(serde_json::json!(()))
+}
+
+/// Represents `crypto.randomUUID()`. Can be called from the JsRuntime using
+/// `Deno.core.jsonOpSync("random_uuid", {})`.
+pub(crate) fn op_random_uuid(
+    _state: &mut OpState,
+    _args: Value,
+    _bufs: &mut [ZeroCopyBuf],
+) -> Result<Value, AnyError> {
+    Ok(serde_json::json!(Uuid::new_v4().to_string()))
+}
+
+/// Represents `crypto.getRandomValues()`. Can be called from the JsRuntime
+/// using `Deno.core.jsonOpSync("get_random_values", {}, bytes)` and fills the
+/// given buffer with random bytes.
+pub(crate) fn op_get_random_values(
+    _state: &mut OpState,
+    _args: Value,
+    bufs: &mut [ZeroCopyBuf],
+) -> Result<Value, AnyError> {
+    let buf = bufs.get_mut(0).ok_or(RequestProcessorError::Custom(
+        "No buffer given for getRandomValues".to_string(),
+    ))?;
+    if buf.len() > MAX_RANDOM_BYTES {
+        return Err(RequestProcessorError::Custom(format!(
+            "getRandomValues is limited to {} bytes",
+            MAX_RANDOM_BYTES
+        ))
+        .into());
+    }
+
+    rand::thread_rng().fill_bytes(&mut buf[..]);
+
+    Ok(serde_json::json!(()))
+}
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS processor_kv (
+    request_processor TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,         -- JSON string
+    created_at TEXT NOT NULL,    -- RFC3339 string
+    updated_at TEXT NOT NULL,    -- RFC3339 string
+    expires_at TEXT NULL,        -- RFC3339 string, NULL means no expiry
+
+    PRIMARY KEY(request_processor, key),
+    FOREIGN KEY(request_processor) REFERENCES request_processor(id) ON DELETE CASCADE
+);
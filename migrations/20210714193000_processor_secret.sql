@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS processor_secret (
+    request_processor TEXT NOT NULL,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,         -- base64 encoded AES-256-GCM ciphertext
+    nonce TEXT NOT NULL,         -- base64 encoded 96 bit nonce
+    created_at TEXT NOT NULL,    -- RFC3339 string
+    updated_at TEXT NOT NULL,    -- RFC3339 string
+
+    PRIMARY KEY(request_processor, name),
+    FOREIGN KEY(request_processor) REFERENCES request_processor(id) ON DELETE CASCADE
+);
@@ -0,0 +1 @@
+ALTER TABLE request_processor ADD COLUMN config TEXT NOT NULL DEFAULT '{}'; -- JSON object
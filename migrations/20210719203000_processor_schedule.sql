@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS processor_schedule (
+    id TEXT PRIMARY KEY NOT NULL,
+    request_processor TEXT NOT NULL,
+    cron TEXT NOT NULL,
+    enabled INTEGER NOT NULL,    -- boolean
+    created_at TEXT NOT NULL,    -- RFC3339 string
+    last_run_at TEXT NULL,       -- RFC3339 string
+
+    FOREIGN KEY(request_processor) REFERENCES request_processor(id) ON DELETE CASCADE
+);
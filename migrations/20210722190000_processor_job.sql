@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS processor_job (
+    id TEXT PRIMARY KEY NOT NULL,
+    request_processor TEXT NOT NULL,
+    request_conversation TEXT NOT NULL,
+    prelude INTEGER NOT NULL,    -- boolean
+    request TEXT NOT NULL,       -- JSON string
+    status TEXT NOT NULL,        -- one of queued, running, succeeded, failed
+    attempts INTEGER NOT NULL,
+    created_at TEXT NOT NULL,    -- RFC3339 string
+    updated_at TEXT NOT NULL,    -- RFC3339 string
+    error TEXT NULL,
+
+    FOREIGN KEY(request_processor) REFERENCES request_processor(id) ON DELETE CASCADE
+    FOREIGN KEY(request_conversation) REFERENCES request_conversation(id)
+);
+
+CREATE INDEX IF NOT EXISTS processor_job_status ON processor_job(status, created_at);
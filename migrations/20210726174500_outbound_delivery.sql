@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS outbound_delivery (
+    id TEXT PRIMARY KEY NOT NULL,
+    request_processor TEXT NOT NULL,
+    request_conversation TEXT NOT NULL,
+    inc INTEGER NOT NULL,          -- increment of the request in the conversation
+    url TEXT NOT NULL,
+    request TEXT NOT NULL,         -- JSON string
+    status TEXT NOT NULL,          -- one of pending, delivered, dead
+    attempts INTEGER NOT NULL,
+    max_attempts INTEGER NOT NULL,
+    backoff_ms INTEGER NOT NULL,
+    next_attempt_at TEXT NOT NULL, -- RFC3339 string, fixed precision for comparisons
+    created_at TEXT NOT NULL,      -- RFC3339 string
+    updated_at TEXT NOT NULL,      -- RFC3339 string
+    last_error TEXT NULL,
+
+    FOREIGN KEY(request_processor) REFERENCES request_processor(id) ON DELETE CASCADE
+    FOREIGN KEY(request_conversation) REFERENCES request_conversation(id)
+);
+
+CREATE INDEX IF NOT EXISTS outbound_delivery_due ON outbound_delivery(status, next_attempt_at);
@@ -0,0 +1,5 @@
+ALTER TABLE request_conversation ADD COLUMN status TEXT NOT NULL DEFAULT 'running'; -- one of running, succeeded, failed, timed_out
+ALTER TABLE request_conversation ADD COLUMN finished_at TEXT NULL;   -- RFC3339 string
+ALTER TABLE request_conversation ADD COLUMN duration_ms INTEGER NULL;
+ALTER TABLE request_conversation ADD COLUMN status_code INTEGER NULL; -- HTTP status code of the final response
+ALTER TABLE request_conversation ADD COLUMN error TEXT NULL;
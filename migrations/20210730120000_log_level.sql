@@ -0,0 +1,4 @@
+ALTER TABLE conversation_audit_item ADD COLUMN level TEXT NULL;  -- only for kind log: one of debug, info, warn, error
+ALTER TABLE conversation_audit_item ADD COLUMN fields TEXT NULL; -- only for kind log: JSON string
+
+ALTER TABLE request_processor ADD COLUMN min_log_level TEXT NOT NULL DEFAULT 'debug';
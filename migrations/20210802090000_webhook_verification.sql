@@ -0,0 +1 @@
+ALTER TABLE request_processor ADD COLUMN webhook_verification TEXT NULL; -- JSON object, NULL if incoming requests are not verified
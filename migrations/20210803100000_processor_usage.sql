@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS processor_usage (
+    request_processor TEXT NOT NULL,
+    day TEXT NOT NULL,                          -- YYYY-MM-DD, UTC
+    invocations INTEGER NOT NULL DEFAULT 0,
+    cpu_ms INTEGER NOT NULL DEFAULT 0,          -- CPU time spent executing code in V8
+    outbound_requests INTEGER NOT NULL DEFAULT 0,
+    outbound_bytes INTEGER NOT NULL DEFAULT 0,  -- sent and received bodies
+    audit_bytes INTEGER NOT NULL DEFAULT 0,     -- stored audit item payloads
+
+    PRIMARY KEY(request_processor, day),
+    FOREIGN KEY(request_processor) REFERENCES request_processor(id) ON DELETE CASCADE
+);
+
+-- running total of audit_bytes, so quota checks don't have to sum up the days
+CREATE TABLE IF NOT EXISTS processor_storage (
+    request_processor TEXT PRIMARY KEY NOT NULL,
+    storage_bytes INTEGER NOT NULL DEFAULT 0,
+
+    FOREIGN KEY(request_processor) REFERENCES request_processor(id) ON DELETE CASCADE
+);
+
+ALTER TABLE request_processor ADD COLUMN quota TEXT NULL; -- JSON object, NULL if the usage is not limited
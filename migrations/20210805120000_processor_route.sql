@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS processor_route (
+    id TEXT PRIMARY KEY NOT NULL,
+    request_processor TEXT NOT NULL,
+    host TEXT NULL,              -- lowercase, without port, NULL matches any host
+    path TEXT NOT NULL,          -- pattern like /hooks/ttn/{device}
+    methods TEXT NOT NULL,       -- JSON array, empty matches any method
+    prelude INTEGER NOT NULL,    -- boolean
+    created_at TEXT NOT NULL,    -- RFC3339 string
+
+    FOREIGN KEY(request_processor) REFERENCES request_processor(id) ON DELETE CASCADE
+);
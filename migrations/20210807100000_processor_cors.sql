@@ -0,0 +1 @@
+ALTER TABLE request_processor ADD COLUMN cors TEXT NULL; -- JSON object, NULL if cross-origin requests are not handled
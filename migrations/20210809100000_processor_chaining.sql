@@ -0,0 +1,8 @@
+ALTER TABLE request_conversation ADD COLUMN parent TEXT NULL REFERENCES request_conversation(id); -- invoking conversation of nested invocations and pipeline steps
+
+CREATE TABLE IF NOT EXISTS pipeline (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    steps TEXT NOT NULL,         -- JSON array of steps, run in order
+    created_at TEXT NOT NULL     -- RFC3339 string
+);
@@ -1,4 +1,6 @@
+import shlex
 import tempfile
+from typing import Dict, Optional
 
 import pytest
 from fastapi.testclient import TestClient
@@ -11,16 +13,23 @@ from tests.util import ApiClient
 
 class FlowHeaterLayer(ServerLayer):
     """
-    Basic ServerLayer which runs the rust core binary.
+    Basic ServerLayer which runs the rust core binary. The optional `env`
+    overrides settings of the environment, e.g. to run a second core with a
+    different configuration.
     """
 
-    def __init__(self, config: Config):
+    def __init__(
+        self, config: Config, name="fh-core", env: Optional[Dict[str, str]] = None
+    ):
+        env = env or {}
+        port = env.get("CORE_PORT", config.core.port)
+        settings = "".join(f"{key}={shlex.quote(value)} " for key, value in env.items())
         stdout = tempfile.NamedTemporaryFile(mode="w+")
         stderr = tempfile.NamedTemporaryFile(mode="w+")
         super(FlowHeaterLayer, self).__init__(
-            name="fh-core",
-            servers=[f"{config.core.host}:{config.core.port}"],
-            start_cmd="cargo run --bin fh-http",
+            name=name,
+            servers=[f"{config.core.host}:{port}"],
+            start_cmd=f"{settings}cargo run --bin fh-http",
             stdout=stdout,
             stderr=stderr,
         )
@@ -108,3 +108,73 @@ def test_not_found_processor(api_client: ApiClient):
     assert 404 == resp.status_code
     assert data["code"] == 404
     assert f"with id {id} not found" in data["message"]
+
+
+@pytest.mark.admin
+def test_processor_config(api_client: ApiClient):
+    rp_id = api_client.create_processor("my fun code", config={"target": "hiveeyes"})
+
+    response_get = api_client.http_client.get(f"/admin/processor/{rp_id}/config")
+    assert {"target": "hiveeyes"} == response_get.json()
+
+    response_put = api_client.http_client.put(
+        f"/admin/processor/{rp_id}/config", json={"target": "beep", "retries": 3}
+    )
+    assert 200 == response_put.status_code
+    assert {"target": "beep", "retries": 3} == response_put.json()
+
+    response_proc = api_client.http_client.get(f"/admin/processor/{rp_id}")
+    data = response_proc.json()
+    assert {"target": "beep", "retries": 3} == data["config"]
+    assert "my fun code" == data["code"]
+
+    # updating the processor without a config keeps the existing one
+    data.pop("config")
+    data["code"] = "my other code"
+    response_update = api_client.http_client.put(f"/admin/processor/{rp_id}", json=data)
+    assert 200 == response_update.status_code
+    assert {"target": "beep", "retries": 3} == response_update.json()["config"]
+
+    # the config has to be a JSON object
+    response_invalid = api_client.http_client.put(
+        f"/admin/processor/{rp_id}/config", json=["no", "object"]
+    )
+    assert 400 == response_invalid.status_code
+
+
+UPDATED_FIELDS = [
+    ("min_log_level", "warn"),
+    ("webhook_verification", {"provider": "github", "secret": "GITHUB_SECRET"}),
+    ("quota", {"daily_invocations": 10}),
+    ("cors", {"allowed_origins": ["https://app.example.com"]}),
+]
+
+
+@pytest.mark.admin
+@pytest.mark.parametrize("name, value", UPDATED_FIELDS)
+def test_update_keeps_omitted_fields(api_client: ApiClient, name: str, value):
+    rp = RequestProcessor(
+        id=None, name="testing", runtime="v8", language="javascript", code="my code"
+    )
+    setattr(rp, name, value)
+    data = api_client.create_request_processor(rp).json()
+    stored = data[name]
+
+    body = {key: data[key] for key in ["name", "runtime", "language", "code"]}
+    body["code"] = "my other code"
+    response = api_client.http_client.put(f"/admin/processor/{data['id']}", json=body)
+    assert 200 == response.status_code
+    assert "my other code" == response.json()["code"]
+    assert stored == response.json()[name]
+
+    response_get = api_client.http_client.get(f"/admin/processor/{data['id']}")
+    assert stored == response_get.json()[name]
+
+    # optional policies are removed with an explicit null
+    if name != "min_log_level":
+        body[name] = None
+        response = api_client.http_client.put(
+            f"/admin/processor/{data['id']}", json=body
+        )
+        assert 200 == response.status_code
+        assert response.json()[name] is None
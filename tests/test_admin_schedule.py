@@ -0,0 +1,71 @@
+import time
+
+import pytest
+
+from tests.util import ApiClient, wrap_with_async_main
+
+
+@pytest.mark.admin
+def test_create_list_delete_schedule(api_client: ApiClient):
+    rp_id = api_client.create_processor("my fun code")
+
+    response = api_client.http_client.post(
+        f"/admin/processor/{rp_id}/schedule", json={"cron": "0 */5 * * * *"}
+    )
+    assert 200 == response.status_code
+    data = response.json()
+    assert data["request_processor_id"] == rp_id
+    assert data["enabled"] is True
+    assert data["last_run_at"] is None
+
+    response_list = api_client.http_client.get(f"/admin/processor/{rp_id}/schedule")
+    assert [data["id"]] == [s["id"] for s in response_list.json()]
+
+    response_delete = api_client.http_client.delete(f"/admin/schedule/{data['id']}")
+    assert 200 == response_delete.status_code
+
+    response_delete = api_client.http_client.delete(f"/admin/schedule/{data['id']}")
+    assert 404 == response_delete.status_code
+
+
+@pytest.mark.admin
+def test_invalid_cron_expression(api_client: ApiClient):
+    rp_id = api_client.create_processor("my fun code")
+
+    response = api_client.http_client.post(
+        f"/admin/processor/{rp_id}/schedule", json={"cron": "every full moon"}
+    )
+    assert 400 == response.status_code
+
+
+@pytest.mark.admin
+def test_schedule_for_unknown_processor(api_client: ApiClient):
+    rp_id = "8a2e00e9-c710-4337-b717-bdcad0396df5"
+
+    response = api_client.http_client.post(
+        f"/admin/processor/{rp_id}/schedule", json={"cron": "* * * * * *"}
+    )
+    assert 404 == response.status_code
+
+
+def test_scheduled_runs(api_client: ApiClient):
+    code = wrap_with_async_main(
+        """
+        const count = ((await fh.kv.get("runs")) || 0) + 1;
+        await fh.kv.set("runs", count);
+        await fh.log(`${count}`);
+        """
+    )
+    rp_id = api_client.create_processor(code)
+    response = api_client.http_client.post(
+        f"/admin/processor/{rp_id}/schedule", json={"cron": "* * * * * *"}
+    )
+    schedule_id = response.json()["id"]
+
+    time.sleep(3)
+    api_client.http_client.delete(f"/admin/schedule/{schedule_id}")
+
+    # the manual run sees the counter of the scheduled runs
+    response = api_client.run_processor(rp_id)
+    conversation = api_client.get_conversation_from_response(response)
+    assert int(conversation.audit_items[1].payload) > 1
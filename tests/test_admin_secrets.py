@@ -0,0 +1,69 @@
+import pytest
+
+from tests.util import ApiClient, wrap_with_async_main
+
+
+@pytest.mark.admin
+def test_set_and_list_secrets(api_client: ApiClient):
+    rp_id = api_client.create_processor("my fun code")
+
+    response = api_client.set_processor_secret(rp_id, "API_KEY", "s3cr3t-value")
+    assert 200 == response.status_code
+    data = response.json()
+    assert "API_KEY" == data["name"]
+    assert "value" not in data
+
+    response_list = api_client.http_client.get(f"/admin/processor/{rp_id}/secret")
+    assert 200 == response_list.status_code
+    assert ["API_KEY"] == [s["name"] for s in response_list.json()]
+    assert "s3cr3t-value" not in response_list.text
+
+    # the processor itself never exposes secret values
+    response_get = api_client.http_client.get(f"/admin/processor/{rp_id}")
+    assert "s3cr3t-value" not in response_get.text
+
+
+@pytest.mark.admin
+def test_delete_secret(api_client: ApiClient):
+    rp_id = api_client.create_processor("my fun code")
+    api_client.set_processor_secret(rp_id, "API_KEY", "s3cr3t-value")
+
+    response = api_client.http_client.delete(f"/admin/processor/{rp_id}/secret/API_KEY")
+    assert 200 == response.status_code
+
+    response = api_client.http_client.delete(f"/admin/processor/{rp_id}/secret/API_KEY")
+    assert 404 == response.status_code
+
+    response_list = api_client.http_client.get(f"/admin/processor/{rp_id}/secret")
+    assert [] == response_list.json()
+
+
+@pytest.mark.admin
+def test_invalid_secret_name(api_client: ApiClient):
+    rp_id = api_client.create_processor("my fun code")
+    response = api_client.set_processor_secret(rp_id, "not%20valid", "x")
+    assert 400 == response.status_code
+
+
+def test_secret_is_redacted_from_audit_items(api_client: ApiClient):
+    code = wrap_with_async_main(
+        """
+        const key = fh.secret("API_KEY");
+        await fh.log(`Using key ${key}`);
+        """
+    )
+    rp_id = api_client.create_processor(code)
+    api_client.set_processor_secret(rp_id, "API_KEY", "s3cr3t-value")
+
+    response = api_client.run_processor(rp_id, headers={"x-api-key": "s3cr3t-value"})
+    assert 200 == response.status_code
+
+    conversation = api_client.get_conversation_from_response(response)
+    assert 2 == len(conversation.audit_items)
+    assert ["[REDACTED]"] == conversation.audit_items[0].payload["headers"]["x-api-key"]
+    assert "Using key [REDACTED]" == conversation.audit_items[1].payload
+
+
+def test_undefined_secret(api_client: ApiClient):
+    response = api_client.execute('fh.secret("DOES_NOT_EXIST");')
+    assert 500 == response.status_code
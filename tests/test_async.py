@@ -0,0 +1,35 @@
+import time
+
+from tests.util import ApiClient, wrap_with_async_main
+
+
+def test_async_invocation(api_client: ApiClient):
+    code = wrap_with_async_main(
+        """
+        await fh.log("processed in background");
+        """
+    )
+    rp_id = api_client.create_processor(code)
+
+    response = api_client.run_processor(rp_id, headers={"Prefer": "respond-async"})
+    assert 202 == response.status_code
+    data = response.json()
+    assert data["status"] == "queued"
+    assert data["conversation_id"] == response.headers["fh-conversation-id"]
+
+    payloads = []
+    for _ in range(20):
+        conversation = api_client.get_conversation_from_response(response)
+        payloads = [item.payload for item in conversation.audit_items]
+        if len(payloads) > 1:
+            break
+        time.sleep(0.25)
+
+    assert "processed in background" == payloads[1]
+
+
+def test_async_invocation_unknown_processor(api_client: ApiClient):
+    rp_id = "8a2e00e9-c710-4337-b717-bdcad0396df5"
+
+    response = api_client.run_processor(rp_id, headers={"Prefer": "respond-async"})
+    assert 404 == response.status_code
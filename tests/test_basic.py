@@ -1,5 +1,3 @@
-import json
-
 from tests.util import ApiClient
 
 
@@ -21,4 +19,4 @@ def test_spike(
 
     # Check Log entries
     assert "log" == conversation.audit_items[1].kind
-    assert "DENO: Got request body" in json.loads(conversation.audit_items[1].payload)
+    assert "DENO: Got request body" in conversation.audit_items[1].payload
@@ -0,0 +1,269 @@
+import json
+from typing import Dict, List
+
+import pytest
+import requests
+
+from tests.util import ApiClient, RequestProcessor, wrap_with_async_main
+
+UPPERCASE = wrap_with_async_main(
+    """
+    await fh.respond_with({
+        code: 200,
+        headers: {"content-type": ["application/json"]},
+        body: JSON.stringify({text: request.json.text.toUpperCase()}),
+        version: "HTTP/1.1"
+    });
+    """
+)
+
+EXCLAIM = wrap_with_async_main(
+    """
+    await fh.respond_with({
+        code: 200,
+        headers: {"content-type": ["application/json"]},
+        body: JSON.stringify({text: `${request.json.text}!`}),
+        version: "HTTP/1.1"
+    });
+    """
+)
+
+FAILING = wrap_with_async_main('throw new Error("step failed");')
+
+
+def invoking(processor_id: str) -> str:
+    return wrap_with_async_main(
+        f"""
+        const response = await fh.invoke("{processor_id}", {{body: {{text: "hi"}}}});
+        await fh.respond_with(response);
+        """
+    )
+
+
+def create_checked_processor(api_client: ApiClient, check: Dict) -> str:
+    """
+    Creates a processor, whose incoming requests are checked by the server,
+    e.g. with `{"webhook_verification": {...}}`.
+    """
+    rp = RequestProcessor(
+        id=None,
+        name="testing-checked",
+        runtime="v8",
+        language="javascript",
+        code=UPPERCASE,
+        **check,
+    )
+    return api_client.create_request_processor(rp).json()["id"]
+
+
+# signatures are only verified for requests by clients
+WEBHOOK_VERIFICATION = {
+    "webhook_verification": {"provider": "github", "secret": "signing"}
+}
+# CORS policies only restrict browsers, so they don't prevent chaining
+CORS = {"cors": {"allowed_origins": ["https://app.example.com"]}}
+
+
+def create_pipeline(api_client: ApiClient, steps: List[Dict]) -> requests.Response:
+    return api_client.http_client.post(
+        "/admin/pipeline", json={"name": "testing", "steps": steps}
+    )
+
+
+def run_pipeline(api_client: ApiClient, identifier: str) -> requests.Response:
+    return api_client.http_client.post(
+        f"/pipeline/{identifier}/run", json={"text": "hello"}
+    )
+
+
+def test_invoke(api_client: ApiClient):
+    child = api_client.create_processor(UPPERCASE)
+    parent = api_client.create_processor(invoking(child))
+
+    response = api_client.run_processor(parent)
+    assert 200 == response.status_code
+    assert {"text": "HI"} == json.loads(response.json()["body"])
+
+    conversation = api_client.get_conversation_from_response(response)
+    assert conversation.parent_id is None
+    assert 1 == len(conversation.children)
+    kinds = [item.kind for item in conversation.audit_items]
+    assert ["request", "request", "response"] == kinds
+
+    child_conversation, _ = api_client.get_request_conversation(
+        conversation.children[0]
+    )
+    assert child == child_conversation.request_processor_id
+    assert conversation.id == child_conversation.parent_id
+    assert "succeeded" == child_conversation.status
+
+
+def test_invoke_failing_processor(api_client: ApiClient):
+    child = api_client.create_processor(FAILING)
+    parent = api_client.create_processor(invoking(child))
+
+    response = api_client.run_processor(parent)
+    assert 500 == response.status_code
+
+    conversation = api_client.get_conversation_from_response(response)
+    child_id = conversation.children[0]
+    assert child_id in response.json()["message"]
+
+    child_conversation, _ = api_client.get_request_conversation(child_id)
+    assert "failed" == child_conversation.status
+
+
+def test_invoke_depth_limit(api_client: ApiClient):
+    # the processor invokes itself, until the depth limit is reached
+    code = wrap_with_async_main(
+        """
+        await fh.invoke(request.processor_id, {});
+        """
+    )
+    identifier = api_client.create_processor(code)
+
+    response = api_client.run_processor(identifier)
+    assert 500 == response.status_code
+    assert "nested at most 5 levels" in response.json()["message"]
+
+
+def test_invoke_rejects_checked_processor(api_client: ApiClient):
+    # chained invocations would bypass the checks of incoming requests
+    child = create_checked_processor(api_client, WEBHOOK_VERIFICATION)
+    parent = api_client.create_processor(invoking(child))
+
+    response = api_client.run_processor(parent)
+    assert 500 == response.status_code
+    assert "can only be run by clients" in response.json()["message"]
+
+    conversation = api_client.get_conversation_from_response(response)
+    child_conversation, _ = api_client.get_request_conversation(
+        conversation.children[0]
+    )
+    assert "failed" == child_conversation.status
+
+
+def test_invoke_processor_with_cors_policy(api_client: ApiClient):
+    child = create_checked_processor(api_client, CORS)
+    parent = api_client.create_processor(invoking(child))
+
+    response = api_client.run_processor(parent)
+    assert 200 == response.status_code
+    assert {"text": "HI"} == json.loads(response.json()["body"])
+
+
+@pytest.mark.admin
+def test_pipeline(api_client: ApiClient):
+    first = api_client.create_processor(UPPERCASE)
+    second = api_client.create_processor(EXCLAIM)
+
+    response = create_pipeline(
+        api_client,
+        [{"request_processor_id": first}, {"request_processor_id": second}],
+    )
+    assert 200 == response.status_code
+    pipeline = response.json()
+    defaults = {"prelude": True, "retries": 0, "on_error": "abort"}
+    assert [
+        {"request_processor_id": first, **defaults},
+        {"request_processor_id": second, **defaults},
+    ] == pipeline["steps"]
+
+    response = run_pipeline(api_client, pipeline["id"])
+    assert 200 == response.status_code
+    assert pipeline["id"] == response.headers["fh-pipeline-id"]
+    assert {"text": "HELLO!"} == json.loads(response.json()["body"])
+
+    # the conversations of the steps form a chain
+    conversation = api_client.get_conversation_from_response(response)
+    assert first == conversation.request_processor_id
+    assert conversation.parent_id is None
+    child, _ = api_client.get_request_conversation(conversation.children[0])
+    assert second == child.request_processor_id
+    assert conversation.id == child.parent_id
+
+
+@pytest.mark.admin
+@pytest.mark.parametrize("on_error,status_code", [("abort", 500), ("continue", 200)])
+def test_pipeline_on_error(api_client: ApiClient, on_error: str, status_code: int):
+    failing = api_client.create_processor(FAILING)
+    last = api_client.create_processor(EXCLAIM)
+
+    steps = [
+        {"request_processor_id": failing, "retries": 1, "on_error": on_error},
+        {"request_processor_id": last},
+    ]
+    identifier = create_pipeline(api_client, steps).json()["id"]
+
+    response = run_pipeline(api_client, identifier)
+    assert status_code == response.status_code
+    if on_error == "continue":
+        # the skipped step passes its input on
+        assert {"text": "hello!"} == json.loads(response.json()["body"])
+
+        # both attempts of the failing step are recorded
+        conversation = api_client.get_conversation_from_response(response)
+        retry, _ = api_client.get_request_conversation(conversation.children[0])
+        assert ["failed", "failed"] == [conversation.status, retry.status]
+        assert failing == retry.request_processor_id
+
+
+@pytest.mark.admin
+@pytest.mark.parametrize(
+    "steps",
+    [
+        [],
+        [{"request_processor_id": "00000000-0000-0000-0000-000000000000"}],
+        [{"request_processor_id": None, "retries": 11}],
+        [{"request_processor_id": None, "on_error": "ignore"}],
+    ],
+)
+def test_invalid_pipeline(api_client: ApiClient, steps: List[Dict]):
+    identifier = api_client.create_processor(UPPERCASE)
+    for step in steps:
+        if step["request_processor_id"] is None:
+            step["request_processor_id"] = identifier
+
+    response = create_pipeline(api_client, steps)
+    assert 400 == response.status_code
+
+
+@pytest.mark.admin
+def test_pipeline_rejects_checked_processor(api_client: ApiClient):
+    identifier = create_checked_processor(api_client, WEBHOOK_VERIFICATION)
+
+    response = create_pipeline(api_client, [{"request_processor_id": identifier}])
+    assert 400 == response.status_code
+    assert "can only be run by clients" in response.json()["message"]
+
+
+def test_pipeline_with_cors_policy(api_client: ApiClient):
+    identifier = create_checked_processor(api_client, CORS)
+
+    response = create_pipeline(api_client, [{"request_processor_id": identifier}])
+    assert 200 == response.status_code
+
+    response = run_pipeline(api_client, response.json()["id"])
+    assert 200 == response.status_code
+    assert {"text": "HELLO"} == json.loads(response.json()["body"])
+
+
+@pytest.mark.admin
+def test_update_and_delete_pipeline(api_client: ApiClient):
+    first = api_client.create_processor(UPPERCASE)
+    second = api_client.create_processor(EXCLAIM)
+    pipeline = create_pipeline(api_client, [{"request_processor_id": first}]).json()
+    url = f"/admin/pipeline/{pipeline['id']}"
+
+    body = {"name": "renamed", "steps": [{"request_processor_id": second}]}
+    response = api_client.http_client.put(url, json=body)
+    assert 200 == response.status_code
+    assert "renamed" == response.json()["name"]
+    assert pipeline["created_at"] == response.json()["created_at"]
+
+    response = api_client.http_client.get(url)
+    assert second == response.json()["steps"][0]["request_processor_id"]
+
+    assert 200 == api_client.http_client.delete(url).status_code
+    assert 404 == api_client.http_client.get(url).status_code
+    assert 404 == run_pipeline(api_client, pipeline["id"]).status_code
@@ -0,0 +1,64 @@
+from tests.util import ApiClient, wrap_with_async_main
+
+
+def get_logs(api_client: ApiClient, response):
+    conversation = api_client.get_conversation_from_response(response)
+    return [item for item in conversation.audit_items if item.kind == "log"]
+
+
+def test_console_without_prelude(api_client: ApiClient):
+    response = api_client.execute(
+        """
+        console.log("plain", 42, { name: "fh", list: [1, "two"] });
+        console.warn("careful");
+        console.error(new Map([["key", null]]));
+        console.debug("%s has %d items%c", "list", "3", "color: red");
+        """,
+        prelude=False,
+    )
+    assert 200 == response.status_code
+
+    logs = get_logs(api_client, response)
+    assert [
+        ("info", 'plain 42 { name: "fh", list: [ 1, "two" ] }'),
+        ("warn", "careful"),
+        ("error", 'Map(1) { "key" => null }'),
+        ("debug", "list has 3 items"),
+    ] == [(log.level, log.payload) for log in logs]
+
+
+def test_console_with_prelude(api_client: ApiClient):
+    response = api_client.execute(
+        """
+        const circular = { id: 1 };
+        circular.self = circular;
+
+        console.log(circular);
+        await fh.log("in between");
+        console.assert(1 === 2, "math is broken");
+        """
+    )
+    assert 200 == response.status_code
+
+    logs = get_logs(api_client, response)
+    assert [
+        "{ id: 1, self: [Circular] }",
+        "in between",
+        "Assertion failed: math is broken",
+    ] == [log.payload for log in logs]
+
+
+def test_console_is_stored_on_failure(api_client: ApiClient):
+    rp_id = api_client.create_processor(
+        wrap_with_async_main(
+            """
+            console.log("before");
+            throw new Error("boom");
+            """
+        )
+    )
+    response = api_client.run_processor(rp_id)
+    assert 500 == response.status_code
+
+    logs = get_logs(api_client, response)
+    assert ["before"] == [log.payload for log in logs]
@@ -1,3 +1,4 @@
+import time
 from pathlib import Path
 
 from dateutil.parser import parse
@@ -22,6 +23,38 @@ def test_get_conversation(api_client: ApiClient):
     parse(conversation.created_at)
 
 
+def test_conversation_status_succeeded(api_client: ApiClient):
+    response = api_client.execute('await fh.log("done");')
+    conversation = api_client.get_conversation_from_response(response)
+
+    assert "succeeded" == conversation.status
+    assert 200 == conversation.status_code
+    assert conversation.duration_ms >= 0
+    assert parse(conversation.finished_at) >= parse(conversation.created_at)
+    assert conversation.error is None
+
+
+def test_conversation_status_failed(api_client: ApiClient):
+    rp_id = api_client.create_processor('throw new Error("boom");')
+
+    # run asynchronously, to get hold of the conversation id
+    response = api_client.run_processor(
+        rp_id, prelude=False, headers={"Prefer": "respond-async"}
+    )
+    assert 202 == response.status_code
+
+    for _ in range(20):
+        conversation = api_client.get_conversation_from_response(response)
+        if conversation.status != "running":
+            break
+        time.sleep(0.25)
+
+    assert "failed" == conversation.status
+    assert conversation.status_code is None
+    assert "boom" in conversation.error
+    assert conversation.finished_at is not None
+
+
 def test_audit_item_logging(api_client: ApiClient):
     response = api_client.execute(basedir / "audit-item-logging.js", prelude=True)
 
@@ -32,8 +65,8 @@ def test_audit_item_logging(api_client: ApiClient):
         conversation_id
     )
     assert 3 == len(conversation.audit_items)
-    assert '"Hello, World"' == conversation.audit_items[1].payload
-    assert '"Body is: "' == conversation.audit_items[2].payload
+    assert "Hello, World" == conversation.audit_items[1].payload
+    assert "Body is: " == conversation.audit_items[2].payload
 
 
 def test_audit_item_request(api_client: ApiClient):
@@ -0,0 +1,148 @@
+from dataclasses import asdict
+from typing import Dict, Optional
+
+import pytest
+
+from tests.util import ApiClient, RequestProcessor, wrap_with_async_main
+
+CODE = wrap_with_async_main('await fh.log("hello");')
+
+ORIGIN = "https://app.example.com"
+
+POLICY = {
+    "allowed_origins": [ORIGIN],
+    "allowed_methods": ["GET", "POST"],
+    "allowed_headers": ["content-type"],
+    "expose_headers": ["x-request-id"],
+    "max_age_secs": 600,
+}
+
+
+def processor(cors: Optional[Dict]) -> RequestProcessor:
+    return RequestProcessor(
+        id=None,
+        name="testing-cors",
+        runtime="v8",
+        language="javascript",
+        code=CODE,
+        cors=cors,
+    )
+
+
+def create_processor(api_client: ApiClient, cors: Optional[Dict]) -> str:
+    return api_client.create_request_processor(processor(cors)).json()["id"]
+
+
+def preflight(
+    api_client: ApiClient,
+    identifier: str,
+    origin: str = ORIGIN,
+    headers: Optional[Dict] = None,
+):
+    return api_client.http_client.options(
+        f"/processor/{identifier}/run_with_prelude",
+        headers={
+            "origin": origin,
+            "access-control-request-method": "POST",
+            **(headers or {}),
+        },
+    )
+
+
+def test_preflight_is_answered_without_running_code(api_client: ApiClient):
+    identifier = create_processor(api_client, POLICY)
+
+    response = preflight(
+        api_client,
+        identifier,
+        headers={"access-control-request-headers": "Content-Type"},
+    )
+    assert 204 == response.status_code
+    assert ORIGIN == response.headers["access-control-allow-origin"]
+    assert "POST" == response.headers["access-control-allow-methods"]
+    assert "Content-Type" == response.headers["access-control-allow-headers"]
+    assert "600" == response.headers["access-control-max-age"]
+    assert "Origin" in response.headers["vary"]
+    assert "fh-conversation-id" not in response.headers
+
+    usage = api_client.http_client.get(f"/admin/processor/{identifier}/usage")
+    assert [] == usage.json()["days"]
+
+
+def test_cors_headers_on_response(api_client: ApiClient):
+    identifier = create_processor(api_client, POLICY)
+
+    response = api_client.run_processor(
+        identifier, method="post", headers={"origin": ORIGIN}
+    )
+    assert 200 == response.status_code
+    assert ORIGIN == response.headers["access-control-allow-origin"]
+    assert "access-control-allow-credentials" not in response.headers
+
+    exposed = response.headers["access-control-expose-headers"].lower()
+    assert "fh-conversation-id, x-request-id" == exposed
+
+    # requests without origin are not affected
+    response = api_client.run_processor(identifier, method="post")
+    assert 200 == response.status_code
+    assert "access-control-allow-origin" not in response.headers
+
+
+def test_any_origin(api_client: ApiClient):
+    identifier = create_processor(api_client, {"allowed_origins": ["*"]})
+
+    response = preflight(api_client, identifier, origin="https://other.example.com")
+    assert 204 == response.status_code
+    assert "*" == response.headers["access-control-allow-origin"]
+
+
+@pytest.mark.parametrize(
+    "origin,headers",
+    [
+        ("https://evil.example.com", {}),
+        (ORIGIN, {"access-control-request-method": "DELETE"}),
+        (ORIGIN, {"access-control-request-headers": "x-secret"}),
+    ],
+)
+def test_preflight_is_rejected(api_client: ApiClient, origin: str, headers: Dict):
+    identifier = create_processor(api_client, POLICY)
+
+    response = preflight(api_client, identifier, origin=origin, headers=headers)
+    assert 403 == response.status_code
+    assert "access-control-allow-origin" not in response.headers
+
+
+def test_request_from_other_origin_is_rejected(api_client: ApiClient):
+    identifier = create_processor(api_client, POLICY)
+
+    response = api_client.run_processor(
+        identifier, method="post", headers={"origin": "https://evil.example.com"}
+    )
+    assert 403 == response.status_code
+    assert "fh-conversation-id" not in response.headers
+
+
+def test_without_policy_preflight_runs_code(api_client: ApiClient):
+    identifier = create_processor(api_client, None)
+
+    response = preflight(api_client, identifier)
+    assert 200 == response.status_code
+    assert "fh-conversation-id" in response.headers
+    assert "access-control-allow-origin" not in response.headers
+
+
+@pytest.mark.admin
+@pytest.mark.parametrize(
+    "cors",
+    [
+        {"allowed_origins": []},
+        {"allowed_origins": ["app.example.com"]},
+        {"allowed_origins": ["*"], "allow_credentials": True},
+        {"allowed_origins": ["*"], "allowed_methods": ["P O S T"]},
+    ],
+)
+def test_invalid_policy(api_client: ApiClient, cors: Dict):
+    response = api_client.http_client.post(
+        "/admin/processor", json=asdict(processor(cors))
+    )
+    assert 400 == response.status_code
@@ -0,0 +1,84 @@
+import hashlib
+import hmac
+
+from tests.util import ApiClient
+
+SECRET = "It's a Secret to Everybody"
+PAYLOAD = "Hello, World!"
+
+
+def get_log_payloads(api_client: ApiClient, response):
+    conversation = api_client.get_conversation_from_response(response)
+    return [item.payload for item in conversation.audit_items if item.kind == "log"]
+
+
+def test_digest(api_client: ApiClient):
+    response = api_client.execute(
+        """
+        const data = new TextEncoder().encode("Hello, World!");
+        for (const hash of ["SHA-1", "SHA-256", "SHA-512"]) {
+            const digest = new Uint8Array(await crypto.subtle.digest(hash, data));
+            console.log([...digest].map((b) => b.toString(16).padStart(2, "0")).join(""));
+        }
+        """
+    )
+    assert 200 == response.status_code
+
+    assert [
+        hashlib.sha1(PAYLOAD.encode()).hexdigest(),
+        hashlib.sha256(PAYLOAD.encode()).hexdigest(),
+        hashlib.sha512(PAYLOAD.encode()).hexdigest(),
+    ] == get_log_payloads(api_client, response)
+
+
+def test_verify_github_signature(api_client: ApiClient):
+    code = f"""
+        const encoder = new TextEncoder();
+        const key = await crypto.subtle.importKey(
+            "raw",
+            encoder.encode("{SECRET}"),
+            {{ name: "HMAC", hash: "SHA-256" }},
+            false,
+            ["sign", "verify"]
+        );
+
+        const header = request.headers["x-hub-signature-256"][0];
+        const signature = new Uint8Array(
+            header.slice("sha256=".length).match(/../g).map((h) => parseInt(h, 16))
+        );
+        const valid = await crypto.subtle.verify("HMAC", key, signature, encoder.encode(request.body));
+
+        const own = new Uint8Array(await crypto.subtle.sign("HMAC", key, encoder.encode(request.body)));
+        console.log(valid, own.length);
+    """
+    signature = hmac.new(SECRET.encode(), PAYLOAD.encode(), hashlib.sha256).hexdigest()
+
+    response = api_client.execute(
+        code,
+        method="post",
+        data=PAYLOAD,
+        headers={"X-Hub-Signature-256": f"sha256={signature}"},
+    )
+    assert ["true 32"] == get_log_payloads(api_client, response)
+
+    response = api_client.execute(
+        code,
+        method="post",
+        data=PAYLOAD,
+        headers={"X-Hub-Signature-256": f"sha256={'0' * 64}"},
+    )
+    assert ["false 32"] == get_log_payloads(api_client, response)
+
+
+def test_unsupported_algorithm(api_client: ApiClient):
+    response = api_client.execute(
+        """
+        try {
+            await crypto.subtle.digest("MD5", new Uint8Array([1]));
+        } catch (e) {
+            console.log(e.name);
+        }
+        """
+    )
+
+    assert ["NotSupportedError"] == get_log_payloads(api_client, response)
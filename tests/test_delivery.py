@@ -0,0 +1,107 @@
+import json
+import time
+
+import pytest
+
+from tests.util import ApiClient, wrap_with_async_main
+
+# nothing listens on this port, so every attempt fails
+UNREACHABLE_URL = "http://127.0.0.1:9/"
+
+
+def dispatch_with_retry(api_client: ApiClient, max_attempts: int):
+    code = f"""
+        const response = await fh.dispatch_request(
+            "{UNREACHABLE_URL}",
+            {{method: "POST", body: "data", headers: {{}}, path: "/", version: "HTTP/1.1"}},
+            {{retry: {{max_attempts: {max_attempts}, backoff_ms: 100}}}}
+        );
+        await fh.log(response);
+    """
+    response = api_client.execute(code)
+    conversation = api_client.get_conversation_from_response(response)
+    logged = json.loads(conversation.audit_items[-1].payload)
+    assert 202 == logged["code"]
+
+    return logged["headers"]["FH-Delivery-Id"][0], conversation
+
+
+def wait_for_status(api_client: ApiClient, delivery_id: str, status: str):
+    data = {}
+    for _ in range(20):
+        data = api_client.http_client.get(f"/admin/delivery/{delivery_id}").json()
+        if data["status"] == status:
+            break
+        time.sleep(0.25)
+
+    return data
+
+
+@pytest.mark.admin
+def test_failed_delivery_is_retried_until_dead(api_client: ApiClient):
+    delivery_id, conversation = dispatch_with_retry(api_client, max_attempts=3)
+
+    data = wait_for_status(api_client, delivery_id, "dead")
+    assert "dead" == data["status"]
+    assert 3 == data["attempts"]
+    assert data["last_error"]
+
+    # every attempt is recorded on the original conversation
+    conversation, _ = api_client.get_request_conversation(conversation.id)
+    requests = [item for item in conversation.audit_items if item.kind == "request"]
+    assert 1 + 3 == len(requests)
+    # with the increment of the original request
+    assert [data["inc"]] * 3 == [item.inc for item in requests[1:]]
+
+    response = api_client.http_client.get("/admin/delivery", params={"status": "dead"})
+    assert delivery_id in [d["id"] for d in response.json()]
+
+
+@pytest.mark.admin
+def test_retry_and_drop_delivery(api_client: ApiClient):
+    delivery_id, _ = dispatch_with_retry(api_client, max_attempts=1)
+    data = api_client.http_client.get(f"/admin/delivery/{delivery_id}").json()
+    assert "dead" == data["status"]
+
+    response = api_client.http_client.post(f"/admin/delivery/{delivery_id}/retry")
+    assert 200 == response.status_code
+    assert "pending" == response.json()["status"]
+
+    data = wait_for_status(api_client, delivery_id, "dead")
+    assert 2 == data["attempts"]
+
+    response = api_client.http_client.delete(f"/admin/delivery/{delivery_id}")
+    assert 200 == response.status_code
+
+    response = api_client.http_client.get(f"/admin/delivery/{delivery_id}")
+    assert 404 == response.status_code
+
+
+@pytest.mark.admin
+def test_delivery_secrets_are_redacted(api_client: ApiClient):
+    code = f"""
+        const response = await fh.dispatch_request(
+            "{UNREACHABLE_URL}?key=" + fh.secret("API_KEY"),
+            {{method: "POST", body: "data", headers: {{}}, path: "/", version: "HTTP/1.1"}},
+            {{
+                headers: {{"x-api-key": [fh.secret("API_KEY")]}},
+                retry: {{max_attempts: 1, backoff_ms: 100}},
+            }}
+        );
+        await fh.log(response);
+    """
+    rp_id = api_client.create_processor(wrap_with_async_main(code))
+    api_client.set_processor_secret(rp_id, "API_KEY", "s3cr3t-value")
+
+    response = api_client.run_processor(rp_id)
+    conversation = api_client.get_conversation_from_response(response)
+    logged = json.loads(conversation.audit_items[-1].payload)
+    delivery_id = logged["headers"]["FH-Delivery-Id"][0]
+
+    data = api_client.http_client.get(f"/admin/delivery/{delivery_id}").json()
+    listed = api_client.http_client.get("/admin/delivery").json()
+    listed = [d for d in listed if d["id"] == delivery_id]
+    assert 1 == len(listed)
+    for delivery in [data, *listed]:
+        assert "s3cr3t-value" not in json.dumps(delivery)
+        assert ["[REDACTED]"] == delivery["request"]["headers"]["x-api-key"]
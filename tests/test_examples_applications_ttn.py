@@ -1,7 +1,7 @@
 import json
 from pathlib import Path
 
-from tests.util import ApiClient
+from tests.util import ApiClient, read_code, wrap_with_async_main
 
 basedir = Path("examples/10-applications-ttn")
 
@@ -29,7 +29,7 @@ def test_ttn_to_hiveeyes(api_client: ApiClient):
 
     # Check Log entries
     assert "log" == conversation.audit_items[1].kind
-    data = json.loads(json.loads(conversation.audit_items[1].payload))
+    data = json.loads(conversation.audit_items[1].payload)
     print(data)
 
     assert data["method"] == "POST"
@@ -62,7 +62,7 @@ def test_ttn_to_beeobserver(api_client: ApiClient):
 
     # Check Log entries
     assert "log" == conversation.audit_items[1].kind
-    data = json.loads(json.loads(conversation.audit_items[1].payload))
+    data = json.loads(conversation.audit_items[1].payload)
     print(data)
 
     assert data["method"] == "POST"
@@ -70,3 +70,30 @@ def test_ttn_to_beeobserver(api_client: ApiClient):
     with open(basedir / "ttn-to-beeobserver-egress.json", "r") as f:
         outcome = json.load(f)
         assert json.loads(data["body"]) == outcome
+
+
+def test_ttn_to_beeobserver_with_config(api_client: ApiClient):
+    """
+    Same as `test_ttn_to_beeobserver`, but the BEEP device keys are taken from
+    the processor configuration `fh.config`.
+    """
+
+    with open(basedir / "ttn-to-beeobserver-ingress.json", "r") as f:
+        payload = json.load(f)
+
+    code = wrap_with_async_main(
+        read_code(basedir / "ttn-to-beeobserver-config-recipe.js")
+    )
+    config = {"keys": {"hiveeyes-testdrive-site42-hive2": "okMiKiTwawCauc"}}
+    identifier = api_client.create_processor(code, config=config)
+    response = api_client.run_processor(identifier, method="post", json=payload)
+
+    assert response.status_code == 200
+
+    # Fetch RequestConversation
+    conversation = api_client.get_conversation_from_response(response)
+    data = json.loads(conversation.audit_items[1].payload)
+
+    with open(basedir / "ttn-to-beeobserver-egress.json", "r") as f:
+        outcome = json.load(f)
+        assert json.loads(data["body"]) == outcome
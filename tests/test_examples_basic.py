@@ -46,9 +46,17 @@ def test_error_runtime(api_client: ApiClient):
 
     assert response.status_code == 500
     data = response.json()
+    assert "JSON.foobar is not a function" in data["message"]
+    assert "JSON.foobar is not a function" in data["error"]["message"]
+    assert "JSON.foobar" in data["error"]["stack"]
+    assert data["error"]["line_number"] > 0
+    assert data["conversation_id"] == response.headers["fh-conversation-id"]
 
-    # TODO: That might yield a stacktrace after the HTTP request
-    #       header "FH-Debug: true" will get evaluated appropriately.
+    # the error is recorded on the conversation as well
+    conversation = api_client.get_conversation_from_response(response)
+    assert "failed" == conversation.status
+    assert "error" == conversation.audit_items[-1].kind
+    assert data["error"] == conversation.audit_items[-1].payload
 
 
 def test_json_demo(api_client: ApiClient):
@@ -63,10 +71,10 @@ def test_json_demo(api_client: ApiClient):
 
     # Check Log entries
     assert "log" == conversation.audit_items[1].kind
-    assert 'Stringify: {"a":"b"}' in json.loads(conversation.audit_items[1].payload)
+    assert 'Stringify: {"a":"b"}' in conversation.audit_items[1].payload
 
     assert conversation.audit_items[2].kind == "log"
-    assert "Parse works, too" in json.loads(conversation.audit_items[2].payload)
+    assert "Parse works, too" in conversation.audit_items[2].payload
 
 
 def test_json_request_get(api_client: ApiClient):
@@ -83,7 +91,7 @@ def test_json_request_get(api_client: ApiClient):
 
     # Check Log entries
     assert "log" == conversation.audit_items[1].kind
-    data = json.loads(json.loads(conversation.audit_items[1].payload))
+    data = json.loads(conversation.audit_items[1].payload)
     print(data)
 
     assert data["method"] == "GET"
@@ -104,7 +112,7 @@ def test_json_request_post(api_client: ApiClient):
 
     # Check Log entries
     assert "log" == conversation.audit_items[1].kind
-    data = json.loads(json.loads(conversation.audit_items[1].payload))
+    data = json.loads(conversation.audit_items[1].payload)
     print(data)
 
     assert data["method"] == "POST"
@@ -125,7 +133,7 @@ def test_json_request_post_x_www_form_urlencoded(api_client: ApiClient):
 
     # Check Log entries
     assert "log" == conversation.audit_items[1].kind
-    data = json.loads(json.loads(conversation.audit_items[1].payload))
+    data = json.loads(conversation.audit_items[1].payload)
     print(data)
     assert data["method"] == "POST"
     assert data["body"] == "foo=bar"
@@ -145,7 +153,7 @@ def test_json_request_post_json(api_client: ApiClient):
 
     # Check Log entries
     assert "log" == conversation.audit_items[1].kind
-    data = json.loads(json.loads(conversation.audit_items[1].payload))
+    data = json.loads(conversation.audit_items[1].payload)
     print(data)
     assert data["method"] == "POST"
     assert data["body"] == '{"foo": "bar"}'
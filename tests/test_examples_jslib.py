@@ -24,7 +24,7 @@ def test_modhello(api_client: ApiClient):
 
     # Check Log entries
     assert "log" == conversation.audit_items[1].kind
-    assert "Hello world." == json.loads(conversation.audit_items[1].payload)
+    assert "Hello world." == conversation.audit_items[1].payload
 
 
 def test_modcaesar(api_client: ApiClient):
@@ -44,10 +44,10 @@ def test_modcaesar(api_client: ApiClient):
 
     # Check Log entries
     assert "log" == conversation.audit_items[1].kind
-    assert "encoded: TQXXA IADXP.\n" == json.loads(conversation.audit_items[1].payload)
+    assert "encoded: TQXXA IADXP.\n" == conversation.audit_items[1].payload
 
     assert "log" == conversation.audit_items[2].kind
-    assert "decoded: HELLO WORLD.\n" == json.loads(conversation.audit_items[2].payload)
+    assert "decoded: HELLO WORLD.\n" == conversation.audit_items[2].payload
 
 
 def test_htmlparser(api_client: ApiClient):
@@ -68,7 +68,7 @@ def test_htmlparser(api_client: ApiClient):
 
     # Check log entries
     assert "log" == conversation.audit_items[1].kind
-    data = json.loads(json.loads(conversation.audit_items[1].payload))
+    data = json.loads(conversation.audit_items[1].payload)
     print(data)
 
     assert data == {
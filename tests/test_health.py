@@ -0,0 +1,81 @@
+import threading
+import time
+
+import requests
+from fh.gateway.config import Config
+
+from tests.util import ApiClient, wrap_with_async_main
+
+
+# operational endpoints are not exposed by the gateway, so they are requested
+# from the core directly
+def test_health(api_client: ApiClient, config: Config):
+    response = requests.get(f"{config.core.upstream}/healthz")
+    assert 200 == response.status_code
+    assert {"status": "ok"} == response.json()
+
+
+def test_readiness(api_client: ApiClient, config: Config):
+    response = requests.get(f"{config.core.upstream}/readyz")
+    assert 200 == response.status_code
+
+    readiness = response.json()
+    assert "ok" == readiness["status"]
+    assert {"db", "db_queue", "processor", "processor_queue"} == set(
+        readiness["checks"]
+    )
+    for check in readiness["checks"].values():
+        assert "ok" == check["status"]
+        assert "error" not in check
+
+    assert "latency_ms" in readiness["checks"]["db"]
+    assert "latency_ms" in readiness["checks"]["processor"]
+    assert readiness["checks"]["db_queue"]["capacity"] > 0
+    assert readiness["checks"]["processor_queue"]["depth"] >= 0
+
+
+def test_readiness_during_slow_processor(api_client: ApiClient, config: Config):
+    # keeps the processing manager busy for longer than the ping timeout
+    rp_id = api_client.create_processor(
+        wrap_with_async_main(
+            "await new Promise((resolve) => setTimeout(resolve, 4000));"
+        )
+    )
+    url = f"{config.core.upstream}/processor/{rp_id}/run_with_prelude"
+    slow = threading.Thread(target=requests.get, args=(url,))
+    slow.start()
+    time.sleep(0.5)
+
+    # the busy manager is checked by its progress, so readiness doesn't wait
+    start = time.monotonic()
+    response = requests.get(f"{config.core.upstream}/readyz")
+    assert time.monotonic() - start < 1
+    assert 200 == response.status_code
+    assert "ok" == response.json()["checks"]["processor"]["status"]
+
+    slow.join()
+    response = requests.get(f"{config.core.upstream}/readyz")
+    assert 200 == response.status_code
+    assert 200 == api_client.execute("", prelude=False).status_code
+
+
+def test_dropped_caller_does_not_stop_managers(api_client: ApiClient, config: Config):
+    rp_id = api_client.create_processor(
+        wrap_with_async_main(
+            "await new Promise((resolve) => setTimeout(resolve, 1500));"
+        )
+    )
+    url = f"{config.core.upstream}/processor/{rp_id}/run_with_prelude"
+
+    # the client stops waiting, so the response can't be delivered anymore
+    try:
+        requests.get(url, timeout=0.5)
+    except requests.exceptions.Timeout:
+        pass
+    else:
+        raise AssertionError("the request should have timed out")
+    time.sleep(1.5)
+
+    response = requests.get(f"{config.core.upstream}/readyz")
+    assert 200 == response.status_code
+    assert 200 == api_client.execute("", prelude=False).status_code
@@ -0,0 +1,46 @@
+import json
+from pathlib import Path
+
+from tests.util import ApiClient, read_code, wrap_with_async_main
+
+basedir = Path("examples/07-kv-store")
+
+
+def test_kv_counter(api_client: ApiClient):
+    code = wrap_with_async_main(read_code(basedir / "counter.js"))
+    identifier = api_client.create_processor(code)
+
+    for expected in [1, 2, 3]:
+        response = api_client.run_processor(identifier)
+        assert response.status_code == 200
+
+        conversation = api_client.get_conversation_from_response(response)
+        assert "log" == conversation.audit_items[1].kind
+        assert f"Count: {expected}" == conversation.audit_items[1].payload
+
+
+def test_kv_is_isolated_per_processor(api_client: ApiClient):
+    code = wrap_with_async_main(read_code(basedir / "counter.js"))
+    first = api_client.create_processor(code)
+    second = api_client.create_processor(code)
+
+    api_client.run_processor(first)
+    response = api_client.run_processor(second)
+
+    conversation = api_client.get_conversation_from_response(response)
+    assert "Count: 1" == conversation.audit_items[1].payload
+
+
+def test_kv_list_and_delete(api_client: ApiClient):
+    response = api_client.execute(basedir / "ttl.js")
+    assert response.status_code == 200
+
+    conversation = api_client.get_conversation_from_response(response)
+    assert 3 == len(conversation.audit_items)
+    assert ["token:a", "token:b"] == json.loads(conversation.audit_items[1].payload)
+    assert ["other", "token:a"] == json.loads(conversation.audit_items[2].payload)
+
+
+def test_kv_value_too_large(api_client: ApiClient):
+    response = api_client.execute('await fh.kv.set("big", "x".repeat(70000));')
+    assert response.status_code == 500
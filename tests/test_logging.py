@@ -0,0 +1,79 @@
+from tests.util import ApiClient, RequestProcessor, wrap_with_async_main
+
+
+def test_log_levels_and_fields(api_client: ApiClient):
+    response = api_client.execute(
+        """
+        await fh.log("plain");
+        await fh.log.debug("details", { step: 1 });
+        await fh.log.warn({ reason: "slow" });
+        await fh.log.error("failed", { attempt: 2 });
+        """
+    )
+    assert 200 == response.status_code
+
+    conversation = api_client.get_conversation_from_response(response)
+    logs = [item for item in conversation.audit_items if item.kind == "log"]
+
+    assert ["info", "debug", "warn", "error"] == [log.level for log in logs]
+    assert "plain" == logs[0].payload
+    assert logs[0].fields is None
+    assert {"step": 1} == logs[1].fields
+    assert '{"reason":"slow"}' == logs[2].payload
+    assert {"attempt": 2} == logs[3].fields
+
+
+def test_conversation_level_filter(api_client: ApiClient):
+    response = api_client.execute(
+        """
+        await fh.log.debug("debug");
+        await fh.log.info("info");
+        await fh.log.warn("warn");
+        await fh.log.error("error");
+        """
+    )
+    conversation_id = response.headers["fh-conversation-id"]
+
+    response = api_client.http_client.get(
+        f"/conversation/{conversation_id}", params={"level": "warn"}
+    )
+    assert 200 == response.status_code
+    items = response.json()["audit_items"]
+    assert ["warn", "error"] == [i["payload"] for i in items if i["kind"] == "log"]
+    # non-log items are never filtered
+    assert "request" == items[0]["kind"]
+
+    response = api_client.http_client.get(
+        f"/conversation/{conversation_id}/audit_item", params={"level": "error"}
+    )
+    assert 200 == response.status_code
+    assert ["error"] == [i["payload"] for i in response.json() if i["kind"] == "log"]
+
+    response = api_client.http_client.get(
+        f"/conversation/{conversation_id}", params={"level": "verbose"}
+    )
+    assert 400 == response.status_code
+
+
+def test_processor_min_log_level(api_client: ApiClient):
+    rp = RequestProcessor(
+        id=None,
+        name="testing",
+        runtime="v8",
+        language="javascript",
+        code=wrap_with_async_main(
+            """
+            await fh.log.info("dropped");
+            await fh.log.warn("kept");
+            """
+        ),
+        min_log_level="warn",
+    )
+    rp_id = api_client.create_request_processor(rp).json()["id"]
+
+    response = api_client.run_processor(rp_id)
+    assert 200 == response.status_code
+
+    conversation = api_client.get_conversation_from_response(response)
+    logs = [item for item in conversation.audit_items if item.kind == "log"]
+    assert ["kept"] == [log.payload for log in logs]
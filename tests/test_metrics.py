@@ -0,0 +1,100 @@
+from uuid import uuid4
+
+import requests
+from fh.gateway.config import Config
+
+from tests.util import ApiClient, wrap_with_async_main
+
+
+def get_metrics(config: Config) -> str:
+    # operational endpoints are not exposed by the gateway
+    response = requests.get(f"{config.core.upstream}/metrics")
+    assert 200 == response.status_code
+    assert response.headers["content-type"].startswith("text/plain")
+
+    return response.text
+
+
+def test_processor_metrics(api_client: ApiClient, config: Config):
+    response = api_client.execute('await fh.log("hello");')
+    assert 200 == response.status_code
+    conversation = api_client.get_conversation_from_response(response)
+    processor_id = conversation.request_processor_id
+
+    metrics = get_metrics(config)
+
+    assert (
+        f'fh_http_requests_total{{processor_id="{processor_id}",status="200"}} 1'
+        in metrics
+    )
+    assert (
+        f'fh_http_request_duration_seconds_count{{processor_id="{processor_id}"}} 1'
+        in metrics
+    )
+    assert (
+        f'fh_v8_execution_duration_seconds_count{{processor_id="{processor_id}"}} 1'
+        in metrics
+    )
+    assert 'fh_audit_items_total{kind="log"}' in metrics
+    assert 'fh_queue_depth{queue="db"}' in metrics
+    assert 'fh_queue_depth{queue="processor"}' in metrics
+
+
+def test_failed_request_metrics(api_client: ApiClient, config: Config):
+    response = api_client.execute("throw new Error('boom');")
+    assert 500 == response.status_code
+    processor_id = api_client.get_conversation_from_response(
+        response
+    ).request_processor_id
+
+    metrics = get_metrics(config)
+
+    assert (
+        f'fh_http_requests_total{{processor_id="{processor_id}",status="500"}} 1'
+        in metrics
+    )
+
+
+def test_route_and_pipeline_metrics(api_client: ApiClient, config: Config):
+    processor_id = api_client.create_processor(wrap_with_async_main(""))
+    path = f"/hooks-{uuid4().hex}"
+    response = api_client.http_client.post(
+        f"/admin/processor/{processor_id}/route", json={"path": path}
+    )
+    assert 200 == response.status_code
+    assert 200 == api_client.http_client.get(path).status_code
+
+    response = api_client.http_client.post(
+        "/admin/pipeline",
+        json={"name": "testing", "steps": [{"request_processor_id": processor_id}]},
+    )
+    pipeline_id = response.json()["id"]
+    response = api_client.http_client.post(f"/pipeline/{pipeline_id}/run")
+    assert 200 == response.status_code
+
+    metrics = get_metrics(config)
+
+    # requests to custom routes are counted for the resolved processor
+    assert (
+        f'fh_http_requests_total{{processor_id="{processor_id}",status="200"}} 1'
+        in metrics
+    )
+    assert (
+        f'fh_http_requests_total{{processor_id="{pipeline_id}",status="200"}} 1'
+        in metrics
+    )
+
+
+def test_unknown_ids_are_not_labelled(api_client: ApiClient, config: Config):
+    processor_id = str(uuid4())
+    pipeline_id = str(uuid4())
+    response = api_client.http_client.post(f"/processor/{processor_id}/run")
+    assert 404 == response.status_code
+    response = api_client.http_client.post(f"/pipeline/{pipeline_id}/run")
+    assert 404 == response.status_code
+
+    metrics = get_metrics(config)
+
+    assert 'fh_http_requests_total{processor_id="unknown",status="404"}' in metrics
+    assert processor_id not in metrics
+    assert pipeline_id not in metrics
@@ -0,0 +1,144 @@
+from typing import Dict, Optional
+
+import pytest
+import requests
+from fh.gateway.config import Config
+
+from tests.conftest import FlowHeaterLayer
+from tests.util import wrap_with_async_main
+
+
+@pytest.fixture(scope="module")
+def limited_core(config: Config, fh_core: FlowHeaterLayer):
+    """
+    Spins up a second core with a low limit per processor, so the other tests
+    are not limited. Yields its upstream URL.
+    """
+    port = config.core.port + 100
+    server = FlowHeaterLayer(
+        config,
+        name="fh-core-limited",
+        env={"CORE_PORT": str(port), "CORE_RATE_LIMIT_PROCESSOR": "1,2"},
+    )
+    server.setUp()
+    yield f"http://{config.core.host}:{port}"
+    server.tearDown()
+
+
+@pytest.fixture(scope="module")
+def client_limited_core(config: Config, fh_core: FlowHeaterLayer):
+    """
+    Spins up a core with a low limit per client, which is identified by a
+    header. It trusts no local proxy. Yields its upstream URL.
+    """
+    port = config.core.port + 102
+    server = FlowHeaterLayer(
+        config,
+        name="fh-core-client-limited",
+        env={
+            "CORE_PORT": str(port),
+            "CORE_RATE_LIMIT_CLIENT": "1,1",
+            "CORE_RATE_LIMIT_CLIENT_HEADER": "x-api-key",
+            "CORE_TRUSTED_PROXIES": "192.0.2.1",
+        },
+    )
+    server.setUp()
+    yield f"http://{config.core.host}:{port}"
+    server.tearDown()
+
+
+def create_processor(
+    upstream: str, cors: Optional[Dict] = None, code: str = ""
+) -> str:
+    response = requests.post(
+        f"{upstream}/admin/processor",
+        json={
+            "name": "testing-rate-limit",
+            "runtime": "v8",
+            "language": "javascript",
+            "code": code,
+            "cors": cors,
+        },
+    )
+    assert 200 == response.status_code
+
+    return response.json()["id"]
+
+
+def test_processor_rate_limit(limited_core: str):
+    url = f"{limited_core}/processor/{create_processor(limited_core)}/run"
+
+    # the burst allows two invocations at once
+    assert 200 == requests.get(url).status_code
+    assert 200 == requests.get(url).status_code
+
+    response = requests.get(url)
+    assert 429 == response.status_code
+    assert 429 == response.json()["code"]
+    assert "Rate limit per processor exceeded" == response.json()["message"]
+    # one token is refilled per second
+    assert "1" == response.headers["retry-after"]
+    assert "fh-conversation-id" not in response.headers
+
+
+def test_processor_rate_limit_is_isolated(limited_core: str):
+    limited_url = f"{limited_core}/processor/{create_processor(limited_core)}/run"
+    other_url = f"{limited_core}/processor/{create_processor(limited_core)}/run"
+
+    for _ in range(2):
+        assert 200 == requests.get(limited_url).status_code
+    assert 429 == requests.get(limited_url).status_code
+
+    # every processor has its own bucket
+    assert 200 == requests.get(other_url).status_code
+
+
+def test_rate_limited_cors_requests(limited_core: str):
+    origin = "https://app.example.com"
+    cors = {"allowed_origins": [origin], "allowed_methods": ["POST"]}
+    url = f"{limited_core}/processor/{create_processor(limited_core, cors)}/run"
+
+    # preflight requests do not take a token
+    for _ in range(3):
+        response = requests.options(
+            url,
+            headers={"origin": origin, "access-control-request-method": "POST"},
+        )
+        assert 204 == response.status_code
+
+    for _ in range(2):
+        assert 200 == requests.post(url, headers={"origin": origin}).status_code
+
+    # the rejection is readable by the browser
+    response = requests.post(url, headers={"origin": origin})
+    assert 429 == response.status_code
+    assert origin == response.headers["access-control-allow-origin"]
+    assert "1" == response.headers["retry-after"]
+
+
+def test_chained_invocations_are_rate_limited(limited_core: str):
+    child = create_processor(limited_core)
+    code = wrap_with_async_main(
+        f"""
+        for (let i = 0; i < 3; i++) {{
+            await fh.invoke("{child}", {{}}, {{prelude: false}});
+        }}
+        """
+    )
+    parent = create_processor(limited_core, code=code)
+
+    # the third invocation exceeds the burst of the invoked processor
+    response = requests.get(f"{limited_core}/processor/{parent}/run_with_prelude")
+    assert 500 == response.status_code
+    assert "Rate limit of RequestProcessor" in response.json()["message"]
+
+
+def test_client_key_of_untrusted_peers_is_ignored(client_limited_core: str):
+    url = f"{client_limited_core}/processor/{create_processor(client_limited_core)}/run"
+
+    assert 200 == requests.get(url, headers={"x-api-key": "first"}).status_code
+
+    # clients must not get a new bucket by sending a new key
+    response = requests.get(url, headers={"x-api-key": "second"})
+    assert 429 == response.status_code
+    assert "Rate limit per client exceeded" == response.json()["message"]
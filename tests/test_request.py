@@ -0,0 +1,196 @@
+import http.client
+from urllib.parse import urlsplit
+
+import pytest
+import requests
+from fh.gateway.config import Config
+
+from tests.conftest import FlowHeaterLayer
+from tests.util import ApiClient
+
+
+@pytest.fixture(scope="module")
+def untrusting_core(config: Config, fh_core: FlowHeaterLayer):
+    """
+    Spins up a second core, which does not trust the address of the tests as
+    proxy. Yields its upstream URL.
+    """
+    port = config.core.port + 101
+    server = FlowHeaterLayer(
+        config,
+        name="fh-core-untrusting",
+        env={"CORE_PORT": str(port), "CORE_TRUSTED_PROXIES": "192.0.2.1"},
+    )
+    server.setUp()
+    yield f"http://{config.core.host}:{port}"
+    server.tearDown()
+
+
+def run(api_client: ApiClient, **kwargs):
+    """
+    Runs an empty processor and returns the recorded incoming request.
+    """
+    response = api_client.execute("", **kwargs)
+    assert 200 == response.status_code
+
+    conversation = api_client.get_conversation_from_response(response)
+    return conversation.audit_items[0].payload
+
+
+def test_query_params(api_client: ApiClient):
+    request = run(api_client, params=[("page", "1"), ("tag", "a b"), ("tag", "c")])
+
+    assert "page=1&tag=a+b&tag=c" == request["query"]
+    assert {"page": ["1"], "tag": ["a b", "c"]} == request["query_params"]
+    assert request["json"] is None
+    assert request["form"] is None
+
+
+def test_json_body(api_client: ApiClient):
+    request = run(api_client, method="post", json={"hello": ["world"]})
+    assert {"hello": ["world"]} == request["json"]
+
+    # invalid JSON is only available as raw body
+    request = run(
+        api_client,
+        method="post",
+        data="{invalid",
+        headers={"content-type": "application/json"},
+    )
+    assert request["json"] is None
+    assert "{invalid" == request["body"]
+
+
+def test_urlencoded_form(api_client: ApiClient):
+    request = run(api_client, method="post", data={"payload": "Hello world.", "n": "1"})
+
+    assert {"payload": ["Hello world."], "n": ["1"]} == request["form"]
+
+
+def test_multipart_form(api_client: ApiClient):
+    request = run(
+        api_client,
+        method="post",
+        data={"name": "fh"},
+        files={"upload": ("notes.txt", "line 1\r\nline 2", "text/plain")},
+    )
+
+    assert {"name": ["fh"], "upload": ["line 1\r\nline 2"]} == request["form"]
+
+
+def test_parsed_request_in_main(api_client: ApiClient):
+    response = api_client.execute(
+        """
+        await fh.log(`${request.query_params.user[0]}: ${request.json.message}`);
+        """,
+        method="post",
+        params={"user": "tim"},
+        json={"message": "hi"},
+    )
+
+    conversation = api_client.get_conversation_from_response(response)
+    assert "tim: hi" == conversation.audit_items[1].payload
+
+
+def test_client_information(api_client: ApiClient):
+    response = api_client.execute("")
+    assert 200 == response.status_code
+
+    conversation = api_client.get_conversation_from_response(response)
+    request = conversation.audit_items[0].payload
+
+    assert "127.0.0.1" == request["remote_addr"]
+    # the gateway is a trusted proxy, so this is the host requested from it
+    assert "testserver" == request["host"]
+    assert "http" == request["scheme"]
+    assert conversation.request_processor_id == request["processor_id"]
+
+
+def test_forwarded_headers_of_trusted_gateway(api_client: ApiClient):
+    request = run(
+        api_client,
+        headers={
+            "x-forwarded-for": "203.0.113.7",
+            "x-forwarded-host": "example.com",
+            "x-forwarded-proto": "https",
+        },
+    )
+
+    assert "203.0.113.7" == request["remote_addr"]
+    # the gateway overwrites the host with the one requested from it
+    assert "testserver" == request["host"]
+    assert "https" == request["scheme"]
+
+
+def test_forwarded_headers_of_untrusted_peers(untrusting_core: str):
+    response = requests.post(
+        f"{untrusting_core}/admin/processor",
+        json={
+            "name": "testing",
+            "runtime": "v8",
+            "language": "javascript",
+            "code": "",
+        },
+    )
+    assert 200 == response.status_code
+
+    response = requests.get(
+        f"{untrusting_core}/processor/{response.json()['id']}/run",
+        headers={
+            "x-forwarded-for": "203.0.113.7",
+            "x-forwarded-host": "example.com",
+            "x-forwarded-proto": "https",
+        },
+    )
+    assert 200 == response.status_code
+
+    conversation_id = response.headers["fh-conversation-id"]
+    response = requests.get(f"{untrusting_core}/conversation/{conversation_id}")
+    request = response.json()["audit_items"][0]["payload"]
+
+    assert "127.0.0.1" == request["remote_addr"]
+    assert "example.com" != request["host"]
+    assert "http" == request["scheme"]
+
+
+def test_client_information_in_main(api_client: ApiClient):
+    response = api_client.execute(
+        """
+        await fh.log(`${request.scheme} ${request.remote_addr} ${request.processor_id}`);
+        """
+    )
+
+    conversation = api_client.get_conversation_from_response(response)
+    expected = f"http 127.0.0.1 {conversation.request_processor_id}"
+    assert expected == conversation.audit_items[1].payload
+
+
+def test_body_limit(api_client: ApiClient):
+    # the default limit is 2 MiB
+    response = api_client.execute("", method="post", data="x" * (3 * 1024 * 1024))
+
+    assert 413 == response.status_code
+    assert 413 == response.json()["code"]
+    assert "fh-conversation-id" not in response.headers
+
+
+def test_body_limit_chunked(api_client: ApiClient, config: Config):
+    rp_id = api_client.create_processor("")
+
+    # the gateway buffers bodies, so the chunked body is sent to the core directly
+    upstream = urlsplit(config.core.upstream)
+    conn = http.client.HTTPConnection(upstream.hostname, upstream.port, timeout=10)
+    conn.putrequest("POST", f"/processor/{rp_id}/run")
+    conn.putheader("Transfer-Encoding", "chunked")
+    conn.endheaders()
+
+    # 33 chunks of 64 KiB exceed the default limit of 2 MiB. The body is never
+    # finished, so the limit has to be enforced while it is streamed.
+    chunk = b"x" * (64 * 1024)
+    for _ in range(33):
+        conn.send(b"%x\r\n%s\r\n" % (len(chunk), chunk))
+
+    response = conn.getresponse()
+    assert 413 == response.status
+    assert "fh-conversation-id" not in response.headers
+    conn.close()
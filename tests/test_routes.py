@@ -0,0 +1,208 @@
+from typing import Dict
+from uuid import uuid4
+
+import pytest
+import requests
+from fh.gateway.config import Config
+
+from tests.util import ApiClient, wrap_with_async_main
+
+CODE = wrap_with_async_main("")
+
+
+def create_route(
+    api_client: ApiClient, identifier: str, route: Dict
+) -> requests.Response:
+    return api_client.http_client.post(
+        f"/admin/processor/{identifier}/route", json=route
+    )
+
+
+def unique_prefix() -> str:
+    """
+    Routes are global, so every test uses its own path to not conflict with
+    routes of previous runs.
+    """
+    return f"/hooks-{uuid4().hex}"
+
+
+def incoming_request(api_client: ApiClient, response: requests.Response) -> Dict:
+    conversation = api_client.get_conversation_from_response(response)
+    return conversation.audit_items[0].payload
+
+
+@pytest.mark.admin
+def test_route_with_path_params(api_client: ApiClient):
+    identifier = api_client.create_processor(CODE)
+    prefix = unique_prefix()
+
+    response = create_route(
+        api_client, identifier, {"path": f"{prefix}/ttn/{{device}}"}
+    )
+    assert 200 == response.status_code
+    route = response.json()
+    assert identifier == route["request_processor_id"]
+    assert route["host"] is None
+    assert [] == route["methods"]
+    assert route["prelude"] is True
+
+    response = api_client.http_client.post(
+        f"{prefix}/ttn/sensor%201?page=1", json={"hello": "world"}
+    )
+    assert 200 == response.status_code
+
+    request = incoming_request(api_client, response)
+    assert {"device": "sensor 1"} == request["path_params"]
+    assert f"{prefix}/ttn/sensor%201" == request["path"]
+    assert identifier == request["processor_id"]
+    assert {"hello": "world"} == request["json"]
+
+    # the route matches whole segments only
+    response = api_client.http_client.post(f"{prefix}/ttn/sensor/1")
+    assert 404 == response.status_code
+
+    response = api_client.http_client.get(f"/admin/processor/{identifier}/route")
+    assert 200 == response.status_code
+    assert [route["id"]] == [r["id"] for r in response.json()]
+
+    response = api_client.http_client.delete(f"/admin/route/{route['id']}")
+    assert 200 == response.status_code
+
+    response = api_client.http_client.post(f"{prefix}/ttn/sensor")
+    assert 404 == response.status_code
+
+
+@pytest.mark.admin
+def test_route_precedence(api_client: ApiClient):
+    literal = api_client.create_processor(CODE)
+    param = api_client.create_processor(CODE)
+    prefix = unique_prefix()
+
+    response = create_route(api_client, param, {"path": f"{prefix}/{{name}}"})
+    assert 200 == response.status_code
+    response = create_route(api_client, literal, {"path": f"{prefix}/status"})
+    assert 200 == response.status_code
+
+    response = api_client.http_client.get(f"{prefix}/status")
+    assert literal == incoming_request(api_client, response)["processor_id"]
+
+    response = api_client.http_client.get(f"{prefix}/other")
+    assert param == incoming_request(api_client, response)["processor_id"]
+
+
+@pytest.mark.admin
+def test_route_methods(api_client: ApiClient):
+    identifier = api_client.create_processor(CODE)
+    prefix = unique_prefix()
+
+    response = create_route(
+        api_client, identifier, {"path": prefix, "methods": ["post", "PUT"]}
+    )
+    assert 200 == response.status_code
+    assert ["POST", "PUT"] == response.json()["methods"]
+
+    assert 200 == api_client.http_client.post(prefix).status_code
+
+    response = api_client.http_client.get(prefix)
+    assert 405 == response.status_code
+    assert "POST, PUT" == response.headers["allow"]
+
+
+@pytest.mark.admin
+def test_route_conflict(api_client: ApiClient):
+    identifier = api_client.create_processor(CODE)
+    other = api_client.create_processor(CODE)
+    prefix = unique_prefix()
+
+    route = {"path": f"{prefix}/{{device}}", "methods": ["POST"]}
+    assert 200 == create_route(api_client, identifier, route).status_code
+
+    # same shape and an overlapping method
+    route = {"path": f"{prefix}/{{id}}/", "methods": ["POST", "PUT"]}
+    response = create_route(api_client, other, route)
+    assert 409 == response.status_code
+
+    # disjoint methods do not conflict
+    route = {"path": f"{prefix}/{{id}}", "methods": ["GET"]}
+    assert 200 == create_route(api_client, other, route).status_code
+
+
+@pytest.mark.admin
+@pytest.mark.parametrize(
+    "path",
+    [
+        "no-slash",
+        "/processor/custom",
+        "/hooks/{a}/{a}",
+        "/hooks/device-{id}",
+        "/hooks//ttn",
+    ],
+)
+def test_invalid_route(api_client: ApiClient, path: str):
+    identifier = api_client.create_processor(CODE)
+
+    response = create_route(api_client, identifier, {"path": path})
+    assert 400 == response.status_code
+
+
+@pytest.mark.admin
+def test_route_of_unknown_processor(api_client: ApiClient):
+    response = create_route(
+        api_client,
+        "00000000-0000-0000-0000-000000000000",
+        {"path": unique_prefix()},
+    )
+    assert 404 == response.status_code
+
+
+# sent to the core directly, so the host header is used as is
+@pytest.mark.admin
+def test_route_with_host(api_client: ApiClient, config: Config):
+    identifier = api_client.create_processor(CODE)
+    prefix = unique_prefix()
+
+    route = {"host": "Hooks.Example.com", "path": prefix}
+    response = create_route(api_client, identifier, route)
+    assert 200 == response.status_code
+    assert "hooks.example.com" == response.json()["host"]
+
+    def run(host: str) -> requests.Response:
+        return requests.get(f"{config.core.upstream}{prefix}", headers={"host": host})
+
+    assert 200 == run("hooks.example.com:8080").status_code
+    assert 404 == run("other.example.com").status_code
+
+
+@pytest.mark.admin
+def test_route_with_host_through_gateway(api_client: ApiClient):
+    identifier = api_client.create_processor(CODE)
+    prefix = unique_prefix()
+
+    route = {"host": "hooks.example.com", "path": prefix}
+    assert 200 == create_route(api_client, identifier, route).status_code
+
+    # the gateway passes the host in `X-Forwarded-Host` to the core, which
+    # trusts it as proxy
+    def run(host: str) -> requests.Response:
+        return api_client.http_client.get(prefix, headers={"host": host})
+
+    assert 200 == run("hooks.example.com").status_code
+    assert 404 == run("other.example.com").status_code
+
+
+@pytest.mark.parametrize(
+    "method,path",
+    [
+        ("get", "/metrics"),
+        ("get", "/healthz"),
+        ("get", "/readyz"),
+        ("patch", "/admin/processor"),
+        ("post", "/conversation/00000000-0000-0000-0000-000000000000"),
+    ],
+)
+def test_gateway_rejects_built_in_endpoints(
+    api_client: ApiClient, method: str, path: str
+):
+    # only the endpoints of the gateway itself proxy to the built-in ones
+    response = api_client.http_client.request(method, path)
+    assert 404 == response.status_code
@@ -0,0 +1,70 @@
+import re
+import threading
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+import pytest
+
+from tests.util import ApiClient
+
+TRACEPARENT = re.compile(r"^00-([0-9a-f]{32})-([0-9a-f]{16})-0[01]$")
+INCOMING_TRACE_ID = "4bf92f3577b34da6a3ce929d0e0e4736"
+INCOMING_TRACEPARENT = f"00-{INCOMING_TRACE_ID}-00f067aa0ba902b7-01"
+
+
+class CaptureHandler(BaseHTTPRequestHandler):
+    """
+    Records the headers of all received requests.
+    """
+
+    def do_POST(self):
+        self.server.captured.append({k.lower(): v for k, v in self.headers.items()})
+        self.rfile.read(int(self.headers.get("content-length", 0)))
+
+        self.send_response(200)
+        self.send_header("content-type", "application/json")
+        self.end_headers()
+        self.wfile.write(b"{}")
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def capture_server():
+    server = HTTPServer(("127.0.0.1", 0), CaptureHandler)
+    server.captured = []
+    thread = threading.Thread(target=server.serve_forever, daemon=True)
+    thread.start()
+
+    yield server
+
+    server.shutdown()
+
+
+def fetch_code(server: HTTPServer) -> str:
+    return f"""
+        await fetch("http://127.0.0.1:{server.server_port}/", {{
+            method: "POST",
+            body: "data",
+        }});
+    """
+
+
+def test_outbound_requests_carry_traceparent(api_client: ApiClient, capture_server):
+    response = api_client.execute(fetch_code(capture_server))
+    assert 200 == response.status_code
+
+    assert 1 == len(capture_server.captured)
+    assert TRACEPARENT.match(capture_server.captured[0]["traceparent"])
+
+
+def test_incoming_trace_is_continued(api_client: ApiClient, capture_server):
+    response = api_client.execute(
+        fetch_code(capture_server), headers={"traceparent": INCOMING_TRACEPARENT}
+    )
+    assert 200 == response.status_code
+
+    match = TRACEPARENT.match(capture_server.captured[0]["traceparent"])
+    assert match
+    assert INCOMING_TRACE_ID == match.group(1)
+    assert "00f067aa0ba902b7" != match.group(2)
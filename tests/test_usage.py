@@ -0,0 +1,74 @@
+import pytest
+
+from tests.util import ApiClient, RequestProcessor, wrap_with_async_main
+
+CODE = wrap_with_async_main('await fh.log("hello");')
+
+
+def get_usage(api_client: ApiClient, identifier: str) -> dict:
+    response = api_client.http_client.get(f"/admin/processor/{identifier}/usage")
+    assert 200 == response.status_code
+
+    return response.json()
+
+
+@pytest.mark.admin
+def test_usage_is_accounted(api_client: ApiClient):
+    identifier = api_client.create_processor(CODE)
+    assert [] == get_usage(api_client, identifier)["days"]
+
+    for _ in range(2):
+        assert 200 == api_client.run_processor(identifier).status_code
+
+    usage = get_usage(api_client, identifier)
+    assert identifier == usage["request_processor_id"]
+    assert 1 == len(usage["days"])
+
+    today = usage["days"][0]
+    assert 2 == today["invocations"]
+    assert today["cpu_ms"] >= 0
+    assert 0 == today["outbound_requests"]
+    assert today["audit_bytes"] > 0
+    assert today["audit_bytes"] == usage["storage_bytes"]
+
+
+@pytest.mark.admin
+def test_cpu_time_excludes_waiting(api_client: ApiClient):
+    code = wrap_with_async_main(
+        "await new Promise((resolve) => setTimeout(resolve, 1000));"
+    )
+    identifier = api_client.create_processor(code)
+    assert 200 == api_client.run_processor(identifier).status_code
+
+    today = get_usage(api_client, identifier)["days"][0]
+    assert today["cpu_ms"] < 1000
+
+
+@pytest.mark.admin
+def test_usage_of_unknown_processor(api_client: ApiClient):
+    response = api_client.http_client.get(
+        "/admin/processor/00000000-0000-0000-0000-000000000000/usage"
+    )
+    assert 404 == response.status_code
+
+
+@pytest.mark.admin
+def test_quota_rejects_invocations(api_client: ApiClient):
+    rp = RequestProcessor(
+        id=None,
+        name="testing-quota",
+        runtime="v8",
+        language="javascript",
+        code=CODE,
+        quota={"daily_invocations": 1},
+    )
+    identifier = api_client.create_request_processor(rp).json()["id"]
+
+    assert 200 == api_client.run_processor(identifier).status_code
+
+    response = api_client.run_processor(identifier)
+    assert 429 == response.status_code
+    assert "Daily limit of 1 invocations reached" in response.json()["message"]
+
+    usage = get_usage(api_client, identifier)
+    assert 1 == usage["days"][0]["invocations"]
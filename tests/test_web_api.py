@@ -0,0 +1,213 @@
+import json
+import threading
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+import pytest
+from fh.gateway.config import Config
+
+from tests.util import ApiClient, wrap_with_async_main
+
+
+BINARY_BODY = bytes([0, 159, 255, 104, 105])
+
+
+class BinaryHandler(BaseHTTPRequestHandler):
+    """
+    Responds with a body, which is not valid UTF-8.
+    """
+
+    def do_GET(self):
+        self.send_response(200)
+        self.send_header("content-type", "application/octet-stream")
+        self.send_header("content-length", str(len(BINARY_BODY)))
+        self.end_headers()
+        self.wfile.write(BINARY_BODY)
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def binary_server():
+    server = HTTPServer(("127.0.0.1", 0), BinaryHandler)
+    thread = threading.Thread(target=server.serve_forever, daemon=True)
+    thread.start()
+
+    yield server
+
+    server.shutdown()
+
+
+def get_log_payloads(api_client: ApiClient, response):
+    conversation = api_client.get_conversation_from_response(response)
+    return [item.payload for item in conversation.audit_items if item.kind == "log"]
+
+
+def test_url_and_search_params(api_client: ApiClient):
+    response = api_client.execute(
+        """
+        const url = new URL("/path?a=1&b=two+words#top", "https://example.org:8443");
+        url.searchParams.append("c", "x&y");
+
+        console.log(url.host, url.pathname, url.hash, url.searchParams.get("b"));
+        console.log(url.href);
+        console.log(new URLSearchParams({ payload: "Hello world." }).toString());
+        """,
+        prelude=False,
+    )
+    assert 200 == response.status_code
+
+    assert [
+        "example.org:8443 /path #top two words",
+        "https://example.org:8443/path?a=1&b=two+words&c=x%26y#top",
+        "payload=Hello+world.",
+    ] == get_log_payloads(api_client, response)
+
+
+def test_encoding(api_client: ApiClient):
+    response = api_client.execute(
+        """
+        const bytes = new TextEncoder().encode("Grüße");
+        console.log(bytes.length, new TextDecoder().decode(bytes));
+        console.log(btoa("Hello, World"), atob("SGVsbG8sIFdvcmxk"));
+        """,
+        prelude=False,
+    )
+
+    assert ["7 Grüße", "SGVsbG8sIFdvcmxk Hello, World"] == get_log_payloads(
+        api_client, response
+    )
+
+
+def test_timers_and_crypto(api_client: ApiClient):
+    response = api_client.execute(
+        """
+        const cancelled = setTimeout(() => console.log("cancelled"), 10);
+        clearTimeout(cancelled);
+
+        let ticks = 0;
+        const interval = setInterval(() => {
+            ticks += 1;
+            if (ticks === 3) {
+                clearInterval(interval);
+                console.log(`ticks: ${ticks}`);
+            }
+        }, 5);
+
+        await new Promise((resolve) => setTimeout(resolve, 50));
+
+        const uuid = crypto.randomUUID();
+        const values = crypto.getRandomValues(new Uint8Array(16));
+        console.log(/^[0-9a-f-]{36}$/.test(uuid), values.length);
+        """
+    )
+    assert 200 == response.status_code
+
+    assert ["ticks: 3", "true 16"] == get_log_payloads(api_client, response)
+
+
+def test_fetch(api_client: ApiClient, config: Config):
+    # without prelude, the request body is echoed back
+    echo_id = api_client.create_processor("")
+
+    response = api_client.execute(
+        f"""
+        const response = await fetch("{config.core.upstream}/processor/{echo_id}/run", {{
+            method: "POST",
+            headers: {{ "content-type": "application/json" }},
+            body: JSON.stringify({{ hello: "world" }}),
+        }});
+        const data = await response.json();
+
+        console.log(response.status, response.ok, JSON.parse(data.body).hello);
+        """
+    )
+    assert 200 == response.status_code
+
+    conversation = api_client.get_conversation_from_response(response)
+    kinds = [item.kind for item in conversation.audit_items]
+    assert ["request", "request", "response", "log"] == kinds
+    assert "POST" == conversation.audit_items[1].payload["method"]
+    assert "200 true world" == conversation.audit_items[3].payload
+
+
+def test_fetch_binary_body(api_client: ApiClient, binary_server: HTTPServer):
+    url = f"http://127.0.0.1:{binary_server.server_port}/"
+    response = api_client.execute(
+        f"""
+        const bytes = new Uint8Array(await (await fetch("{url}")).arrayBuffer());
+        const text = await (await fetch("{url}")).text();
+
+        console.log(Array.from(bytes).join(","));
+        console.log(text.endsWith("hi"), text.includes("\\ufffd"));
+        """
+    )
+    assert 200 == response.status_code
+
+    assert [
+        ",".join(str(b) for b in BINARY_BODY),
+        "true true",
+    ] == get_log_payloads(api_client, response)
+
+
+def test_concurrent_fetches(api_client: ApiClient, config: Config):
+    echo_id = api_client.create_processor("")
+    url = f"{config.core.upstream}/processor/{echo_id}/run"
+
+    response = api_client.execute(
+        f"""
+        const post = (body) => fetch("{url}", {{method: "POST", body}});
+        const responses = await Promise.all([post("first"), post("second")]);
+        const bodies = await Promise.all(responses.map((r) => r.json()));
+
+        console.log(bodies.map((data) => data.body).join(" "));
+        """
+    )
+    assert 200 == response.status_code
+
+    conversation = api_client.get_conversation_from_response(response)
+    kinds = [item.kind for item in conversation.audit_items]
+    assert 2 == kinds.count("request") - 1
+    assert 2 == kinds.count("response")
+    assert ["first second"] == get_log_payloads(api_client, response)
+
+
+def test_outbound_requests_send_only_given_headers(
+    api_client: ApiClient, config: Config
+):
+    # responds with the names of the received headers
+    target_id = api_client.create_processor(
+        wrap_with_async_main(
+            """
+            await fh.respond_with({
+                code: 200,
+                headers: {},
+                body: JSON.stringify(Object.keys(request.headers)),
+                version: "HTTP/1.1",
+            });
+            """
+        )
+    )
+    url = f"{config.core.upstream}/processor/{target_id}/run_with_prelude"
+
+    # the incoming request is passed on, but its credentials must not be sent
+    response = api_client.execute(
+        f"""
+        const dispatched = await fh.dispatch_request(
+            "{url}", request, {{headers: {{"x-given": ["dispatch"]}}}}
+        );
+        const fetched = await fetch("{url}", {{headers: {{"x-given": "fetch"}}}});
+        console.log(JSON.parse(dispatched.body).body);
+        console.log((await fetched.json()).body);
+        """,
+        headers={"Authorization": "Bearer secret", "Cookie": "session=secret"},
+    )
+    assert 200 == response.status_code
+
+    logs = get_log_payloads(api_client, response)
+    assert 2 == len(logs)
+    for payload in logs:
+        names = [name.lower() for name in json.loads(payload)]
+        assert "x-given" in names
+        assert "authorization" not in names
+        assert "cookie" not in names
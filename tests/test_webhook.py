@@ -0,0 +1,121 @@
+import hashlib
+import hmac
+import time
+
+from tests.util import ApiClient, RequestProcessor, wrap_with_async_main
+
+SECRET = "whsec_test"
+PAYLOAD = '{"action": "opened"}'
+
+
+def create_webhook_processor(api_client: ApiClient, provider: str, **options) -> str:
+    rp = RequestProcessor(
+        id=None,
+        name="webhook",
+        runtime="v8",
+        language="javascript",
+        code=wrap_with_async_main('await fh.log("accepted");'),
+        webhook_verification={
+            "provider": provider,
+            "secret": "SIGNING_SECRET",
+            **options,
+        },
+    )
+    rp_id = api_client.create_request_processor(rp).json()["id"]
+    api_client.set_processor_secret(rp_id, "SIGNING_SECRET", SECRET)
+
+    return rp_id
+
+
+def sign(data: str) -> str:
+    return hmac.new(SECRET.encode(), data.encode(), hashlib.sha256).hexdigest()
+
+
+def test_github_signature(api_client: ApiClient):
+    rp_id = create_webhook_processor(api_client, "github")
+
+    response = api_client.run_processor(
+        rp_id,
+        method="post",
+        data=PAYLOAD,
+        headers={"X-Hub-Signature-256": f"sha256={sign(PAYLOAD)}"},
+    )
+    assert 200 == response.status_code
+
+    conversation = api_client.get_conversation_from_response(response)
+    assert "accepted" == conversation.audit_items[1].payload
+
+
+def test_invalid_signature_is_rejected(api_client: ApiClient):
+    rp_id = create_webhook_processor(api_client, "github")
+
+    response = api_client.run_processor(
+        rp_id,
+        method="post",
+        data=PAYLOAD,
+        headers={"X-Hub-Signature-256": f"sha256={sign('forged')}"},
+    )
+    assert 401 == response.status_code
+    data = response.json()
+    assert "Webhook verification failed: Signature does not match" == data["message"]
+    assert data["conversation_id"] == response.headers["fh-conversation-id"]
+
+    # the rejection is recorded, but the code never ran
+    conversation = api_client.get_conversation_from_response(response)
+    assert "failed" == conversation.status
+    assert 401 == conversation.status_code
+    assert ["request", "error"] == [i.kind for i in conversation.audit_items]
+
+    response = api_client.run_processor(rp_id, method="post", data=PAYLOAD)
+    assert 401 == response.status_code
+    assert "Missing header 'x-hub-signature-256'" in response.json()["message"]
+
+
+def test_stripe_signature(api_client: ApiClient):
+    rp_id = create_webhook_processor(api_client, "stripe")
+
+    timestamp = int(time.time())
+    signature = sign(f"{timestamp}.{PAYLOAD}")
+    response = api_client.run_processor(
+        rp_id,
+        method="post",
+        data=PAYLOAD,
+        headers={"Stripe-Signature": f"t={timestamp},v1={signature}"},
+    )
+    assert 200 == response.status_code
+
+    # replayed requests are outside of the tolerance
+    timestamp = timestamp - 3600
+    signature = sign(f"{timestamp}.{PAYLOAD}")
+    response = api_client.run_processor(
+        rp_id,
+        method="post",
+        data=PAYLOAD,
+        headers={"Stripe-Signature": f"t={timestamp},v1={signature}"},
+    )
+    assert 401 == response.status_code
+
+
+def test_slack_signature(api_client: ApiClient):
+    rp_id = create_webhook_processor(api_client, "slack")
+
+    timestamp = str(int(time.time()))
+    response = api_client.run_processor(
+        rp_id,
+        method="post",
+        data=PAYLOAD,
+        headers={
+            "X-Slack-Request-Timestamp": timestamp,
+            "X-Slack-Signature": f"v0={sign(f'v0:{timestamp}:{PAYLOAD}')}",
+        },
+    )
+    assert 200 == response.status_code
+
+
+def test_generic_signature(api_client: ApiClient):
+    rp_id = create_webhook_processor(api_client, "generic", header="X-TTN-Signature")
+
+    response = api_client.run_processor(
+        rp_id, method="post", data=PAYLOAD, headers={"X-TTN-Signature": sign(PAYLOAD)}
+    )
+    assert 200 == response.status_code
@@ -1,4 +1,4 @@
-from dataclasses import asdict, dataclass
+from dataclasses import asdict, dataclass, field
 from pathlib import Path
 from typing import Dict, List, Optional, Tuple, Union
 
@@ -18,6 +18,11 @@ class RequestProcessor:
     runtime: str
     language: str
     code: str
+    config: Dict = field(default_factory=dict)
+    min_log_level: str = "debug"
+    webhook_verification: Optional[Dict] = None
+    quota: Optional[Dict] = None
+    cors: Optional[Dict] = None
 
 
 @dataclass
@@ -33,6 +38,8 @@ class AuditItem:
     payload: Union[str, Dict]
     inc: Optional[int]
     request_id: Optional[str]
+    level: Optional[str] = None
+    fields: Optional[Dict] = None
 
 
 @dataclass
@@ -44,7 +51,14 @@ class RequestConversation:
     id: str
     request_processor_id: str
     created_at: str
+    status: str
+    finished_at: Optional[str]
+    duration_ms: Optional[int]
+    status_code: Optional[int]
+    error: Optional[str]
     audit_items: List[AuditItem]
+    parent_id: Optional[str] = None
+    children: List[str] = field(default_factory=list)
 
 
 def read_code(filename_or_code: Union[Path, str]) -> str:
@@ -83,10 +97,11 @@ class ApiClient:
     def __init__(self, http_client: TestClient):
         self.http_client = http_client
 
-    def create_processor(self, code: str):
+    def create_processor(self, code: str, config: Optional[Dict] = None):
         """
-        Creates a Request Processor with the given code string. Convenience
-        wrapper for the `create_request_processor()` method.
+        Creates a Request Processor with the given code string and optional
+        configuration object. Convenience wrapper for the
+        `create_request_processor()` method.
         """
         rp = RequestProcessor(
             id=None,
@@ -94,6 +109,7 @@ class ApiClient:
             runtime="v8",
             language="javascript",
             code=code,
+            config=config or {},
         )
 
         response = self.create_request_processor(rp)
@@ -113,6 +129,8 @@ class ApiClient:
         assert data["runtime"] == rp.runtime
         assert data["language"] == rp.language
         assert data["code"] == rp.code
+        assert data["config"] == rp.config
+        assert data["min_log_level"] == rp.min_log_level
 
         return response
 
@@ -172,3 +190,13 @@ class ApiClient:
         then fetches the Request Conversation from the API.
         """
         return self.get_request_conversation(response.headers["fh-conversation-id"])[0]
+
+    def set_processor_secret(
+        self, processor_id: str, name: str, value: str
+    ) -> requests.Response:
+        """
+        Creates or overwrites a secret of a Request Processor.
+        """
+        return self.http_client.put(
+            f"/admin/processor/{processor_id}/secret/{name}", json={"value": value}
+        )